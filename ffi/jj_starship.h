@@ -0,0 +1,48 @@
+/*
+ * jj-starship C ABI
+ *
+ * Build: cargo build --profile release-ffi -p jj-starship-ffi
+ * Link:  -ljj_starship_ffi (target/release-ffi/libjj_starship_ffi.{so,dylib})
+ *
+ * The release-ffi profile unwinds, so a panic inside the library is caught
+ * and reported as NULL; under panic = "abort" it would abort the host.
+ */
+#ifndef JJ_STARSHIP_H
+#define JJ_STARSHIP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Render the prompt for the repo containing `path`.
+ *
+ * `options` is NULL or a ';'-separated list of CLI long options without the
+ * leading dashes, applied in order to the defaults, e.g.
+ * "output=tmux;no-color;separator= | ". Supported: truncate-name, id-length,
+ * ancestor-bookmark-depth, bookmarks-display-limit, output, escapes, segment,
+ * separator, bookmark-separator, symbol-set, symbol, jj-symbol, git-symbol,
+ * hg-symbol, sl-symbol, pijul-symbol, no-symbol, no-color, compact,
+ * read-only, no-cache.
+ *
+ * jj-starship's own environment variables (JJ_STARSHIP_*) and config file
+ * are not consulted. jj's config still is, as in the CLI: JJ_CONFIG, or the
+ * files under HOME and XDG_CONFIG_HOME, plus the repo's config. Git reads
+ * its usual global and system config. JJ snapshots are cached in the
+ * default directory, $XDG_CACHE_HOME/jj-starship or ~/.cache/jj-starship,
+ * and kept 30 days without a rewrite; no-cache turns the cache off.
+ *
+ * Returns a NUL-terminated string to release with jj_starship_free, or NULL
+ * outside a repo, on collection failure, for an invalid option, or if the
+ * library panicked.
+ */
+char *jj_starship_prompt(const char *path, const char *options);
+
+/* Release a string returned by jj_starship_prompt. NULL is a no-op. */
+void jj_starship_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* JJ_STARSHIP_H */
@@ -0,0 +1,188 @@
+//! C ABI for embedding jj-starship without spawning a process
+//!
+//! See `jj_starship.h` for the contract. Strings returned by
+//! `jj_starship_prompt` are owned by the caller and released with
+//! `jj_starship_free`; nothing else crosses the boundary.
+
+use jj_starship::output::{self, SymbolOverride, SymbolSet};
+use jj_starship::timings::Timings;
+use jj_starship::{Config, detect, try_collect};
+use std::borrow::Cow;
+use std::ffi::{CStr, CString, c_char};
+use std::panic;
+use std::path::Path;
+use std::ptr;
+
+/// Render the prompt for the repo containing `path`
+///
+/// `options` is NULL or a `;`-separated list of CLI long options without
+/// the leading dashes, applied in order to the defaults: `output=tmux;no-color`.
+/// jj-starship's own environment variables and config file are not consulted.
+/// Returns NULL outside a repo, on collection failure, for an unknown or
+/// malformed option, or if collection panics (a panic never unwinds into the
+/// caller).
+///
+/// # Safety
+///
+/// `path` and `options` must each be NULL or point to a NUL-terminated string
+/// that stays valid for the duration of the call.
+#[unsafe(no_mangle)]
+#[allow(unsafe_code)]
+pub unsafe extern "C" fn jj_starship_prompt(
+    path: *const c_char,
+    options: *const c_char,
+) -> *mut c_char {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+    // SAFETY: both pointers are non-null here and valid per the contract
+    let path = unsafe { CStr::from_ptr(path) };
+    let options = (!options.is_null()).then(|| unsafe { CStr::from_ptr(options) });
+    panic::catch_unwind(|| prompt(path, options))
+        .ok()
+        .flatten()
+        .and_then(|out| CString::new(out).ok())
+        .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Release a string returned by `jj_starship_prompt` (NULL is a no-op)
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer returned by `jj_starship_prompt` that
+/// hasn't been freed yet.
+#[unsafe(no_mangle)]
+#[allow(unsafe_code)]
+pub unsafe extern "C" fn jj_starship_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: `s` came from `CString::into_raw` per the contract
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+fn prompt(path: &CStr, options: Option<&CStr>) -> Option<String> {
+    let path = Path::new(path.to_str().ok()?);
+    let options = options.map_or(Ok(""), CStr::to_str).ok()?;
+    let config = parse_options(options)?;
+    let result = detect(path, &config.boundaries);
+    match try_collect(&result, &config, &mut Timings::default()) {
+        Ok(info) => output::render(&result, info.as_ref(), &config),
+        Err(e) => output::render_error(&result, &e, &config),
+    }
+}
+
+/// Defaults plus `options`; None on the first unknown or malformed option
+fn parse_options(options: &str) -> Option<Config> {
+    let mut config = Config::default();
+    for option in options.split(';').filter(|o| !o.trim().is_empty()) {
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value)),
+            None => (option.trim(), None),
+        };
+        match (name, value) {
+            ("truncate-name", Some(n)) => config.truncate_name = n.parse().ok()?,
+            ("id-length", Some(n)) => config.id_length = n.parse().ok()?,
+            ("ancestor-bookmark-depth", Some(n)) => {
+                config.ancestor_bookmark_depth = n.parse().ok()?;
+            }
+            ("bookmarks-display-limit", Some(n)) => {
+                config.bookmarks_display_limit = n.parse().ok()?;
+            }
+            ("output", Some(format)) => config.output = format.parse().ok()?,
+            ("escapes", Some(escapes)) => config.escapes = escapes.parse().ok()?,
+            ("segment", Some(segment)) => config.segment = segment.parse().ok()?,
+            ("separator", Some(s)) => config.layout.separator = Cow::Owned(s.to_string()),
+            ("bookmark-separator", Some(s)) => {
+                config.layout.bookmark_separator = Cow::Owned(s.to_string());
+            }
+            ("symbol-set", Some(set)) => {
+                let set: SymbolSet = set.parse().ok()?;
+                config.jj_symbol = Cow::Borrowed(set.jj());
+                config.git_symbol = Cow::Borrowed(set.git());
+                config.hg_symbol = Cow::Borrowed(set.hg());
+                config.sl_symbol = Cow::Borrowed(set.sapling());
+                config.pijul_symbol = Cow::Borrowed(set.pijul());
+                config.budgets.placeholder = Cow::Borrowed(set.timeout());
+                config.symbols = set.status();
+            }
+            ("symbol", Some(symbol)) => {
+                symbol
+                    .parse::<SymbolOverride>()
+                    .ok()?
+                    .apply(&mut config.symbols);
+            }
+            ("jj-symbol", Some(s)) => config.jj_symbol = Cow::Owned(s.to_string()),
+            ("git-symbol", Some(s)) => config.git_symbol = Cow::Owned(s.to_string()),
+            ("hg-symbol", Some(s)) => config.hg_symbol = Cow::Owned(s.to_string()),
+            ("sl-symbol", Some(s)) => config.sl_symbol = Cow::Owned(s.to_string()),
+            ("pijul-symbol", Some(s)) => config.pijul_symbol = Cow::Owned(s.to_string()),
+            ("no-symbol", None) => {
+                config.jj_symbol = Cow::Borrowed("");
+                config.git_symbol = Cow::Borrowed("");
+                config.hg_symbol = Cow::Borrowed("");
+                config.sl_symbol = Cow::Borrowed("");
+                config.pijul_symbol = Cow::Borrowed("");
+            }
+            ("no-color", None) => {
+                config.jj_display.show_color = false;
+                config.git_display.show_color = false;
+                config.hg_display.show_color = false;
+                config.sl_display.show_color = false;
+                config.pijul_display.show_color = false;
+            }
+            ("compact", None) => config.compact = true,
+            ("read-only", None) => config.read_only = true,
+            ("no-cache", None) => config.use_cache = false,
+            _ => return None,
+        }
+    }
+    Some(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_starship::output::OutputFormat;
+
+    #[test]
+    fn parses_options_in_order() {
+        let config =
+            parse_options("output=tmux; id-length=6;no-color;separator= | ;symbol=ahead=>")
+                .unwrap();
+        assert_eq!(config.output, OutputFormat::Tmux);
+        assert_eq!(config.id_length, 6);
+        assert!(!config.git_display.show_color);
+        assert_eq!(config.layout.separator, " | ");
+        assert_eq!(config.symbols.ahead, ">");
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        assert!(parse_options("").is_some());
+        assert!(parse_options("colour").is_none());
+        assert!(parse_options("id-length=six").is_none());
+        assert!(parse_options("compact=yes").is_none());
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn prompt_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+
+        // SAFETY: valid NUL-terminated strings; results freed exactly once
+        unsafe {
+            assert!(jj_starship_prompt(ptr::null(), ptr::null()).is_null());
+            assert!(jj_starship_prompt(path.as_ptr(), ptr::null()).is_null());
+
+            // Data models are rendered outside a repo too
+            let out = jj_starship_prompt(path.as_ptr(), c"output=json".as_ptr());
+            assert!(!out.is_null());
+            let json = CStr::from_ptr(out).to_str().unwrap().to_string();
+            jj_starship_free(out);
+            assert!(json.contains("\"repo_type\":null"));
+
+            jj_starship_free(ptr::null_mut());
+        }
+    }
+}
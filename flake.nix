@@ -16,6 +16,7 @@
       overlays.default = final: prev: {
         jj-starship = self.packages.${final.system}.jj-starship;
         jj-starship-no-git = self.packages.${final.system}.jj-starship-no-git;
+        jj-starship-no-jj = self.packages.${final.system}.jj-starship-no-jj;
       };
     }
     // flake-utils.lib.eachDefaultSystem (
@@ -53,19 +54,27 @@
         # Build package with configurable features
         mkJjStarship =
           {
+            withJj ? true,
             withGit ? true,
           }:
           pkgs.rustPlatform.buildRustPackage {
-            pname = "jj-starship" + pkgs.lib.optionalString (!withGit) "-no-git";
+            pname =
+              "jj-starship"
+              + pkgs.lib.optionalString (!withJj) "-no-jj"
+              + pkgs.lib.optionalString (!withGit) "-no-git";
             version = "${version}${versionSuffix}";
 
             inherit src meta;
 
             cargoLock.lockFile = ./Cargo.lock;
 
-            buildNoDefaultFeatures = !withGit;
+            buildNoDefaultFeatures = !(withJj && withGit);
+            buildFeatures = pkgs.lib.optionals withJj [ "jj" ] ++ pkgs.lib.optionals withGit [ "git" ];
 
-            nativeBuildInputs = [ pkgs.pkg-config ];
+            nativeBuildInputs = [
+              pkgs.pkg-config
+              pkgs.installShellFiles
+            ];
 
             buildInputs =
               with pkgs;
@@ -83,12 +92,19 @@
               ];
 
             doCheck = true;
+
+            # Man pages come from the CLI definitions, so run the built binary
+            postInstall = pkgs.lib.optionalString (pkgs.stdenv.buildPlatform.canExecute pkgs.stdenv.hostPlatform) ''
+              $out/bin/jj-starship man man
+              installManPage man/*.1
+            '';
           };
       in
       {
         packages = {
           jj-starship = mkJjStarship { withGit = true; };
           jj-starship-no-git = mkJjStarship { withGit = false; };
+          jj-starship-no-jj = mkJjStarship { withJj = false; };
           default = self.packages.${system}.jj-starship;
         };
 
@@ -97,6 +113,7 @@
           # Ensure both package variants build successfully
           jj-starship = self.packages.${system}.jj-starship;
           jj-starship-no-git = self.packages.${system}.jj-starship-no-git;
+          jj-starship-no-jj = self.packages.${system}.jj-starship-no-jj;
         };
 
         # Development shell with Rust tooling
@@ -0,0 +1,179 @@
+//! `bench` subcommand - repeated detection/collection latency per backend
+
+use jj_starship::config::Config;
+use jj_starship::detect::{self, RepoType};
+use jj_starship::error::Result;
+#[cfg(feature = "git")]
+use jj_starship::git;
+#[cfg(feature = "jj")]
+use jj_starship::jj;
+use jj_starship::timings::Timings;
+use jj_starship::{hg, pijul, sapling};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Latency summary for one backend over all iterations
+#[derive(Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub name: &'static str,
+    pub min: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+}
+
+impl Stats {
+    /// Summarize samples (nearest-rank percentiles); `samples` must be non-empty
+    fn from_samples(name: &'static str, samples: &mut [Duration]) -> Self {
+        samples.sort_unstable();
+        let rank = |p: usize| samples[(samples.len() * p).div_ceil(100).max(1) - 1];
+        Self {
+            name,
+            min: samples[0],
+            median: rank(50),
+            p95: rank(95),
+        }
+    }
+}
+
+/// Time `f` over `iterations` runs, bailing on the first error
+fn measure<T>(
+    name: &'static str,
+    iterations: usize,
+    mut f: impl FnMut() -> Result<T>,
+) -> Result<Stats> {
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        f()?;
+        samples.push(start.elapsed());
+    }
+    Ok(Stats::from_samples(name, &mut samples))
+}
+
+/// Benchmark detection and every backend present at `cwd`
+///
+/// Colocated repos are measured with both collectors. Iterations are
+/// clamped to at least 1.
+#[allow(unreachable_patterns)]
+pub fn run(cwd: &Path, config: &Config, iterations: usize) -> Result<Vec<Stats>> {
+    let iterations = iterations.max(1);
+    let mut stats = vec![measure("detect", iterations, || {
+        Ok(detect::detect(cwd, &config.boundaries))
+    })?];
+
+    let result = detect::detect(cwd, &config.boundaries);
+    let Some(repo_root) = result.repo_root else {
+        return Ok(stats);
+    };
+    let mode = config.collect_mode(&repo_root);
+
+    #[cfg(feature = "jj")]
+    if matches!(result.repo_type, RepoType::Jj | RepoType::JjColocated) {
+        stats.push(measure("jj", iterations, || {
+            jj::collect(
+                &repo_root,
+                config.id_length,
+                config.ancestor_bookmark_depth,
+                config.jj_display,
+                mode,
+                config.budgets.divergence,
+                config.conflict_count.as_deref(),
+                config.read_only,
+                &mut Timings::default(),
+            )
+        })?);
+    }
+    #[cfg(feature = "git")]
+    if matches!(result.repo_type, RepoType::Git | RepoType::JjColocated) {
+        stats.push(measure("git", iterations, || {
+            git::collect(
+                &repo_root,
+                config.boundaries.separate_git_dir(&repo_root),
+                config.id_length,
+                mode,
+                config.budgets.status,
+                None,
+                &mut Timings::default(),
+            )
+        })?);
+    }
+    if result.repo_type == RepoType::Hg {
+        stats.push(measure("hg", iterations, || {
+            hg::collect(&repo_root, config.id_length)
+        })?);
+    }
+    if result.repo_type == RepoType::Sapling {
+        stats.push(measure("sapling", iterations, || {
+            sapling::collect(&repo_root, config.id_length)
+        })?);
+    }
+    if result.repo_type == RepoType::Pijul {
+        stats.push(measure("pijul", iterations, || pijul::collect(&repo_root))?);
+    }
+    Ok(stats)
+}
+
+/// Write an aligned table of min/median/p95 in milliseconds
+pub fn report(stats: &[Stats], iterations: usize, out: &mut impl Write) -> io::Result<()> {
+    let width = stats.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    writeln!(
+        out,
+        "{:<width$}  {:>10}  {:>10}  {:>10}   ({} iterations)",
+        "",
+        "min",
+        "median",
+        "p95",
+        iterations.max(1)
+    )?;
+    for s in stats {
+        writeln!(
+            out,
+            "{:<width$}  {:>8.3}ms  {:>8.3}ms  {:>8.3}ms",
+            s.name,
+            s.min.as_secs_f64() * 1000.0,
+            s.median.as_secs_f64() * 1000.0,
+            s.p95.as_secs_f64() * 1000.0,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let mut samples: Vec<_> = (1..=20).rev().map(Duration::from_millis).collect();
+        let stats = Stats::from_samples("jj", &mut samples);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.median, Duration::from_millis(10));
+        assert_eq!(stats.p95, Duration::from_millis(19));
+    }
+
+    #[test]
+    fn single_sample() {
+        let mut samples = [Duration::from_millis(4)];
+        let stats = Stats::from_samples("git", &mut samples);
+        assert_eq!(stats.min, stats.p95);
+        assert_eq!(stats.median, Duration::from_millis(4));
+    }
+
+    #[test]
+    fn report_table() {
+        let stats = [Stats {
+            name: "detect",
+            min: Duration::from_micros(20),
+            median: Duration::from_micros(25),
+            p95: Duration::from_micros(40),
+        }];
+        let mut out = Vec::new();
+        report(&stats, 10, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "               min      median         p95   (10 iterations)\n\
+             detect     0.020ms     0.025ms     0.040ms\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Per-phase time budgets - a phase that runs over is abandoned and its
+//! element rendered as a placeholder, so the cheap parts still show
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Default placeholder for an element whose phase ran out of time
+pub const DEFAULT_TIMEOUT_PLACEHOLDER: &str = "⧗";
+
+/// Time budgets for the expensive collection phases
+#[derive(Debug, Clone)]
+pub struct Budgets {
+    /// JJ divergence lookup (None = unbounded)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub divergence: Option<Duration>,
+    /// Git worktree status scan (None = unbounded)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub status: Option<Duration>,
+    /// Rendered in place of a timed-out status element
+    pub placeholder: Cow<'static, str>,
+}
+
+impl Default for Budgets {
+    fn default() -> Self {
+        Self {
+            divergence: None,
+            status: None,
+            placeholder: Cow::Borrowed(DEFAULT_TIMEOUT_PLACEHOLDER),
+        }
+    }
+}
+
+/// A bounded phase, with at most one worker thread running at a time
+///
+/// An abandoned worker keeps running until it finishes; long-lived processes
+/// (`watch`, the C library) would otherwise pile them up behind a slow repo.
+#[derive(Debug, Default)]
+pub struct Phase {
+    running: AtomicBool,
+}
+
+impl Phase {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Clears [`Phase::running`] when the worker ends, panicking or not
+struct Running(&'static Phase);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::Release);
+    }
+}
+
+/// Run `f` as `phase` within `budget`, returning None if it doesn't finish
+/// in time
+///
+/// Unbounded phases run inline. Bounded phases run on a detached thread
+/// that is abandoned on timeout and torn down when the process exits; while
+/// one is still running, the phase is skipped (None) rather than started
+/// again.
+pub fn run<T: Send + 'static>(
+    phase: &'static Phase,
+    budget: Option<Duration>,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let Some(budget) = budget else {
+        return Some(f());
+    };
+    if phase.running.swap(true, Ordering::Acquire) {
+        tracing::debug!("previous worker still running; phase skipped");
+        return None;
+    }
+    let (tx, rx) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let result = {
+            let _running = Running(phase);
+            f()
+        };
+        let _ = tx.send(result);
+    });
+    rx.recv_timeout(budget).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbounded_runs_inline() {
+        static PHASE: Phase = Phase::new();
+        assert_eq!(run(&PHASE, None, || 7), Some(7));
+    }
+
+    #[test]
+    fn within_budget() {
+        static PHASE: Phase = Phase::new();
+        assert_eq!(run(&PHASE, Some(Duration::from_secs(5)), || 7), Some(7));
+        assert_eq!(run(&PHASE, Some(Duration::from_secs(5)), || 8), Some(8));
+    }
+
+    #[test]
+    fn over_budget_is_abandoned() {
+        static PHASE: Phase = Phase::new();
+        let slow = || thread::sleep(Duration::from_millis(200));
+        assert_eq!(run(&PHASE, Some(Duration::from_millis(10)), slow), None);
+        // The abandoned worker is still running: skip rather than pile up
+        assert_eq!(run(&PHASE, Some(Duration::from_secs(5)), || 7), None);
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(run(&PHASE, Some(Duration::from_secs(5)), || 7), Some(7));
+    }
+}
@@ -0,0 +1,255 @@
+//! Cache directory housekeeping - measuring, emptying and pruning it
+//!
+//! The directory holds JJ snapshots (`.snap`, each with a `.lock` beside it)
+//! and `--notify` state (`.alerts`). Only files with those extensions, and
+//! snapshot temp files left by an interrupted write, are counted or removed,
+//! so a cache directory pointed somewhere shared stays safe to clear.
+//! Cache hits aren't recorded, since that would be a write per prompt;
+//! `--timings` shows whether a prompt was rendered from its snapshot.
+
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// FNV-1a, for hashes that name and key files in the cache directory
+///
+/// Unlike `DefaultHasher`, whose algorithm may change between Rust
+/// releases, a rebuilt binary keeps finding the files the old one wrote.
+#[derive(Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Totals over the files jj-starship wrote to the cache directory
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub snapshots: usize,
+    pub files: usize,
+    pub bytes: u64,
+    /// Time since the least recently written snapshot
+    pub oldest: Option<Duration>,
+}
+
+/// Count the cache files in `dir`; an absent directory is empty
+///
+/// # Errors
+///
+/// Returns an error if the directory can't be listed.
+pub fn stats(dir: &Path) -> io::Result<Stats> {
+    let mut stats = Stats::default();
+    let now = SystemTime::now();
+    for (path, meta) in files(dir)? {
+        stats.files += 1;
+        stats.bytes += meta.len();
+        if path.extension().is_some_and(|ext| ext == "snap") {
+            stats.snapshots += 1;
+            let age = meta
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok());
+            stats.oldest = stats.oldest.max(age);
+        }
+    }
+    Ok(stats)
+}
+
+/// Remove the cache files in `dir`, returning how many were removed
+///
+/// # Errors
+///
+/// Returns the first error listing the directory or removing a file.
+pub fn clear(dir: &Path) -> io::Result<usize> {
+    let files = files(dir)?;
+    for (path, _) in &files {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(files.len())
+}
+
+/// Remove the cache files in `dir` not written for longer than `max_age`,
+/// so workspaces that are gone or rarely visited don't linger
+///
+/// A lock file goes once its snapshot has, and only while no collector
+/// holds it: it's taken first, so a running prompt keeps locking the same
+/// file.
+pub fn prune(dir: &Path, max_age: Duration) {
+    let Ok(files) = files(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    let (locks, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|(path, _)| path.extension().is_some_and(|ext| ext == "lock"));
+    for (path, meta) in files {
+        let expired = meta
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    for (path, _) in locks {
+        if path.with_extension("snap").exists() {
+            continue;
+        }
+        if let Ok(file) = File::options().write(true).open(&path)
+            && file.try_lock().is_ok()
+        {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+/// The cache files in `dir` with their metadata
+fn files(dir: &Path) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if owned(&path)
+            && let Ok(meta) = entry.metadata()
+            && meta.is_file()
+        {
+            files.push((path, meta));
+        }
+    }
+    Ok(files)
+}
+
+/// A file jj-starship writes: `.snap`, `.lock`, `.alerts` or a snapshot's
+/// `.tmp<pid>`
+fn owned(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    matches!(ext, "snap" | "lock" | "alerts")
+        || ext
+            .strip_prefix("tmp")
+            .is_some_and(|pid| !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_cache_files_are_counted_and_cleared() {
+        let tmp = tempfile::tempdir().unwrap();
+        for (name, contents) in [
+            ("a.snap", "12345"),
+            ("a.lock", ""),
+            ("b.snap", "123"),
+            ("a.alerts", "0 1\n"),
+            ("c.tmp4242", "1"),
+            ("notes.txt", "keep"),
+            ("d.tmp", "keep"),
+        ] {
+            fs::write(tmp.path().join(name), contents).unwrap();
+        }
+
+        let stats = stats(tmp.path()).unwrap();
+        assert_eq!(stats.snapshots, 2);
+        assert_eq!(stats.files, 5);
+        assert_eq!(stats.bytes, 13);
+        assert!(stats.oldest.is_some());
+
+        assert_eq!(clear(tmp.path()).unwrap(), 5);
+        let mut left: Vec<_> = fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["d.tmp", "notes.txt"]);
+    }
+
+    #[test]
+    fn prune_removes_expired_files_and_free_orphan_locks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let day = Duration::from_secs(86_400);
+        for name in [
+            "old.snap",
+            "old.lock",
+            "new.snap",
+            "new.lock",
+            "old.alerts",
+            "new.alerts",
+            "old.tmp4242",
+            "held.lock",
+            "notes.txt",
+        ] {
+            let file = File::create(tmp.path().join(name)).unwrap();
+            if name.starts_with("old") || name == "notes.txt" {
+                file.set_modified(SystemTime::now() - 2 * day).unwrap();
+            }
+        }
+        let held = File::options()
+            .write(true)
+            .open(tmp.path().join("held.lock"))
+            .unwrap();
+        held.lock().unwrap();
+
+        prune(tmp.path(), day);
+        let mut left: Vec<_> = fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "held.lock",
+                "new.alerts",
+                "new.lock",
+                "new.snap",
+                "notes.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn stable_hasher_is_fnv1a() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = StableHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn missing_dir_is_empty() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("absent");
+        assert_eq!(stats(&dir).unwrap(), Stats::default());
+        assert_eq!(clear(&dir).unwrap(), 0);
+    }
+}
@@ -1,10 +1,921 @@
 //! ANSI color codes for terminal output
 //! Uses standard ANSI colors (0-15) so they adapt to terminal theme
 
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
 pub const RESET: &str = "\x1b[0m";
+/// Restore the default foreground only, keeping any background
+pub const FG_RESET: &str = "\x1b[39m";
 pub const PURPLE: &str = "\x1b[35m"; // Color 5: Magenta
 pub const GREEN: &str = "\x1b[32m"; // Color 2: Green
 pub const RED: &str = "\x1b[31m"; // Color 1: Red
 pub const BLUE: &str = "\x1b[34m"; // Color 4: Blue
+#[cfg(feature = "jj")]
 pub const BRIGHT_MAGENTA: &str = "\x1b[95m"; // Bright magenta (jj change_id prefix)
+#[cfg(feature = "jj")]
 pub const BRIGHT_BLACK: &str = "\x1b[90m"; // Bright black/gray (jj change_id rest)
+
+/// Prompt element colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Purple,
+    Green,
+    Red,
+    Blue,
+    #[cfg(feature = "jj")]
+    BrightMagenta,
+    #[cfg(feature = "jj")]
+    BrightBlack,
+}
+
+impl Color {
+    /// ANSI SGR escape
+    #[must_use]
+    pub const fn ansi(self) -> &'static str {
+        match self {
+            Self::Purple => PURPLE,
+            Self::Green => GREEN,
+            Self::Red => RED,
+            Self::Blue => BLUE,
+            #[cfg(feature = "jj")]
+            Self::BrightMagenta => BRIGHT_MAGENTA,
+            #[cfg(feature = "jj")]
+            Self::BrightBlack => BRIGHT_BLACK,
+        }
+    }
+
+    /// 256-color palette index (same slot as `ansi`)
+    #[must_use]
+    pub const fn index(self) -> u8 {
+        match self {
+            Self::Purple => 5,
+            Self::Green => 2,
+            Self::Red => 1,
+            Self::Blue => 4,
+            #[cfg(feature = "jj")]
+            Self::BrightMagenta => 13,
+            #[cfg(feature = "jj")]
+            Self::BrightBlack => 8,
+        }
+    }
+
+    /// oh-my-posh named color (same palette slot as `ansi`)
+    #[must_use]
+    pub const fn omp(self) -> &'static str {
+        match self {
+            Self::Purple => "magenta",
+            Self::Green => "green",
+            Self::Red => "red",
+            Self::Blue => "blue",
+            #[cfg(feature = "jj")]
+            Self::BrightMagenta => "lightMagenta",
+            #[cfg(feature = "jj")]
+            Self::BrightBlack => "darkGray",
+        }
+    }
+}
+
+/// Replacements for the default colors, e.g. from a starship palette
+///
+/// Unset slots keep the standard ANSI color, which follows the terminal
+/// theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Palette {
+    pub purple: Option<TermColor>,
+    pub green: Option<TermColor>,
+    pub red: Option<TermColor>,
+    pub blue: Option<TermColor>,
+    /// JJ change id prefix
+    pub bright_magenta: Option<TermColor>,
+    /// JJ change id rest
+    pub bright_black: Option<TermColor>,
+}
+
+impl Palette {
+    /// Replacement for `color`, None to keep the default
+    #[must_use]
+    pub const fn get(&self, color: Color) -> Option<TermColor> {
+        match color {
+            Color::Purple => self.purple,
+            Color::Green => self.green,
+            Color::Red => self.red,
+            Color::Blue => self.blue,
+            #[cfg(feature = "jj")]
+            Color::BrightMagenta => self.bright_magenta,
+            #[cfg(feature = "jj")]
+            Color::BrightBlack => self.bright_black,
+        }
+    }
+
+    /// `self`, with the unset slots taken from `other`
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            purple: self.purple.or(other.purple),
+            green: self.green.or(other.green),
+            red: self.red.or(other.red),
+            blue: self.blue.or(other.blue),
+            bright_magenta: self.bright_magenta.or(other.bright_magenta),
+            bright_black: self.bright_black.or(other.bright_black),
+        }
+    }
+}
+
+/// Built-in color presets (`--theme`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Standard ANSI colors
+    #[default]
+    Default,
+    /// Blue and orange from the Okabe-Ito palette instead of green and red,
+    /// with conflicts told apart by shape
+    Colorblind,
+    /// Bright foregrounds, and a white rather than dim change id suffix
+    HighContrast,
+}
+
+impl Theme {
+    /// Colors replacing the defaults
+    #[must_use]
+    pub const fn palette(self) -> Palette {
+        match self {
+            Self::Default => Palette {
+                purple: None,
+                green: None,
+                red: None,
+                blue: None,
+                bright_magenta: None,
+                bright_black: None,
+            },
+            Self::Colorblind => Palette {
+                purple: Some(TermColor::Rgb(0xcc, 0x79, 0xa7)),
+                green: Some(TermColor::Rgb(0x00, 0x72, 0xb2)),
+                red: Some(TermColor::Rgb(0xe6, 0x9f, 0x00)),
+                blue: Some(TermColor::Rgb(0x56, 0xb4, 0xe9)),
+                bright_magenta: None,
+                bright_black: None,
+            },
+            Self::HighContrast => Palette {
+                purple: Some(TermColor::Indexed(13)),
+                green: Some(TermColor::Indexed(10)),
+                red: Some(TermColor::Indexed(9)),
+                blue: Some(TermColor::Indexed(12)),
+                bright_magenta: Some(TermColor::Indexed(13)),
+                bright_black: Some(TermColor::Indexed(15)),
+            },
+        }
+    }
+
+    /// `--style-fd` styles when conflicted, needing attention and clean
+    #[must_use]
+    pub const fn state_styles(self) -> [&'static str; 3] {
+        match self {
+            Self::Default => ["bold red", "yellow", "green"],
+            Self::Colorblind => ["bold fg:#d55e00", "fg:#e69f00", "fg:#0072b2"],
+            Self::HighContrast => ["bold bright-red", "bold bright-yellow", "bold bright-green"],
+        }
+    }
+
+    /// Whether conflicts get a glyph of their own shape, as their color
+    /// alone doesn't set them apart
+    #[must_use]
+    pub const fn conflict_shape(self) -> bool {
+        matches!(self, Self::Colorblind)
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "colorblind" => Ok(Self::Colorblind),
+            "high-contrast" => Ok(Self::HighContrast),
+            _ => Err(format!(
+                "expected default, colorblind or high-contrast, got {s:?}"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::Colorblind => "colorblind",
+            Self::HighContrast => "high-contrast",
+        })
+    }
+}
+
+/// Zero-width markers around ANSI escapes, so a shell embedding the output
+/// in its prompt doesn't count them toward the cursor position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Escapes {
+    /// Bare escapes (starship does its own wrapping)
+    #[default]
+    None,
+    /// `%{...%}` for `PROMPT` with `PROMPT_SUBST`
+    Zsh,
+    /// Readline's `\001...\002` for `PS1`
+    ///
+    /// Not `\[...\]`: bash decodes those in the prompt string before command
+    /// substitution runs, so they'd be printed literally.
+    Bash,
+}
+
+impl Escapes {
+    /// Wrap one escape sequence as zero-width
+    #[must_use]
+    pub fn wrap(self, escape: &str) -> String {
+        match self {
+            Self::None => escape.to_string(),
+            Self::Zsh => format!("%{{{escape}%}}"),
+            Self::Bash => format!("\x01{escape}\x02"),
+        }
+    }
+}
+
+impl FromStr for Escapes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "zsh" => Ok(Self::Zsh),
+            "bash" => Ok(Self::Bash),
+            _ => Err(format!("expected none, zsh or bash, got {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for Escapes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Zsh => "zsh",
+            Self::Bash => "bash",
+        })
+    }
+}
+
+/// User-configurable terminal color: a 256-color index or 24-bit RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermColor {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl TermColor {
+    /// Foreground SGR escape
+    #[must_use]
+    pub fn fg(self) -> String {
+        format!("\x1b[{}m", self.sgr(38))
+    }
+
+    /// Background SGR escape
+    #[must_use]
+    pub fn bg(self) -> String {
+        format!("\x1b[{}m", self.sgr(48))
+    }
+
+    /// SGR parameters selecting this color, after `base` (38 fg, 48 bg)
+    fn sgr(self, base: u8) -> String {
+        match self {
+            Self::Indexed(n) => format!("{base};5;{n}"),
+            Self::Rgb(r, g, b) => format!("{base};2;{r};{g};{b}"),
+        }
+    }
+
+    /// tmux color name: `colourN` or `#rrggbb`
+    fn tmux(self) -> String {
+        match self {
+            Self::Indexed(n) => format!("colour{n}"),
+            Self::Rgb(..) => self.spec(),
+        }
+    }
+
+    /// Palette index or `#rrggbb`, as zsh, tmux and zjstatus spell colors
+    #[must_use]
+    pub fn spec(self) -> String {
+        match self {
+            Self::Indexed(n) => n.to_string(),
+            Self::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+
+    /// oh-my-posh color: a named color for the 16 basic slots, else hex
+    #[must_use]
+    pub fn omp(self) -> String {
+        const NAMES: [&str; 16] = [
+            "black",
+            "red",
+            "green",
+            "yellow",
+            "blue",
+            "magenta",
+            "cyan",
+            "white",
+            "darkGray",
+            "lightRed",
+            "lightGreen",
+            "lightYellow",
+            "lightBlue",
+            "lightMagenta",
+            "lightCyan",
+            "lightWhite",
+        ];
+        match self {
+            Self::Indexed(n) if n < 16 => NAMES[usize::from(n)].to_string(),
+            Self::Indexed(n) => {
+                let (r, g, b) = xterm_rgb(n);
+                Self::Rgb(r, g, b).spec()
+            }
+            Self::Rgb(..) => self.spec(),
+        }
+    }
+}
+
+/// RGB of an xterm 256-color index above the 16 basic slots
+fn xterm_rgb(n: u8) -> (u8, u8, u8) {
+    if n >= 232 {
+        let level = 8 + (n - 232) * 10;
+        return (level, level, level);
+    }
+    let cube = n - 16;
+    let level = |c: u8| if c == 0 { 0 } else { 55 + c * 40 };
+    (level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+}
+
+/// Accepts `0`-`255`, `#rrggbb`, or one of the eight basic color names
+impl FromStr for TermColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const NAMES: [&str; 8] = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+        if let Some(hex) = s.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Self::Rgb(r, g, b)),
+                _ => Err(format!("expected #rrggbb, got {s:?}")),
+            };
+        }
+        if let Some(index) = NAMES.iter().position(|&name| name == s) {
+            #[allow(clippy::cast_possible_truncation)] // < 8
+            return Ok(Self::Indexed(index as u8));
+        }
+        s.parse()
+            .map(Self::Indexed)
+            .map_err(|_| format!("expected 0-255, #rrggbb or a color name, got {s:?}"))
+    }
+}
+
+/// How colored text is marked up for the consuming prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    /// ANSI escapes (starship, raw terminals)
+    Ansi(Escapes),
+    /// ANSI foreground escapes that leave the segment background in place
+    Powerline(Escapes),
+    /// oh-my-posh inline color tags: `<red>text</>`
+    OhMyPosh,
+    /// zsh prompt escapes: `%F{1}text%f`
+    Zsh,
+    /// tmux style markup: `#[fg=colour1]text#[fg=default]`
+    Tmux,
+    /// zjstatus dynamic-render markup: `#[fg=1]text`
+    ///
+    /// Each directive replaces the previous style, so there's no reset; the
+    /// only unstyled text that can follow a colored element is a separator
+    /// space.
+    Zellij,
+}
+
+impl Markup {
+    /// Escape the characters of `text` that the markup would interpret:
+    /// `%` in zsh prompts, `#` in tmux formats
+    #[must_use = "returns escaped string, does not modify input"]
+    pub fn escape(self, text: &str) -> Cow<'_, str> {
+        let (special, escaped) = match self {
+            Self::Ansi(Escapes::Zsh) | Self::Powerline(Escapes::Zsh) | Self::Zsh => ('%', "%%"),
+            Self::Tmux => ('#', "##"),
+            _ => return Cow::Borrowed(text),
+        };
+        if text.contains(special) {
+            Cow::Owned(text.replace(special, escaped))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Wrap `text` in `color`
+    #[must_use = "returns styled string, does not print"]
+    pub fn paint(self, text: &str, color: Color) -> String {
+        match self {
+            Self::Ansi(Escapes::None) => format!("{}{text}{RESET}", color.ansi()),
+            Self::Ansi(escapes) => format!(
+                "{}{text}{}",
+                escapes.wrap(color.ansi()),
+                escapes.wrap(RESET)
+            ),
+            Self::Powerline(escapes) => format!(
+                "{}{text}{}",
+                escapes.wrap(color.ansi()),
+                escapes.wrap(FG_RESET)
+            ),
+            Self::OhMyPosh => format!("<{}>{text}</>", color.omp()),
+            Self::Zsh => format!("%F{{{}}}{text}%f", color.index()),
+            Self::Tmux => format!("#[fg=colour{}]{text}#[fg=default]", color.index()),
+            Self::Zellij => format!("#[fg={}]{text}", color.index()),
+        }
+    }
+
+    /// Wrap `text` in a configured color
+    #[must_use = "returns styled string, does not print"]
+    pub fn paint_term(self, text: &str, color: TermColor) -> String {
+        match self {
+            Self::Ansi(escapes) => {
+                format!("{}{text}{}", escapes.wrap(&color.fg()), escapes.wrap(RESET))
+            }
+            Self::Powerline(escapes) => format!(
+                "{}{text}{}",
+                escapes.wrap(&color.fg()),
+                escapes.wrap(FG_RESET)
+            ),
+            Self::OhMyPosh => format!("<{}>{text}</>", color.omp()),
+            Self::Zsh => format!("%F{{{}}}{text}%f", color.spec()),
+            Self::Tmux => format!("#[fg={}]{text}#[fg=default]", color.tmux()),
+            Self::Zellij => format!("#[fg={}]{text}", color.spec()),
+        }
+    }
+
+    /// Wrap `text` in a full style
+    ///
+    /// Each markup renders the attributes it has: zsh has no italic or dim,
+    /// oh-my-posh no dim, and powerline output keeps its segment background.
+    #[must_use = "returns styled string, does not print"]
+    pub fn paint_style(self, text: &str, style: Style) -> String {
+        match self {
+            Self::Ansi(escapes) => {
+                let set = escapes.wrap(&format!("\x1b[{}m", style.sgr()));
+                format!("{set}{text}{}", escapes.wrap(RESET))
+            }
+            Self::Powerline(escapes) => {
+                let style = Style { bg: None, ..style };
+                let set = escapes.wrap(&format!("\x1b[{}m", style.sgr()));
+                format!("{set}{text}{}", escapes.wrap("\x1b[22;23;24;39m"))
+            }
+            Self::OhMyPosh => {
+                let mut out = match (style.fg, style.bg) {
+                    (fg, Some(bg)) => format!(
+                        "<{},{}>{text}</>",
+                        fg.map(TermColor::omp).unwrap_or_default(),
+                        bg.omp()
+                    ),
+                    (Some(fg), None) => format!("<{}>{text}</>", fg.omp()),
+                    (None, None) => text.to_string(),
+                };
+                for (set, tag) in [
+                    (style.bold, "b"),
+                    (style.italic, "i"),
+                    (style.underline, "u"),
+                ] {
+                    if set {
+                        out = format!("<{tag}>{out}</{tag}>");
+                    }
+                }
+                out
+            }
+            Self::Zsh => {
+                let mut out = text.to_string();
+                if let Some(fg) = style.fg {
+                    out = format!("%F{{{}}}{out}%f", fg.spec());
+                }
+                if let Some(bg) = style.bg {
+                    out = format!("%K{{{}}}{out}%k", bg.spec());
+                }
+                if style.underline {
+                    out = format!("%U{out}%u");
+                }
+                if style.bold {
+                    out = format!("%B{out}%b");
+                }
+                out
+            }
+            Self::Tmux => {
+                let (set, reset): (Vec<_>, Vec<_>) = style
+                    .attributes(TermColor::tmux, "italics", "underscore")
+                    .into_iter()
+                    .unzip();
+                format!("#[{}]{text}#[{}]", set.join(","), reset.join(","))
+            }
+            Self::Zellij => {
+                let set: Vec<_> = style
+                    .attributes(TermColor::spec, "italic", "underline")
+                    .into_iter()
+                    .map(|(set, _)| set)
+                    .collect();
+                format!("#[{}]{text}", set.join(","))
+            }
+        }
+    }
+}
+
+/// Colors and text attributes from a starship style string (see
+/// [`crate::starship::style`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Style {
+    pub fg: Option<TermColor>,
+    pub bg: Option<TermColor>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub dimmed: bool,
+}
+
+/// Starship syntax, e.g. `bold fg:#ff8800 bg:236`
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = Vec::new();
+        for (set, word) in [
+            (self.bold, "bold"),
+            (self.italic, "italic"),
+            (self.underline, "underline"),
+            (self.dimmed, "dimmed"),
+        ] {
+            if set {
+                words.push(word.to_string());
+            }
+        }
+        words.extend(self.fg.map(|fg| format!("fg:{}", fg.spec())));
+        words.extend(self.bg.map(|bg| format!("bg:{}", bg.spec())));
+        f.write_str(&words.join(" "))
+    }
+}
+
+impl Style {
+    /// SGR parameters, `;`-separated
+    fn sgr(self) -> String {
+        let mut params = Vec::new();
+        for (set, param) in [
+            (self.bold, "1"),
+            (self.dimmed, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+        ] {
+            if set {
+                params.push(param.to_string());
+            }
+        }
+        params.extend(self.fg.map(|fg| fg.sgr(38)));
+        params.extend(self.bg.map(|bg| bg.sgr(48)));
+        params.join(";")
+    }
+
+    /// `(set, reset)` pairs of tmux-style attributes, with the markup's
+    /// color spelling and italic/underline names
+    fn attributes(
+        self,
+        color: fn(TermColor) -> String,
+        italic: &'static str,
+        underline: &'static str,
+    ) -> Vec<(String, String)> {
+        let mut attributes = Vec::new();
+        if let Some(fg) = self.fg {
+            attributes.push((format!("fg={}", color(fg)), "fg=default".to_string()));
+        }
+        if let Some(bg) = self.bg {
+            attributes.push((format!("bg={}", color(bg)), "bg=default".to_string()));
+        }
+        for (set, name) in [
+            (self.bold, "bold"),
+            (self.dimmed, "dim"),
+            (self.italic, italic),
+            (self.underline, underline),
+        ] {
+            if set {
+                attributes.push((name.to_string(), format!("no{name}")));
+            }
+        }
+        attributes
+    }
+}
+
+/// A part of the segment that `--style` can restyle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    /// Repo symbol
+    Symbol,
+    /// Bookmarks (JJ), branch (Git) and other names
+    Name,
+    /// Change id or commit hash
+    Id,
+    /// Status brackets, error glyph and `DIR` marker
+    Status,
+    /// Unique prefix of the JJ change id
+    IdPrefix,
+    /// Rest of the JJ change id after its unique prefix
+    IdRest,
+}
+
+impl FromStr for Part {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "symbol" => Ok(Self::Symbol),
+            "name" => Ok(Self::Name),
+            "id" => Ok(Self::Id),
+            "status" => Ok(Self::Status),
+            "id-prefix" => Ok(Self::IdPrefix),
+            "id-rest" => Ok(Self::IdRest),
+            _ => Err(format!(
+                "expected symbol, name, id, status, id-prefix or id-rest, got {s:?}"
+            )),
+        }
+    }
+}
+
+/// Styles replacing the default colors of segment parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Styles {
+    pub symbol: Option<Style>,
+    pub name: Option<Style>,
+    pub id: Option<Style>,
+    pub status: Option<Style>,
+    pub id_prefix: Option<Style>,
+    pub id_rest: Option<Style>,
+}
+
+impl Styles {
+    /// Style for `part`, None to keep its color
+    #[must_use]
+    pub const fn get(&self, part: Part) -> Option<Style> {
+        match part {
+            Part::Symbol => self.symbol,
+            Part::Name => self.name,
+            Part::Id => self.id,
+            Part::Status => self.status,
+            Part::IdPrefix => self.id_prefix,
+            Part::IdRest => self.id_rest,
+        }
+    }
+
+    /// Replace the style of `part`
+    pub const fn set(&mut self, part: Part, style: Style) {
+        let slot = match part {
+            Part::Symbol => &mut self.symbol,
+            Part::Name => &mut self.name,
+            Part::Id => &mut self.id,
+            Part::Status => &mut self.status,
+            Part::IdPrefix => &mut self.id_prefix,
+            Part::IdRest => &mut self.id_rest,
+        };
+        *slot = Some(style);
+    }
+}
+
+/// Repo state that `--state-style` colors, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Conflict,
+    /// JJ change id with several visible commits
+    Divergent,
+    /// Ahead of or behind the remote
+    Unsynced,
+    /// Any other status indicator
+    Dirty,
+    Clean,
+}
+
+impl FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "conflict" => Ok(Self::Conflict),
+            "divergent" => Ok(Self::Divergent),
+            "unsynced" => Ok(Self::Unsynced),
+            "dirty" => Ok(Self::Dirty),
+            "clean" => Ok(Self::Clean),
+            _ => Err(format!(
+                "expected conflict, divergent, unsynced, dirty or clean, got {s:?}"
+            )),
+        }
+    }
+}
+
+/// Styles for the repo symbol and status in each [`State`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StateStyles {
+    pub conflict: Option<Style>,
+    pub divergent: Option<Style>,
+    pub unsynced: Option<Style>,
+    pub dirty: Option<Style>,
+    pub clean: Option<Style>,
+}
+
+impl StateStyles {
+    /// Style for `state`, None to keep the part styles
+    #[must_use]
+    pub const fn get(&self, state: State) -> Option<Style> {
+        match state {
+            State::Conflict => self.conflict,
+            State::Divergent => self.divergent,
+            State::Unsynced => self.unsynced,
+            State::Dirty => self.dirty,
+            State::Clean => self.clean,
+        }
+    }
+
+    /// Replace the style of `state`
+    pub const fn set(&mut self, state: State, style: Style) {
+        let slot = match state {
+            State::Conflict => &mut self.conflict,
+            State::Divergent => &mut self.divergent,
+            State::Unsynced => &mut self.unsynced,
+            State::Dirty => &mut self.dirty,
+            State::Clean => &mut self.clean,
+        };
+        *slot = Some(style);
+    }
+}
+
+/// One `--style PART=STYLE` (or `--state-style STATE=STYLE`) entry; the
+/// style string is parsed once the starship palette is at hand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleOverride<T = Part> {
+    pub target: T,
+    pub style: String,
+}
+
+impl<T: FromStr<Err = String>> FromStr for StyleOverride<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, style) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=STYLE, got {s:?}"))?;
+        Ok(Self {
+            target: target.parse()?,
+            style: style.to_string(),
+        })
+    }
+}
+
+/// Markup with the palette and styles its colors come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Painter {
+    pub markup: Markup,
+    pub palette: Palette,
+    pub styles: Styles,
+}
+
+impl Painter {
+    /// Wrap `text` in `color`, or its palette replacement
+    #[must_use = "returns styled string, does not print"]
+    pub fn paint(self, text: &str, color: Color) -> String {
+        match self.palette.get(color) {
+            Some(replacement) => self.markup.paint_term(text, replacement),
+            None => self.markup.paint(text, color),
+        }
+    }
+
+    /// Wrap `part` of the segment in its style, else in `color`; a style
+    /// without a foreground keeps `color`
+    #[must_use = "returns styled string, does not print"]
+    pub fn paint_part(self, text: &str, part: Part, color: Color) -> String {
+        let Some(style) = self.styles.get(part) else {
+            return self.paint(text, color);
+        };
+        let fg = style
+            .fg
+            .or_else(|| self.palette.get(color))
+            .unwrap_or(TermColor::Indexed(color.index()));
+        self.markup.paint_style(
+            text,
+            Style {
+                fg: Some(fg),
+                ..style
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_term_color() {
+        assert_eq!("236".parse(), Ok(TermColor::Indexed(236)));
+        assert_eq!("cyan".parse(), Ok(TermColor::Indexed(6)));
+        assert_eq!("#ff8800".parse(), Ok(TermColor::Rgb(255, 136, 0)));
+        assert!("#ff88".parse::<TermColor>().is_err());
+        assert!("256".parse::<TermColor>().is_err());
+        assert!("teal".parse::<TermColor>().is_err());
+    }
+
+    #[test]
+    fn escapes_wrap_ansi_only() {
+        assert_eq!(
+            Markup::Ansi(Escapes::Zsh).paint("main", Color::Green),
+            "%{\x1b[32m%}main%{\x1b[0m%}"
+        );
+        assert_eq!(
+            Markup::Ansi(Escapes::Bash).paint("main", Color::Green),
+            "\x01\x1b[32m\x02main\x01\x1b[0m\x02"
+        );
+    }
+
+    #[test]
+    fn palette_replaces_default_colors() {
+        let painter = Painter {
+            markup: Markup::Ansi(Escapes::None),
+            palette: Palette {
+                red: Some(TermColor::Rgb(0xf3, 0x8b, 0xa8)),
+                ..Palette::default()
+            },
+            styles: Styles::default(),
+        };
+        assert_eq!(
+            painter.paint("!", Color::Red),
+            "\x1b[38;2;243;139;168m!\x1b[0m"
+        );
+        assert_eq!(painter.paint("main", Color::Green), "\x1b[32mmain\x1b[0m");
+
+        let tmux = Painter {
+            markup: Markup::Tmux,
+            ..painter
+        };
+        assert_eq!(tmux.paint("!", Color::Red), "#[fg=#f38ba8]!#[fg=default]");
+        assert_eq!(TermColor::Indexed(9).omp(), "lightRed");
+        assert_eq!(TermColor::Indexed(208).omp(), "#ff8700");
+        assert_eq!(TermColor::Indexed(244).omp(), "#808080");
+    }
+
+    #[test]
+    fn styles_per_markup() {
+        let style = Style {
+            fg: Some(TermColor::Rgb(0xff, 0x88, 0x00)),
+            bg: Some(TermColor::Indexed(236)),
+            bold: true,
+            italic: true,
+            ..Style::default()
+        };
+        assert_eq!(
+            Markup::Ansi(Escapes::None).paint_style("main", style),
+            "\x1b[1;3;38;2;255;136;0;48;5;236mmain\x1b[0m"
+        );
+        assert_eq!(
+            Markup::Powerline(Escapes::None).paint_style("main", style),
+            "\x1b[1;3;38;2;255;136;0mmain\x1b[22;23;24;39m"
+        );
+        assert_eq!(
+            Markup::Zsh.paint_style("main", style),
+            "%B%K{236}%F{#ff8800}main%f%k%b"
+        );
+        assert_eq!(
+            Markup::Tmux.paint_style("main", style),
+            "#[fg=#ff8800,bg=colour236,bold,italics]main#[fg=default,bg=default,nobold,noitalics]"
+        );
+        assert_eq!(
+            Markup::Zellij.paint_style("main", style),
+            "#[fg=#ff8800,bg=236,bold,italic]main"
+        );
+        assert_eq!(
+            Markup::OhMyPosh.paint_style("main", style),
+            "<i><b><#ff8800,#303030>main</></b></i>"
+        );
+
+        let painter = Painter {
+            markup: Markup::Ansi(Escapes::None),
+            palette: Palette::default(),
+            styles: Styles {
+                name: Some(Style {
+                    underline: true,
+                    ..Style::default()
+                }),
+                ..Styles::default()
+            },
+        };
+        assert_eq!(
+            painter.paint_part("main", Part::Name, Color::Green),
+            "\x1b[4;38;5;2mmain\x1b[0m",
+            "no foreground keeps the default color"
+        );
+        assert_eq!(
+            painter.paint_part("abc", Part::Id, Color::Purple),
+            "\x1b[35mabc\x1b[0m"
+        );
+    }
+}
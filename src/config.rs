@@ -1,18 +1,54 @@
 //! Configuration for jj-starship
 
+use crate::budget::Budgets;
+use crate::color::{
+    Escapes, Painter, Palette, State, StateStyles, Style, StyleOverride, Styles, TermColor, Theme,
+};
+use crate::config_file::ConfigFile;
+use crate::detect::{Boundaries, Nesting, ResolveSymlinks};
+use crate::forge::Forge;
+use crate::hooks::Hook;
+use crate::netfs::NetworkFs;
+use crate::output::{
+    Affix, CountThreshold, CountThresholds, DEFAULT_BOOKMARK_SEPARATOR, DEFAULT_POWERLINE_BG,
+    DEFAULT_SEPARATOR, DisplayRule, Layout, OutputFormat, Powerline, PrefixWord, Segment,
+    SymbolOverride, SymbolSet, Symbols,
+};
+use crate::scale::{CollectMode, Thresholds};
+use crate::starship;
+use crate::text;
 use std::borrow::Cow;
 use std::env;
+use std::fmt;
+use std::path::{self, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
 
 /// Default symbol for JJ repos
 pub const DEFAULT_JJ_SYMBOL: &str = "󱗆 ";
 /// Default symbol for Git repos
 pub const DEFAULT_GIT_SYMBOL: &str = " ";
+/// Default symbol for Mercurial repos
+pub const DEFAULT_HG_SYMBOL: &str = " ";
+/// Default symbol for Sapling repos
+pub const DEFAULT_SL_SYMBOL: &str = " ";
+/// Default symbol for Pijul repos
+pub const DEFAULT_PIJUL_SYMBOL: &str = " ";
+
+/// Default days a snapshot is kept without being rewritten
+pub const DEFAULT_CACHE_MAX_AGE_DAYS: u64 = 30;
+
+/// Default tracked file threshold for large-repo safe mode
+pub const DEFAULT_LARGE_REPO_FILES: usize = 100_000;
+/// Default packed object threshold for large-repo safe mode
+pub const DEFAULT_LARGE_REPO_OBJECTS: usize = 2_000_000;
 
 /// Display options for a repo type
 ///
 /// Each toggle is independent - any combination is valid. Bools are clearer
 /// than bitflags for 6 orthogonal visibility settings.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, Hash)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct DisplayConfig {
     pub show_prefix: bool,
@@ -21,10 +57,12 @@ pub struct DisplayConfig {
     pub show_status: bool,
     pub show_color: bool,
     /// Show unique prefix coloring for `change_id` (JJ only)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
     pub show_prefix_color: bool,
 }
 
 impl DisplayConfig {
+    #[must_use]
     pub const fn all_visible() -> Self {
         Self {
             show_prefix: true,
@@ -35,10 +73,138 @@ impl DisplayConfig {
             show_prefix_color: true,
         }
     }
+
+    /// Whether the `change_id` is rendered with unique prefix coloring
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    #[must_use]
+    pub const fn uses_prefix_color(self) -> bool {
+        self.show_id && self.show_color && self.show_prefix_color
+    }
+}
+
+/// A part of the segment that `--hide`/`--show` toggle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Prefix,
+    Name,
+    Id,
+    Status,
+    Color,
+    /// Unique prefix coloring of the JJ change id
+    PrefixColor,
+}
+
+impl FromStr for Element {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefix" => Ok(Self::Prefix),
+            "name" => Ok(Self::Name),
+            "id" => Ok(Self::Id),
+            "status" => Ok(Self::Status),
+            "color" => Ok(Self::Color),
+            "prefix-color" => Ok(Self::PrefixColor),
+            _ => Err(format!(
+                "expected prefix, name, id, status, color or prefix-color, got {s:?}"
+            )),
+        }
+    }
+}
+
+/// Repo types that `--hide`/`--show` entries can be scoped to, as named in
+/// the `--no-<repo>-*` flags
+pub(crate) const TOGGLE_REPOS: [&str; 5] = ["jj", "git", "hg", "sl", "pijul"];
+
+/// One `--hide`/`--show` entry: `id` for every repo type, `jj:id` for one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementToggle {
+    /// None for every repo type
+    pub repo: Option<&'static str>,
+    pub element: Element,
+}
+
+impl ElementToggle {
+    /// Whether the entry covers `element` of `repo`
+    #[must_use]
+    pub fn matches(self, repo: &str, element: Element) -> bool {
+        self.element == element && self.repo.is_none_or(|r| r == repo)
+    }
+}
+
+impl FromStr for ElementToggle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((repo, element)) = s.split_once(':') else {
+            return Ok(Self {
+                repo: None,
+                element: s.parse()?,
+            });
+        };
+        let repo = TOGGLE_REPOS
+            .into_iter()
+            .find(|&r| r == repo)
+            .ok_or_else(|| format!("expected jj, git, hg, sl or pijul, got {repo:?}"))?;
+        Ok(Self {
+            repo: Some(repo),
+            element: element.parse()?,
+        })
+    }
+}
+
+/// A VCS backend that can render a colocated JJ+Git repo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    Jj,
+    Git,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jj" => Ok(Self::Jj),
+            "git" => Ok(Self::Git),
+            _ => Err(format!("expected jj or git, got {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Jj => "jj",
+            Self::Git => "git",
+        })
+    }
+}
+
+/// Which backend renders each part of a colocated repo's segment
+///
+/// When the two differ both backends are collected (combined mode), and the
+/// segment is the view backend's with the status backend's indicators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Colocated {
+    /// Prefix, name and id
+    pub view: Backend,
+    /// Status indicators (and the `--style-fd` style)
+    pub status: Backend,
+}
+
+impl Colocated {
+    /// Whether rendering reads the Git working tree
+    #[must_use]
+    pub fn uses_git(self) -> bool {
+        self.view == Backend::Git || self.status == Backend::Git
+    }
 }
 
 /// Configuration options
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     /// Max length for branch/bookmark name (0 = unlimited)
     pub truncate_name: usize,
@@ -47,19 +213,109 @@ pub struct Config {
     /// Max depth to search for ancestor bookmarks (0 = disabled, default: 10)
     pub ancestor_bookmark_depth: usize,
     /// Max bookmarks to display (0 = unlimited)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
     pub bookmarks_display_limit: usize,
     /// Prefixes to strip from bookmark names (comma-separated)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
     pub strip_bookmark_prefix: Vec<String>,
+    /// Revset whose conflicted commits are counted, e.g. `mutable()` (None =
+    /// no count)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub conflict_count: Option<String>,
+    /// Tracked file count above which the dirty scan is skipped (0 = unlimited)
+    pub large_repo_files: usize,
+    /// Packed object count above which history walks are skipped (0 = unlimited)
+    pub large_repo_objects: usize,
+    /// Network filesystem handling (fast path on NFS/SMB/sshfs)
+    pub network_fs: NetworkFs,
+    /// What `prompt` prints: the styled segment or a machine-readable model
+    pub output: OutputFormat,
+    /// Zero-width markers around ANSI escapes for raw shell prompts
+    pub escapes: Escapes,
+    /// Right-prompt variant: symbol, shortest id, one status glyph
+    pub compact: bool,
+    /// Plain sentence instead of glyphs (`--verbose-words`)
+    pub verbose_words: bool,
+    /// Link names and ids to their forge pages (`--hyperlinks`)
+    pub hyperlinks: bool,
+    /// Desktop notification when the repo enters a conflict or divergent
+    /// state (`--notify`)
+    pub notify: bool,
+    /// Forge the links point at; [`output::render`](crate::output::render)
+    /// finds it from the repo's remote when None
+    pub forge: Option<Forge>,
+    /// Color the change id prefix and rest from jj's `colors` settings, as
+    /// `jj log` does; [`output::render`](crate::output::render) reads them
+    pub jj_colors: bool,
+    /// `[hooks.NAME]` commands from the config file
+    pub hooks: Vec<Hook>,
+    /// Part of the segment to print (name/id or status, for split prompts)
+    pub segment: Segment,
+    /// Separators between rendered elements
+    pub layout: Layout,
+    /// Segment colors for `--output powerline`
+    pub powerline: Powerline,
+    /// Backends rendering colocated JJ+Git repos
+    #[cfg_attr(not(all(feature = "jj", feature = "git")), allow(dead_code))]
+    pub colocated: Colocated,
+    /// Status indicator glyphs
+    pub symbols: Symbols,
+    /// Color preset (`--theme`)
+    pub theme: Theme,
+    /// Replacements for the default element colors
+    pub palette: Palette,
+    /// Styles replacing the colors of segment parts (`--style`)
+    pub styles: Styles,
+    /// Styles for the symbol and status by repo state (`--state-style`)
+    pub state_styles: StateStyles,
+    /// Limits on the upward repo search
+    pub boundaries: Boundaries,
+    /// Per-phase time budgets
+    pub budgets: Budgets,
+    /// Never write or lock repo state (skips the jj index)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub read_only: bool,
+    /// Render JJ repos from the on-disk snapshot while the op head is unchanged
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub use_cache: bool,
+    /// Directory for JJ snapshots and `--notify` state, None for neither
+    pub cache_dir: Option<PathBuf>,
+    /// How long a cache file is kept without being rewritten, None for ever
+    pub cache_max_age: Option<Duration>,
+    /// Once the op head has moved, render the previous snapshot instead of
+    /// collecting; the binary refreshes it in the background
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub stale_while_revalidate: bool,
     /// Symbol prefix for JJ repos
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
     pub jj_symbol: Cow<'static, str>,
     /// Symbol prefix for Git repos
     #[cfg_attr(not(feature = "git"), allow(dead_code))]
     pub git_symbol: Cow<'static, str>,
     /// JJ display options
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
     pub jj_display: DisplayConfig,
     /// Git display options
     #[cfg_attr(not(feature = "git"), allow(dead_code))]
     pub git_display: DisplayConfig,
+    /// Symbol prefix for Mercurial repos
+    pub hg_symbol: Cow<'static, str>,
+    /// Mercurial display options (no status is collected)
+    pub hg_display: DisplayConfig,
+    /// Symbol prefix for Sapling repos
+    pub sl_symbol: Cow<'static, str>,
+    /// Sapling display options (no status is collected)
+    pub sl_display: DisplayConfig,
+    /// Symbol prefix for Pijul repos
+    pub pijul_symbol: Cow<'static, str>,
+    /// Pijul display options (only the channel name is collected)
+    pub pijul_display: DisplayConfig,
+    /// Elements shown only in some repo states (`--show-if`)
+    pub show_if: Vec<DisplayRule>,
+    /// Print nothing while the repo is clean, synced and on its default branch
+    pub only_when_dirty: bool,
+    /// Counts a Git indicator must exceed to be shown
+    pub count_thresholds: CountThresholds,
 }
 
 impl Default for Config {
@@ -70,10 +326,48 @@ impl Default for Config {
             ancestor_bookmark_depth: 10,
             bookmarks_display_limit: 3,
             strip_bookmark_prefix: Vec::new(),
+            conflict_count: None,
+            large_repo_files: DEFAULT_LARGE_REPO_FILES,
+            large_repo_objects: DEFAULT_LARGE_REPO_OBJECTS,
+            network_fs: NetworkFs::Auto,
+            output: OutputFormat::Prompt,
+            escapes: Escapes::None,
+            compact: false,
+            verbose_words: false,
+            hyperlinks: false,
+            notify: false,
+            forge: None,
+            jj_colors: false,
+            hooks: Vec::new(),
+            segment: Segment::All,
+            layout: Layout::default(),
+            powerline: Powerline::default(),
+            colocated: Colocated::default(),
+            symbols: Symbols::default(),
+            theme: Theme::default(),
+            palette: Palette::default(),
+            styles: Styles::default(),
+            state_styles: StateStyles::default(),
+            boundaries: Boundaries::default(),
+            budgets: Budgets::default(),
+            read_only: false,
+            use_cache: true,
+            cache_dir: default_cache_dir(),
+            cache_max_age: max_age_days(DEFAULT_CACHE_MAX_AGE_DAYS),
+            stale_while_revalidate: false,
             jj_symbol: Cow::Borrowed(DEFAULT_JJ_SYMBOL),
             git_symbol: Cow::Borrowed(DEFAULT_GIT_SYMBOL),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            hg_symbol: Cow::Borrowed(DEFAULT_HG_SYMBOL),
+            hg_display: DisplayConfig::all_visible(),
+            sl_symbol: Cow::Borrowed(DEFAULT_SL_SYMBOL),
+            sl_display: DisplayConfig::all_visible(),
+            pijul_symbol: Cow::Borrowed(DEFAULT_PIJUL_SYMBOL),
+            pijul_display: DisplayConfig::all_visible(),
+            show_if: Vec::new(),
+            only_when_dirty: false,
+            count_thresholds: CountThresholds::default(),
         }
     }
 }
@@ -94,106 +388,695 @@ pub struct DisplayFlags {
 }
 
 impl DisplayFlags {
-    fn into_config(self, env_prefix: &str) -> DisplayConfig {
+    /// Display options for `repo` (`jj`, `git`, ...): hidden by a `--no-*`
+    /// flag, its env var or a `--hide` entry, unless a `--show` entry wins
+    fn into_config(self, repo: &str, visibility: &Visibility) -> DisplayConfig {
+        let env_prefix = format!("JJ_STARSHIP_NO_{}", repo.to_ascii_uppercase());
+        let shown = |flag: bool, var: &str, element: Element| {
+            let hidden = flag || setting(var).is_some() || visibility.hides(repo, element);
+            !hidden || visibility.shows(repo, element)
+        };
         DisplayConfig {
-            show_prefix: !self.no_prefix && env::var(format!("{env_prefix}_PREFIX")).is_err(),
-            show_name: !self.no_name && env::var(format!("{env_prefix}_NAME")).is_err(),
-            show_id: !self.no_id && env::var(format!("{env_prefix}_ID")).is_err(),
-            show_status: !self.no_status && env::var(format!("{env_prefix}_STATUS")).is_err(),
-            show_color: !self.no_color && env::var(format!("{env_prefix}_COLOR")).is_err(),
-            show_prefix_color: !self.no_prefix_color
-                && env::var("JJ_STARSHIP_NO_PREFIX_COLOR").is_err(),
+            show_prefix: shown(
+                self.no_prefix,
+                &format!("{env_prefix}_PREFIX"),
+                Element::Prefix,
+            ),
+            show_name: shown(self.no_name, &format!("{env_prefix}_NAME"), Element::Name),
+            show_id: shown(self.no_id, &format!("{env_prefix}_ID"), Element::Id),
+            show_status: shown(
+                self.no_status,
+                &format!("{env_prefix}_STATUS"),
+                Element::Status,
+            ),
+            show_color: !plain_requested()
+                && shown(
+                    self.no_color,
+                    &format!("{env_prefix}_COLOR"),
+                    Element::Color,
+                ),
+            show_prefix_color: shown(
+                self.no_prefix_color,
+                "JJ_STARSHIP_NO_PREFIX_COLOR",
+                Element::PrefixColor,
+            ),
         }
     }
 }
 
-impl Config {
-    /// Create config from CLI args and environment variables
-    /// CLI args take precedence over env vars
-    #[allow(clippy::fn_params_excessive_bools, clippy::too_many_arguments)]
-    pub fn new(
-        truncate_name: Option<usize>,
-        id_length: Option<usize>,
-        ancestor_bookmark_depth: Option<usize>,
-        bookmarks_display_limit: Option<usize>,
-        strip_bookmark_prefix: Option<String>,
-        jj_symbol: Option<String>,
-        git_symbol: Option<String>,
-        no_symbol: bool,
-        jj_flags: DisplayFlags,
-        git_flags: DisplayFlags,
-    ) -> Self {
-        let truncate_name = truncate_name
-            .or_else(|| env::var("JJ_STARSHIP_TRUNCATE_NAME").ok()?.parse().ok())
-            .unwrap_or(0);
+/// CLI `--hide`/`--show`/`--show-if` lists, `--only-when-dirty` and the
+/// `--count-threshold` entries
+#[derive(Debug, Clone, Default)]
+pub struct VisibilityFlags {
+    pub hide: Vec<ElementToggle>,
+    pub show: Vec<ElementToggle>,
+    pub show_if: Vec<DisplayRule>,
+    pub only_when_dirty: bool,
+    pub count_thresholds: Vec<CountThreshold>,
+}
 
-        let id_length = id_length
-            .or_else(|| env::var("JJ_STARSHIP_ID_LENGTH").ok()?.parse().ok())
-            .unwrap_or(8);
+impl VisibilityFlags {
+    fn into_visibility(self) -> Visibility {
+        Visibility {
+            hide: list(self.hide, "JJ_STARSHIP_HIDE"),
+            show: list(self.show, "JJ_STARSHIP_SHOW"),
+            show_if: list(self.show_if, "JJ_STARSHIP_SHOW_IF"),
+            only_when_dirty: self.only_when_dirty
+                || setting("JJ_STARSHIP_ONLY_WHEN_DIRTY").is_some(),
+            count_thresholds: count_thresholds(self.count_thresholds),
+        }
+    }
+}
 
-        let ancestor_bookmark_depth = ancestor_bookmark_depth
-            .or_else(|| {
-                env::var("JJ_STARSHIP_ANCESTOR_BOOKMARK_DEPTH")
-                    .ok()?
-                    .parse()
-                    .ok()
-            })
-            .unwrap_or(10);
-
-        let bookmarks_display_limit = bookmarks_display_limit
-            .or_else(|| {
-                env::var("JJ_STARSHIP_BOOKMARKS_DISPLAY_LIMIT")
-                    .ok()?
-                    .parse()
-                    .ok()
-            })
-            .unwrap_or(3);
+/// `JJ_STARSHIP_COUNT_THRESHOLDS` and then the CLI entries applied, so
+/// flags win on the same name
+fn count_thresholds(flags: Vec<CountThreshold>) -> CountThresholds {
+    let mut thresholds = CountThresholds::default();
+    for t in list(Vec::new(), "JJ_STARSHIP_COUNT_THRESHOLDS")
+        .into_iter()
+        .chain(flags)
+    {
+        t.apply(&mut thresholds);
+    }
+    thresholds
+}
+
+/// The flag's entries, else the comma-separated entries of `var` that parse
+fn list<T: FromStr>(flag: Vec<T>, var: &str) -> Vec<T> {
+    if !flag.is_empty() {
+        return flag;
+    }
+    setting(var)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|s| s.parse().ok())
+        .collect()
+}
+
+/// Resolved [`VisibilityFlags`]
+#[derive(Debug, Default)]
+struct Visibility {
+    hide: Vec<ElementToggle>,
+    show: Vec<ElementToggle>,
+    show_if: Vec<DisplayRule>,
+    only_when_dirty: bool,
+    count_thresholds: CountThresholds,
+}
 
-        let strip_bookmark_prefix: Vec<String> = strip_bookmark_prefix
-            .or_else(|| env::var("JJ_STARSHIP_STRIP_BOOKMARK_PREFIX").ok())
-            .map(|s| s.split(',').map(ToString::to_string).collect())
-            .unwrap_or_default();
+impl Visibility {
+    fn hides(&self, repo: &str, element: Element) -> bool {
+        self.hide.iter().any(|t| t.matches(repo, element))
+    }
 
-        let (jj_symbol, git_symbol) = if no_symbol {
-            (Cow::Borrowed(""), Cow::Borrowed(""))
-        } else {
-            let jj = jj_symbol
-                .or_else(|| env::var("JJ_STARSHIP_JJ_SYMBOL").ok())
-                .map_or(Cow::Borrowed(DEFAULT_JJ_SYMBOL), Cow::Owned);
-            let git = git_symbol
-                .or_else(|| env::var("JJ_STARSHIP_GIT_SYMBOL").ok())
-                .map_or(Cow::Borrowed(DEFAULT_GIT_SYMBOL), Cow::Owned);
-            (jj, git)
+    fn shows(&self, repo: &str, element: Element) -> bool {
+        self.show.iter().any(|t| t.matches(repo, element))
+    }
+}
+
+/// The set's status glyphs with `JJ_STARSHIP_SYMBOLS` and then the CLI
+/// overrides applied, so flags win on the same name
+fn status_symbols(symbol_set: SymbolSet, theme: Theme, overrides: Vec<SymbolOverride>) -> Symbols {
+    let mut symbols = symbol_set.status();
+    if theme.conflict_shape()
+        && let Some(glyph) = symbol_set.conflict_shape()
+    {
+        symbols.conflict = Cow::Borrowed(glyph);
+        symbols.conflicted = Cow::Borrowed(glyph);
+    }
+    let env_overrides = setting("JJ_STARSHIP_SYMBOLS").unwrap_or_default();
+    for o in env_overrides
+        .split(',')
+        .filter_map(|s| s.parse::<SymbolOverride>().ok())
+        .chain(overrides)
+    {
+        o.apply(&mut symbols);
+    }
+    symbols
+}
+
+/// The theme's colors, with starship's (`--starship-theme`) filling its gaps
+fn palette(theme: Theme, starship_theme: bool) -> Palette {
+    let starship = if starship_theme || setting("JJ_STARSHIP_STARSHIP_THEME").is_some() {
+        starship::load_palette()
+    } else {
+        Palette::default()
+    };
+    theme.palette().or(starship)
+}
+
+/// `JJ_STARSHIP_STYLES` and the `--style` entries
+fn part_styles(overrides: Vec<StyleOverride>) -> Styles {
+    let mut styles = Styles::default();
+    for (part, style) in parsed_styles(overrides, "JJ_STARSHIP_STYLES") {
+        styles.set(part, style);
+    }
+    styles
+}
+
+/// `JJ_STARSHIP_STATE_STYLES` and the `--state-style` entries
+fn state_styles(overrides: Vec<StyleOverride<State>>) -> StateStyles {
+    let mut styles = StateStyles::default();
+    for (state, style) in parsed_styles(overrides, "JJ_STARSHIP_STATE_STYLES") {
+        styles.set(state, style);
+    }
+    styles
+}
+
+/// `var`'s entries and then the CLI entries, with their style strings
+/// parsed, so flags win on the same target
+///
+/// Style strings may name starship palette colors; starship.toml is only
+/// read when one does. A style that doesn't parse is skipped (logged at
+/// warn).
+fn parsed_styles<T: FromStr<Err = String>>(
+    overrides: Vec<StyleOverride<T>>,
+    var: &str,
+) -> Vec<(T, Style)> {
+    let mut palette_colors = None;
+    list(Vec::new(), var)
+        .into_iter()
+        .chain(overrides)
+        .filter_map(|o| {
+            let style = starship::style(&o.style, &toml::Table::new()).or_else(|_| {
+                starship::style(
+                    &o.style,
+                    palette_colors.get_or_insert_with(starship::palette_colors),
+                )
+            });
+            match style {
+                Ok(style) => Some((o.target, style)),
+                Err(e) => {
+                    tracing::warn!("style {:?}: {e}", o.style);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Escape-free output requested for every repo type, via
+/// `JJ_STARSHIP_NO_ANSI`, the `NO_COLOR` convention (set and non-empty) or
+/// a [dumb terminal](dumb_terminal)
+fn plain_requested() -> bool {
+    setting("JJ_STARSHIP_NO_ANSI").is_some()
+        || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+        || dumb_terminal()
+}
+
+/// ASCII glyphs on a dumb terminal, else the default set
+fn default_symbol_set() -> SymbolSet {
+    if dumb_terminal() {
+        SymbolSet::Ascii
+    } else {
+        SymbolSet::default()
+    }
+}
+
+/// `TERM=dumb`, as set by Emacs shell-mode and editors' embedded shells:
+/// no escapes, and ASCII glyphs unless a symbol set is chosen
+///
+/// Whether stdout is a terminal says nothing here, since starship and
+/// `$(...)` prompts always capture it.
+fn dumb_terminal() -> bool {
+    env::var_os("TERM").is_some_and(|term| term == "dumb")
+}
+
+/// CLI flags for per-phase time budgets
+#[derive(Debug, Clone, Default)]
+pub struct BudgetFlags {
+    /// JJ divergence lookup budget in ms (0 = unbounded)
+    pub divergence_timeout_ms: Option<u64>,
+    /// Git status scan budget in ms (0 = unbounded)
+    pub status_timeout_ms: Option<u64>,
+    pub timeout_placeholder: Option<String>,
+}
+
+impl BudgetFlags {
+    fn into_budgets(self, default_placeholder: &'static str) -> Budgets {
+        let budget = |ms: Option<u64>, var: &str| {
+            ms.or_else(|| setting(var)?.parse().ok())
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis)
         };
 
-        Self {
-            truncate_name,
-            id_length,
-            ancestor_bookmark_depth,
-            bookmarks_display_limit,
-            strip_bookmark_prefix,
+        Budgets {
+            divergence: budget(
+                self.divergence_timeout_ms,
+                "JJ_STARSHIP_DIVERGENCE_TIMEOUT_MS",
+            ),
+            status: budget(self.status_timeout_ms, "JJ_STARSHIP_STATUS_TIMEOUT_MS"),
+            placeholder: self
+                .timeout_placeholder
+                .or_else(|| setting("JJ_STARSHIP_TIMEOUT_PLACEHOLDER"))
+                .map_or(Cow::Borrowed(default_placeholder), Cow::Owned),
+        }
+    }
+}
+
+/// CLI flags for the strings between rendered elements
+#[derive(Debug, Clone, Default)]
+pub struct LayoutFlags {
+    pub separator: Option<String>,
+    pub bookmark_separator: Option<String>,
+    pub prefix_word: Vec<PrefixWord>,
+    pub affixes: Vec<Affix>,
+}
+
+impl LayoutFlags {
+    fn into_layout(self) -> Layout {
+        let string = |flag: Option<String>, var: &str, default: &'static str| {
+            flag.or_else(|| setting(var))
+                .map_or(Cow::Borrowed(default), Cow::Owned)
+        };
+
+        Layout {
+            separator: string(self.separator, "JJ_STARSHIP_SEPARATOR", DEFAULT_SEPARATOR),
+            bookmark_separator: string(
+                self.bookmark_separator,
+                "JJ_STARSHIP_BOOKMARK_SEPARATOR",
+                DEFAULT_BOOKMARK_SEPARATOR,
+            ),
+            // Not `list`: an empty entry is a valid (empty) word
+            prefix_words: if self.prefix_word.is_empty() {
+                setting("JJ_STARSHIP_PREFIX_WORD")
+                    .map(|var| var.split(',').filter_map(|s| s.parse().ok()).collect())
+                    .unwrap_or_default()
+            } else {
+                self.prefix_word
+            },
+            affixes: list(self.affixes, "JJ_STARSHIP_AFFIXES"),
+        }
+    }
+}
+
+/// CLI flags for `--output powerline` colors
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerlineFlags {
+    pub bg: Option<TermColor>,
+    pub prev_bg: Option<TermColor>,
+    pub next_bg: Option<TermColor>,
+}
+
+impl PowerlineFlags {
+    fn into_powerline(self) -> Powerline {
+        let color =
+            |flag: Option<TermColor>, var: &str| flag.or_else(|| setting(var)?.parse().ok());
+
+        Powerline {
+            bg: color(self.bg, "JJ_STARSHIP_POWERLINE_BG").unwrap_or(DEFAULT_POWERLINE_BG),
+            prev: color(self.prev_bg, "JJ_STARSHIP_POWERLINE_PREV_BG"),
+            next: color(self.next_bg, "JJ_STARSHIP_POWERLINE_NEXT_BG"),
+        }
+    }
+}
+
+/// CLI flags choosing the backends for colocated repos
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColocatedFlags {
+    pub view: Option<Backend>,
+    /// Defaults to the view backend
+    pub status: Option<Backend>,
+}
+
+impl ColocatedFlags {
+    fn into_colocated(self) -> Colocated {
+        let backend =
+            |flag: Option<Backend>, var: &str| flag.or_else(|| setting(var)?.parse().ok());
+
+        let view = backend(self.view, "JJ_STARSHIP_COLOCATED_VIEW").unwrap_or_default();
+        Colocated {
+            view,
+            status: backend(self.status, "JJ_STARSHIP_COLOCATED_STATUS").unwrap_or(view),
+        }
+    }
+}
+
+/// CLI flags limiting the upward repo search
+#[derive(Debug, Clone, Default)]
+pub struct BoundaryFlags {
+    pub stop_at_home: bool,
+    pub one_filesystem: bool,
+    /// Max parent directories to search (0 = unlimited)
+    pub max_search_depth: Option<usize>,
+    /// Path globs never searched (comma-separated, `~/` expands to $HOME)
+    pub ignore_paths: Option<String>,
+    /// Separated Git dirs (comma-separated, `~/` expands to $HOME)
+    pub git_dirs: Option<String>,
+    /// Which repo to report when repos are nested
+    pub nesting: Option<Nesting>,
+    /// Symlink handling for the start directory
+    pub resolve_symlinks: Option<ResolveSymlinks>,
+}
+
+impl BoundaryFlags {
+    fn into_boundaries(self) -> Boundaries {
+        let stop_at_home = self.stop_at_home || setting("JJ_STARSHIP_STOP_AT_HOME").is_some();
+        let max_search_depth = self
+            .max_search_depth
+            .or_else(|| setting("JJ_STARSHIP_MAX_SEARCH_DEPTH")?.parse().ok())
+            .unwrap_or(0);
+
+        Boundaries {
+            stop_at: stop_at_home.then(home_dir).flatten(),
+            same_filesystem: self.one_filesystem || setting("JJ_STARSHIP_ONE_FILESYSTEM").is_some(),
+            max_depth: (max_search_depth > 0).then_some(max_search_depth),
+            ignore: path_list(self.ignore_paths, "JJ_STARSHIP_IGNORE_PATHS"),
+            git_dirs: path_list(self.git_dirs, "JJ_STARSHIP_GIT_DIRS"),
+            nesting: self
+                .nesting
+                .or_else(|| setting("JJ_STARSHIP_NESTING")?.parse().ok())
+                .unwrap_or_default(),
+            resolve_symlinks: self
+                .resolve_symlinks
+                .or_else(|| setting("JJ_STARSHIP_RESOLVE_SYMLINKS")?.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// CLI flags shaping names, ids and bookmark lists
+#[derive(Debug, Clone, Default)]
+pub struct NameFlags {
+    pub truncate_name: Option<usize>,
+    pub id_length: Option<usize>,
+    pub ancestor_bookmark_depth: Option<usize>,
+    pub bookmarks_display_limit: Option<usize>,
+    /// Prefixes to strip (comma-separated)
+    pub strip_bookmark_prefix: Option<String>,
+    /// Revset for the conflicted commit count
+    pub conflict_count: Option<String>,
+}
+
+/// CLI flags for how much collection does
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanFlags {
+    pub large_repo_files: Option<usize>,
+    pub large_repo_objects: Option<usize>,
+    pub network_fs: Option<NetworkFs>,
+    pub read_only: bool,
+    pub no_cache: bool,
+    pub stale_while_revalidate: bool,
+}
+
+/// CLI flags for the output format and what's written besides the prompt
+///
+/// Bools required for clap's flag parsing.
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct OutputFlags {
+    pub output: Option<OutputFormat>,
+    pub escapes: Option<Escapes>,
+    pub segment: Option<Segment>,
+    pub compact: bool,
+    pub verbose_words: bool,
+    pub hyperlinks: bool,
+    pub notify: bool,
+}
+
+/// CLI flags for glyphs and colors
+#[derive(Debug, Clone, Default)]
+pub struct ThemeFlags {
+    pub symbol_set: Option<SymbolSet>,
+    pub theme: Option<Theme>,
+    pub starship_theme: bool,
+    pub symbols: Vec<SymbolOverride>,
+    pub styles: Vec<StyleOverride>,
+    pub state_styles: Vec<StyleOverride<State>>,
+}
+
+/// CLI flags for the repo type prefixes
+#[derive(Debug, Clone, Default)]
+pub struct PrefixFlags {
+    pub jj_symbol: Option<String>,
+    pub git_symbol: Option<String>,
+    pub hg_symbol: Option<String>,
+    pub sl_symbol: Option<String>,
+    pub pijul_symbol: Option<String>,
+    /// Empty every prefix, winning over the symbols above
+    pub no_symbol: bool,
+}
+
+/// All CLI flags, as taken by [`Config::new`]
+#[derive(Debug, Clone, Default)]
+pub struct Flags {
+    pub names: NameFlags,
+    pub scan: ScanFlags,
+    pub output: OutputFlags,
+    pub theme: ThemeFlags,
+    pub prefixes: PrefixFlags,
+    pub boundaries: BoundaryFlags,
+    pub budgets: BudgetFlags,
+    pub layout: LayoutFlags,
+    pub powerline: PowerlineFlags,
+    pub colocated: ColocatedFlags,
+    pub visibility: VisibilityFlags,
+    pub jj: DisplayFlags,
+    pub git: DisplayFlags,
+}
+
+/// Comma-separated paths from the flag or `var`, with `~` expanded
+fn path_list(flag: Option<String>, var: &str) -> Vec<PathBuf> {
+    flag.or_else(|| setting(var))
+        .map(|s| {
+            s.split(',')
+                .filter(|path| !path.is_empty())
+                .map(expand_home)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A flag's value, else its setting parsed
+fn parsed<T: FromStr>(flag: Option<T>, var: &str) -> Option<T> {
+    flag.or_else(|| setting(var)?.parse().ok())
+}
+
+/// An option's env var, else its config file key
+fn setting(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .or_else(|| config_file().get(name).map(str::to_string))
+}
+
+/// The config file, loaded once
+fn config_file() -> &'static ConfigFile {
+    static FILE: OnceLock<ConfigFile> = OnceLock::new();
+    FILE.get_or_init(ConfigFile::load)
+}
+
+/// `JJ_STARSHIP_CACHE_DIR` (empty for none), else the default
+fn cache_dir() -> Option<PathBuf> {
+    match setting("JJ_STARSHIP_CACHE_DIR") {
+        Some(dir) => (!dir.is_empty()).then(|| expand_home(&dir)),
+        None => default_cache_dir(),
+    }
+}
+
+/// `$XDG_CACHE_HOME/jj-starship`, falling back to `~/.cache/jj-starship`
+#[must_use]
+pub fn default_cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".cache")))
+        .map(|dir| dir.join("jj-starship"))
+}
+
+/// `days` as a cache max age; 0 keeps files forever
+const fn max_age_days(days: u64) -> Option<Duration> {
+    if days == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(days.saturating_mul(86_400)))
+    }
+}
+
+/// `$HOME`, falling back to `%USERPROFILE%` on Windows where shells rarely
+/// set HOME
+#[must_use]
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| cfg!(windows).then(|| env::var_os("USERPROFILE")).flatten())
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Expand a leading `~` to the home dir, left as-is when there's none
+fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(path::is_separator) => {
+            rest.trim_start_matches(path::is_separator)
+        }
+        _ => return PathBuf::from(path),
+    };
+    match home_dir() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+impl Config {
+    /// Create config from CLI args, environment variables and the config file
+    /// CLI args take precedence over env vars, which take precedence over the file
+    #[must_use]
+    pub fn new(flags: Flags) -> Self {
+        let symbol_set = parsed(flags.theme.symbol_set, "JJ_STARSHIP_SYMBOL_SET")
+            .unwrap_or_else(default_symbol_set);
+        let theme = parsed(flags.theme.theme, "JJ_STARSHIP_THEME").unwrap_or_default();
+
+        let PrefixFlags {
             jj_symbol,
             git_symbol,
-            jj_display: jj_flags.into_config("JJ_STARSHIP_NO_JJ"),
-            git_display: git_flags.into_config("JJ_STARSHIP_NO_GIT"),
+            hg_symbol,
+            sl_symbol,
+            pijul_symbol,
+            no_symbol,
+        } = flags.prefixes;
+        let prefix = |symbol: Option<String>, var: &str, default: &'static str| {
+            if no_symbol {
+                return Cow::Borrowed("");
+            }
+            symbol
+                .or_else(|| setting(var))
+                .map_or(Cow::Borrowed(default), Cow::Owned)
+        };
+        let visibility = flags.visibility.into_visibility();
+        // Basic backends have no per-element flags; --no-color still applies
+        let basic_flags = DisplayFlags {
+            no_color: flags.jj.no_color || flags.git.no_color,
+            ..DisplayFlags::default()
+        };
+
+        Self {
+            truncate_name: parsed(flags.names.truncate_name, "JJ_STARSHIP_TRUNCATE_NAME")
+                .unwrap_or(0),
+            id_length: parsed(flags.names.id_length, "JJ_STARSHIP_ID_LENGTH").unwrap_or(8),
+            ancestor_bookmark_depth: parsed(
+                flags.names.ancestor_bookmark_depth,
+                "JJ_STARSHIP_ANCESTOR_BOOKMARK_DEPTH",
+            )
+            .unwrap_or(10),
+            bookmarks_display_limit: parsed(
+                flags.names.bookmarks_display_limit,
+                "JJ_STARSHIP_BOOKMARKS_DISPLAY_LIMIT",
+            )
+            .unwrap_or(3),
+            strip_bookmark_prefix: flags
+                .names
+                .strip_bookmark_prefix
+                .or_else(|| setting("JJ_STARSHIP_STRIP_BOOKMARK_PREFIX"))
+                .map(|s| s.split(',').map(ToString::to_string).collect())
+                .unwrap_or_default(),
+            conflict_count: flags
+                .names
+                .conflict_count
+                .or_else(|| setting("JJ_STARSHIP_CONFLICT_COUNT")),
+            large_repo_files: parsed(flags.scan.large_repo_files, "JJ_STARSHIP_LARGE_REPO_FILES")
+                .unwrap_or(DEFAULT_LARGE_REPO_FILES),
+            large_repo_objects: parsed(
+                flags.scan.large_repo_objects,
+                "JJ_STARSHIP_LARGE_REPO_OBJECTS",
+            )
+            .unwrap_or(DEFAULT_LARGE_REPO_OBJECTS),
+            network_fs: parsed(flags.scan.network_fs, "JJ_STARSHIP_NETWORK_FS").unwrap_or_default(),
+            output: parsed(flags.output.output, "JJ_STARSHIP_OUTPUT").unwrap_or_default(),
+            escapes: parsed(flags.output.escapes, "JJ_STARSHIP_ESCAPES").unwrap_or_default(),
+            compact: flags.output.compact || setting("JJ_STARSHIP_COMPACT").is_some(),
+            verbose_words: flags.output.verbose_words
+                || setting("JJ_STARSHIP_VERBOSE_WORDS").is_some(),
+            hyperlinks: flags.output.hyperlinks || setting("JJ_STARSHIP_HYPERLINKS").is_some(),
+            notify: flags.output.notify || setting("JJ_STARSHIP_NOTIFY").is_some(),
+            forge: None,
+            jj_colors: true,
+            hooks: config_file().hooks.clone(),
+            segment: parsed(flags.output.segment, "JJ_STARSHIP_SEGMENT").unwrap_or_default(),
+            boundaries: flags.boundaries.into_boundaries(),
+            budgets: flags.budgets.into_budgets(symbol_set.timeout()),
+            layout: flags.layout.into_layout(),
+            powerline: flags.powerline.into_powerline(),
+            colocated: flags.colocated.into_colocated(),
+            symbols: status_symbols(symbol_set, theme, flags.theme.symbols),
+            theme,
+            palette: palette(theme, flags.theme.starship_theme),
+            styles: part_styles(flags.theme.styles),
+            state_styles: state_styles(flags.theme.state_styles),
+            read_only: flags.scan.read_only || setting("JJ_STARSHIP_READ_ONLY").is_some(),
+            use_cache: !flags.scan.no_cache && setting("JJ_STARSHIP_NO_CACHE").is_none(),
+            cache_dir: cache_dir(),
+            cache_max_age: max_age_days(
+                parsed(None, "JJ_STARSHIP_CACHE_MAX_AGE_DAYS")
+                    .unwrap_or(DEFAULT_CACHE_MAX_AGE_DAYS),
+            ),
+            stale_while_revalidate: flags.scan.stale_while_revalidate
+                || setting("JJ_STARSHIP_STALE_WHILE_REVALIDATE").is_some(),
+            jj_symbol: prefix(jj_symbol, "JJ_STARSHIP_JJ_SYMBOL", symbol_set.jj()),
+            git_symbol: prefix(git_symbol, "JJ_STARSHIP_GIT_SYMBOL", symbol_set.git()),
+            jj_display: flags.jj.into_config("jj", &visibility),
+            git_display: flags.git.into_config("git", &visibility),
+            hg_symbol: prefix(hg_symbol, "JJ_STARSHIP_HG_SYMBOL", symbol_set.hg()),
+            hg_display: basic_flags.into_config("hg", &visibility),
+            sl_symbol: prefix(sl_symbol, "JJ_STARSHIP_SL_SYMBOL", symbol_set.sapling()),
+            sl_display: basic_flags.into_config("sl", &visibility),
+            pijul_symbol: prefix(pijul_symbol, "JJ_STARSHIP_PIJUL_SYMBOL", symbol_set.pijul()),
+            pijul_display: basic_flags.into_config("pijul", &visibility),
+            show_if: visibility.show_if,
+            only_when_dirty: visibility.only_when_dirty,
+            count_thresholds: visibility.count_thresholds,
+        }
+    }
+
+    /// Collection mode for the repo at `repo_root`: fast path on network
+    /// filesystems, otherwise scaled by the large-repo thresholds
+    #[must_use = "returns collection mode, does not modify state"]
+    pub fn collect_mode(&self, repo_root: &Path) -> CollectMode {
+        if self.network_fs.applies(repo_root) {
+            CollectMode::FastPath
+        } else {
+            CollectMode::Scaled(Thresholds {
+                files: self.large_repo_files,
+                objects: self.large_repo_objects,
+            })
+        }
+    }
+
+    /// Color markup for the output format, drawing from the palette
+    #[must_use]
+    pub const fn painter(&self) -> Painter {
+        Painter {
+            markup: self.output.markup(self.escapes),
+            palette: self.palette,
+            styles: self.styles,
         }
     }
 
     /// Truncate a string to max length, adding ellipsis if needed
     #[must_use = "returns truncated string, does not modify input"]
     pub fn truncate<'a>(&self, s: &'a str) -> Cow<'a, str> {
-        if self.truncate_name == 0 || s.chars().count() <= self.truncate_name {
-            Cow::Borrowed(s)
-        } else if self.truncate_name <= 1 {
-            Cow::Borrowed("…")
-        } else {
-            let truncated: String = s.chars().take(self.truncate_name - 1).collect();
-            Cow::Owned(truncated + "…")
+        text::ellipsize(s, self.truncate_name)
+    }
+
+    /// A bookmark, branch or channel name as printed: truncated, with
+    /// control characters (a stray escape sequence) replaced, and escaped
+    /// for the output format's markup
+    #[must_use = "returns display string, does not modify input"]
+    pub fn name<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let mut name = self.truncate(s);
+        if name.contains(char::is_control) {
+            name = Cow::Owned(
+                name.chars()
+                    .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+                    .collect(),
+            );
+        }
+        match self.output.markup(self.escapes).escape(&name) {
+            Cow::Borrowed(_) => name,
+            Cow::Owned(escaped) => Cow::Owned(escaped),
         }
     }
 
     /// Strip matching prefix from bookmark name (first match wins)
     #[must_use = "returns stripped string, does not modify input"]
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
     pub fn strip_prefix<'a>(&self, s: &'a str) -> Cow<'a, str> {
         for prefix in &self.strip_bookmark_prefix {
             if let Some(stripped) = s.strip_prefix(prefix) {
@@ -0,0 +1,582 @@
+//! Config file - `~/.config/jj-starship/config.toml`
+//!
+//! Keys are the environment variable names without `JJ_STARSHIP_`, in
+//! lowercase (`id_length = 12`, `no_git_id = true`). The file sits below the
+//! environment: CLI args > env vars > config file > defaults.
+
+use crate::color::{Escapes, Part, State, StyleOverride, TermColor, Theme};
+use crate::config::{Backend, ElementToggle, home_dir};
+use crate::detect::{Nesting, ResolveSymlinks};
+use crate::hooks::Hook;
+use crate::netfs::NetworkFs;
+use crate::output::{
+    Affix, CountThreshold, DisplayRule, OutputFormat, Segment, SymbolOverride, SymbolSet,
+};
+use crate::starship;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::str::FromStr;
+use toml::de::{DeTable, DeValue};
+use toml::{Spanned, Table, Value};
+
+/// Overrides the config file location; set but empty disables the file
+pub const PATH_VAR: &str = "JJ_STARSHIP_CONFIG";
+
+/// Prefix shared by the environment variables the keys are named after
+const ENV_PREFIX: &str = "JJ_STARSHIP_";
+
+/// Every key the file understands
+pub const KEYS: &[&str] = &[
+    "truncate_name",
+    "id_length",
+    "ancestor_bookmark_depth",
+    "bookmarks_display_limit",
+    "strip_bookmark_prefix",
+    "conflict_count",
+    "large_repo_files",
+    "large_repo_objects",
+    "network_fs",
+    "output",
+    "escapes",
+    "segment",
+    "separator",
+    "bookmark_separator",
+    "prefix_word",
+    "affixes",
+    "powerline_bg",
+    "powerline_prev_bg",
+    "powerline_next_bg",
+    "stop_at_home",
+    "one_filesystem",
+    "max_search_depth",
+    "ignore_paths",
+    "git_dirs",
+    "nesting",
+    "resolve_symlinks",
+    "divergence_timeout_ms",
+    "status_timeout_ms",
+    "timeout_placeholder",
+    "read_only",
+    "no_cache",
+    "stale_while_revalidate",
+    "cache_dir",
+    "cache_max_age_days",
+    "compact",
+    "verbose_words",
+    "hyperlinks",
+    "notify",
+    "starship_theme",
+    "symbol_set",
+    "theme",
+    "symbols",
+    "styles",
+    "state_styles",
+    "jj_symbol",
+    "git_symbol",
+    "hg_symbol",
+    "sl_symbol",
+    "pijul_symbol",
+    "hide",
+    "show",
+    "show_if",
+    "only_when_dirty",
+    "count_thresholds",
+    "no_jj_prefix",
+    "no_jj_color",
+    "no_jj_name",
+    "no_jj_id",
+    "no_jj_status",
+    "no_prefix_color",
+    "no_git_prefix",
+    "no_git_color",
+    "no_git_name",
+    "no_git_id",
+    "no_git_status",
+    "no_hg_prefix",
+    "no_hg_color",
+    "no_hg_name",
+    "no_hg_id",
+    "no_sl_prefix",
+    "no_sl_color",
+    "no_sl_name",
+    "no_sl_id",
+    "no_pijul_prefix",
+    "no_pijul_color",
+    "no_pijul_name",
+    "colocated_view",
+    "colocated_status",
+    "no_ansi",
+    "hooks",
+];
+
+/// Keys holding `true`/`false`
+const FLAG_KEYS: &[&str] = &[
+    "stop_at_home",
+    "one_filesystem",
+    "read_only",
+    "no_cache",
+    "stale_while_revalidate",
+    "compact",
+    "verbose_words",
+    "hyperlinks",
+    "notify",
+    "starship_theme",
+    "only_when_dirty",
+    "no_ansi",
+];
+
+/// `$JJ_STARSHIP_CONFIG`, else `jj-starship/config.toml` under
+/// `$XDG_CONFIG_HOME` or `~/.config` (on every platform, like starship)
+#[must_use]
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(PATH_VAR) {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("jj-starship/config.toml"))
+}
+
+/// Parsed config file values, as the strings the env vars would hold
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    values: BTreeMap<String, String>,
+    /// `[hooks.NAME]` tables, in name order
+    pub hooks: Vec<Hook>,
+}
+
+impl ConfigFile {
+    /// Read the file at [`path()`]; empty when there's none
+    ///
+    /// A file that can't be read or parsed is logged at warn and ignored, so
+    /// a typo never takes the prompt down.
+    #[must_use]
+    pub fn load() -> Self {
+        let Some(path) = path() else {
+            return Self::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                tracing::warn!("{}: {e}", path.display());
+                return Self::default();
+            }
+        };
+        Self::parse(&text).unwrap_or_else(|e| {
+            tracing::warn!("{}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    /// Parse config file contents
+    ///
+    /// Booleans become presence (`false` is the same as leaving the key
+    /// out), arrays are joined with commas and the `symbols` table becomes
+    /// `NAME=GLYPH` pairs. Values of other types are skipped, as are
+    /// `[hooks.NAME]` tables that don't make a [`Hook`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the TOML syntax error.
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        let mut table: Table = text.parse()?;
+        let hooks = match table.remove("hooks") {
+            Some(Value::Table(hooks)) => hooks
+                .iter()
+                .filter_map(|(name, hook)| {
+                    Hook::from_table(name, hook)
+                        .inspect_err(|e| tracing::warn!("hooks.{name}: {e}"))
+                        .ok()
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let values = table
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, setting(value)?)))
+            .collect();
+        Ok(Self { values, hooks })
+    }
+
+    /// Value for an environment variable name (`JJ_STARSHIP_ID_LENGTH`
+    /// reads `id_length`)
+    #[must_use]
+    pub fn get(&self, var: &str) -> Option<&str> {
+        let key = var.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+        self.values.get(&key).map(String::as_str)
+    }
+}
+
+/// One value in env var form
+fn setting(value: Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s),
+        Value::Integer(n) => Some(n.to_string()),
+        Value::Boolean(set) => set.then(|| "1".to_string()),
+        Value::Array(items) => Some(
+            items
+                .into_iter()
+                .filter_map(setting)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        Value::Table(pairs) => Some(
+            pairs
+                .into_iter()
+                .filter_map(|(name, glyph)| Some(format!("{name}={}", setting(glyph)?)))
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        Value::Float(_) | Value::Datetime(_) => None,
+    }
+}
+
+/// How bad a [`Diagnostic`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The setting is ignored
+    Error,
+    /// The setting applies, but probably not as intended
+    Warning,
+}
+
+/// One problem in a config file, at a 1-based line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {severity}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// Validate config file contents: syntax, unknown keys, value types, values
+/// each option would reject (colors, enums, `--hide` entries, ...) and
+/// settings that cancel each other out
+///
+/// A file with errors still loads; the settings they point at fall back to
+/// their defaults.
+#[must_use]
+pub fn check(text: &str) -> Vec<Diagnostic> {
+    let at = |span: Range<usize>, severity, message: String| {
+        let before = &text[..span.start.min(text.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Diagnostic {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            severity,
+            message,
+        }
+    };
+    let table = match DeTable::parse(text) {
+        Ok(table) => table.into_inner(),
+        Err(e) => {
+            let span = e.span().unwrap_or(0..0);
+            return vec![at(span, Severity::Error, e.message().to_string())];
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    for (key, value) in &table {
+        let name = key.get_ref().as_ref();
+        if !KEYS.contains(&name) {
+            diagnostics.push(at(
+                key.span(),
+                Severity::Error,
+                format!("unknown key `{name}`"),
+            ));
+            continue;
+        }
+        if name == "hooks" {
+            let parsed: Table = text.parse().unwrap_or_default();
+            for (span, problem) in check_hooks(value, parsed.get("hooks")) {
+                diagnostics.push(at(span, Severity::Error, problem));
+            }
+            continue;
+        }
+        for (span, problem) in check_value(name, value) {
+            diagnostics.push(at(span, Severity::Error, format!("`{name}`: {problem}")));
+        }
+    }
+
+    let flag = |name: &str| table.get(name).and_then(|v| v.get_ref().as_bool()) == Some(true);
+    if flag("starship_theme")
+        && flag("no_ansi")
+        && let Some((key, _)) = table.get_key_value("starship_theme")
+    {
+        diagnostics.push(at(
+            key.span(),
+            Severity::Warning,
+            "has no effect: `no_ansi` turns colors off".to_string(),
+        ));
+    }
+    let toggles = |name: &str| -> Vec<(Range<usize>, ElementToggle)> {
+        list_items(table.get(name))
+            .filter_map(|(span, item)| Some((span, item.parse().ok()?)))
+            .collect()
+    };
+    let hidden = toggles("hide");
+    for (span, shown) in toggles("show") {
+        if hidden.iter().any(|(_, hide)| *hide == shown) {
+            diagnostics.push(at(
+                span,
+                Severity::Warning,
+                "also in `hide`; `show` wins".to_string(),
+            ));
+        }
+    }
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+/// Problems with the `[hooks.NAME]` tables, each at the table's name;
+/// `parsed` is the same value as a plain TOML value
+fn check_hooks(
+    value: &Spanned<DeValue<'_>>,
+    parsed: Option<&Value>,
+) -> Vec<(Range<usize>, String)> {
+    let (DeValue::Table(hooks), Some(Value::Table(parsed))) = (value.get_ref(), parsed) else {
+        let found = value.get_ref().type_str();
+        return vec![(
+            value.span(),
+            format!("`hooks`: expected a table, found {found}"),
+        )];
+    };
+    hooks
+        .iter()
+        .filter_map(|(name, _)| {
+            let hook = parsed.get(name.get_ref().as_ref())?;
+            let e = Hook::from_table(name.get_ref(), hook).err()?;
+            Some((name.span(), format!("`hooks.{}`: {e}", name.get_ref())))
+        })
+        .collect()
+}
+
+/// Problems with one known key's value, each at the span to point at
+fn check_value(name: &str, value: &Spanned<DeValue<'_>>) -> Vec<(Range<usize>, String)> {
+    let is_flag = FLAG_KEYS.contains(&name) || (name.starts_with("no_") && name != "no_ansi");
+    let expected = match (value.get_ref(), is_flag) {
+        (DeValue::Boolean(_), true) => return Vec::new(),
+        (_, true) => "true or false",
+        (DeValue::Boolean(_) | DeValue::Float(_) | DeValue::Datetime(_), false) => {
+            "a string or a number"
+        }
+        (DeValue::Table(_), false)
+            if !matches!(
+                name,
+                "symbols" | "styles" | "state_styles" | "count_thresholds"
+            ) =>
+        {
+            "a string or a number"
+        }
+        _ => "",
+    };
+    if !expected.is_empty() {
+        let found = value.get_ref().type_str();
+        return vec![(value.span(), format!("expected {expected}, found {found}"))];
+    }
+    list_items(Some(value))
+        .filter_map(|(span, item)| Some((span, check_setting(name, &item).err()?)))
+        .collect()
+}
+
+/// Each entry of a value: list items, `symbols` pairs, or the value itself
+fn list_items<'a>(
+    value: Option<&'a Spanned<DeValue<'_>>>,
+) -> Box<dyn Iterator<Item = (Range<usize>, String)> + 'a> {
+    let Some(value) = value else {
+        return Box::new(std::iter::empty());
+    };
+    match value.get_ref() {
+        DeValue::Array(items) => Box::new(items.iter().flat_map(|item| list_items(Some(item)))),
+        DeValue::Table(pairs) => Box::new(pairs.iter().map(|(name, entry)| {
+            let entry = match entry.get_ref() {
+                DeValue::Integer(n) => n.as_str().replace('_', ""),
+                entry => entry.as_str().unwrap_or_default().to_string(),
+            };
+            (name.span(), format!("{}={entry}", name.get_ref()))
+        })),
+        DeValue::String(s) => Box::new(s.split(',').map(|item| (value.span(), item.to_string()))),
+        DeValue::Integer(n) => {
+            Box::new(std::iter::once((value.span(), n.as_str().replace('_', ""))))
+        }
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+/// Whether the option behind `name` accepts `value`, in env var form
+fn check_setting(name: &str, value: &str) -> Result<(), String> {
+    fn parse<T: FromStr<Err = String>>(value: &str) -> Result<(), String> {
+        value.parse::<T>().map(drop)
+    }
+    match name {
+        "truncate_name"
+        | "id_length"
+        | "ancestor_bookmark_depth"
+        | "bookmarks_display_limit"
+        | "large_repo_files"
+        | "large_repo_objects"
+        | "max_search_depth"
+        | "divergence_timeout_ms"
+        | "status_timeout_ms"
+        | "cache_max_age_days" => value
+            .parse::<u64>()
+            .map(drop)
+            .map_err(|_| format!("expected a non-negative number, got {value:?}")),
+        "network_fs" => parse::<NetworkFs>(value),
+        "output" => parse::<OutputFormat>(value),
+        "escapes" => parse::<Escapes>(value),
+        "segment" => parse::<Segment>(value),
+        "symbol_set" => parse::<SymbolSet>(value),
+        "theme" => parse::<Theme>(value),
+        "powerline_bg" | "powerline_prev_bg" | "powerline_next_bg" => parse::<TermColor>(value),
+        "nesting" => parse::<Nesting>(value),
+        "resolve_symlinks" => parse::<ResolveSymlinks>(value),
+        "colocated_view" | "colocated_status" => parse::<Backend>(value),
+        "hide" | "show" => parse::<ElementToggle>(value),
+        "show_if" => parse::<DisplayRule>(value),
+        "affixes" => parse::<Affix>(value),
+        "symbols" => parse::<SymbolOverride>(value),
+        "count_thresholds" => parse::<CountThreshold>(value),
+        "styles" => {
+            let o: StyleOverride<Part> = value.parse()?;
+            starship::style(&o.style, &starship::palette_colors()).map(drop)
+        }
+        "state_styles" => {
+            let o: StyleOverride<State> = value.parse()?;
+            starship::style(&o.style, &starship::palette_colors()).map(drop)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_in_env_form() {
+        let file = ConfigFile::parse(
+            r#"
+id_length = 12
+no_git_id = true
+compact = false
+separator = " | "
+strip_bookmark_prefix = ["feat/", "fix/"]
+
+[symbols]
+modified = "*"
+ahead = "up"
+"#,
+        )
+        .unwrap();
+        assert_eq!(file.get("JJ_STARSHIP_ID_LENGTH"), Some("12"));
+        assert_eq!(file.get("JJ_STARSHIP_NO_GIT_ID"), Some("1"));
+        assert_eq!(file.get("JJ_STARSHIP_COMPACT"), None);
+        assert_eq!(file.get("JJ_STARSHIP_SEPARATOR"), Some(" | "));
+        assert_eq!(
+            file.get("JJ_STARSHIP_STRIP_BOOKMARK_PREFIX"),
+            Some("feat/,fix/")
+        );
+        assert_eq!(file.get("JJ_STARSHIP_SYMBOLS"), Some("ahead=up,modified=*"));
+        assert_eq!(file.get("NO_COLOR"), None);
+    }
+
+    #[test]
+    fn hook_tables() {
+        let text = r#"compact = true
+
+[hooks.ci]
+command = "ci-status --short"
+timeout_ms = 100
+
+[hooks.bad]
+command = 5
+"#;
+        let file = ConfigFile::parse(text).unwrap();
+        assert_eq!(file.get("JJ_STARSHIP_COMPACT"), Some("1"));
+        assert_eq!(file.get("JJ_STARSHIP_HOOKS"), None);
+        assert_eq!(
+            file.hooks,
+            [Hook {
+                name: "ci".into(),
+                command: "ci-status --short".into(),
+                timeout: std::time::Duration::from_millis(100),
+            }]
+        );
+        let report: Vec<String> = check(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            report,
+            ["7:8: error: `hooks.bad`: `command`: expected a command line"]
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        assert!(ConfigFile::parse("id_length = ").is_err());
+        let diagnostics = check("compact = true\nid_length = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].severity),
+            (2, Severity::Error)
+        );
+    }
+
+    #[test]
+    fn check_points_at_bad_settings() {
+        let text = r##"id_length = 12
+idlength = 4
+compact = "yes"
+powerline_bg = "#12345"
+hide = ["jj:id", "git:branch"]
+show = "jj:id"
+no_ansi = true
+starship_theme = true
+
+[symbols]
+modified = "*"
+bogus = "x"
+
+[count_thresholds]
+behind = 2
+
+[styles]
+name = "bold fg:#ff8800"
+"##;
+        let report: Vec<String> = check(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            report[..6],
+            [
+                "2:1: error: unknown key `idlength`",
+                "3:11: error: `compact`: expected true or false, found string",
+                "4:16: error: `powerline_bg`: expected #rrggbb, got \"#12345\"",
+                "5:18: error: `hide`: expected prefix, name, id, status, color or prefix-color, got \"branch\"",
+                "6:8: warning: also in `hide`; `show` wins",
+                "8:1: warning: has no effect: `no_ansi` turns colors off",
+            ]
+        );
+        assert!(report[6].starts_with("12:1: error: `symbols`: unknown symbol \"bogus\""));
+        assert!(check("id_length = 8\nno_git_id = true\n").is_empty());
+    }
+}
@@ -1,9 +1,22 @@
-//! Repo type detection - walks up from cwd to find .jj or .git
+//! Repo type detection - walks up from cwd to find .jj, .git, .hg, .sl or
+//! .pijul
+//!
+//! A repo whose root holds a `.jj-starship-ignore` file is reported as no repo.
 
-use std::path::{Path, PathBuf};
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::str::FromStr;
 
 /// Type of repository detected
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 #[non_exhaustive]
 pub enum RepoType {
     /// Pure JJ repo (.jj/ only)
@@ -12,54 +25,877 @@ pub enum RepoType {
     JjColocated,
     /// Pure Git repo (.git/ only)
     Git,
+    /// Mercurial repo (.hg/)
+    Hg,
+    /// Sapling repo (.sl/)
+    Sapling,
+    /// Pijul repo (.pijul/)
+    Pijul,
     /// Not in any repo
+    #[default]
     None,
 }
 
+impl RepoType {
+    /// Stable name used in machine-readable output, None outside a repo
+    #[must_use]
+    pub const fn name(self) -> Option<&'static str> {
+        match self {
+            Self::Jj => Some("jj"),
+            Self::JjColocated => Some("jj-colocated"),
+            Self::Git => Some("git"),
+            Self::Hg => Some("hg"),
+            Self::Sapling => Some("sapling"),
+            Self::Pijul => Some("pijul"),
+            Self::None => None,
+        }
+    }
+
+    /// Whether the repo is a JJ repo (colocated or not)
+    #[must_use]
+    pub const fn is_jj(self) -> bool {
+        matches!(self, Self::Jj | Self::JjColocated)
+    }
+}
+
+/// Limits on the upward walk from the start directory
+///
+/// Walking to `/` stats every ancestor, which can stall on automounted or
+/// network paths. All limits are disabled by default.
+#[derive(Debug, Clone, Default)]
+pub struct Boundaries {
+    /// Don't walk above this directory (checked itself, parents are not)
+    pub stop_at: Option<PathBuf>,
+    /// Don't cross onto a different filesystem/mount (unix only)
+    pub same_filesystem: bool,
+    /// Max parent directories to visit above the start (None = unlimited)
+    pub max_depth: Option<usize>,
+    /// Paths never searched: a start directory matching one of these globs,
+    /// or below a match, is reported as not in a repo without any stat
+    pub ignore: Vec<PathBuf>,
+    /// Git dirs kept apart from their work tree (bare dotfile repos, yadm).
+    /// A start directory inside one's work tree is in that Git repo.
+    pub git_dirs: Vec<PathBuf>,
+    /// Which repo to report when repos are nested
+    pub nesting: Nesting,
+    /// Whether symlinks in the start path are resolved first
+    pub resolve_symlinks: ResolveSymlinks,
+}
+
+impl Boundaries {
+    /// Whether `path` or one of its parents matches an ignore glob
+    #[must_use]
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.ignore.iter().any(|glob| glob_matches(glob, path))
+    }
+
+    /// The separated Git dir whose work tree is exactly `work_tree`
+    #[must_use]
+    pub fn separate_git_dir(&self, work_tree: &Path) -> Option<&Path> {
+        self.git_dirs
+            .iter()
+            .find(|git_dir| separate_work_tree(git_dir).is_some_and(|tree| tree == work_tree))
+            .map(PathBuf::as_path)
+    }
+
+    /// The innermost separated work tree containing `start`, with its Git dir
+    fn separate_repo(&self, start: &Path) -> Option<(&Path, PathBuf)> {
+        self.git_dirs
+            .iter()
+            .filter_map(|git_dir| Some((git_dir.as_path(), separate_work_tree(git_dir)?)))
+            .filter(|(_, work_tree)| start.starts_with(work_tree))
+            .max_by_key(|(_, work_tree)| work_tree.components().count())
+    }
+}
+
+/// File at a repo root that disables output for that repo
+pub const IGNORE_MARKER: &str = ".jj-starship-ignore";
+
+/// Which repo to report when repos are nested (e.g. a Git checkout inside a
+/// JJ repo)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Nesting {
+    /// The repo closest to the start directory
+    #[default]
+    Innermost,
+    /// The repo furthest up; the walk continues to the boundaries
+    Outermost,
+    /// The innermost JJ repo, else the innermost repo
+    PreferJj,
+}
+
+impl FromStr for Nesting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "innermost" => Ok(Self::Innermost),
+            "outermost" => Ok(Self::Outermost),
+            "prefer-jj" => Ok(Self::PreferJj),
+            _ => Err(format!(
+                "expected innermost, outermost or prefer-jj, got {s:?}"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Nesting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Innermost => "innermost",
+            Self::Outermost => "outermost",
+            Self::PreferJj => "prefer-jj",
+        })
+    }
+}
+
+/// Symlink handling for the start directory
+///
+/// Shells track the logical path (`$PWD`, through symlinks) while the
+/// process cwd and jj/git report the physical one, so a repo reached via
+/// `~/code -> /mnt/data/code` has two spellings of its root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResolveSymlinks {
+    /// Walk up from the path as given
+    #[default]
+    Auto,
+    /// Canonicalize first, so `repo_root` matches what jj/git print
+    On,
+    /// Keep the logical path; [`ResolveSymlinks::start_dir`] prefers `$PWD`
+    /// over the process cwd
+    Off,
+}
+
+impl FromStr for ResolveSymlinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "on" => Ok(Self::On),
+            "off" => Ok(Self::Off),
+            _ => Err(format!("expected auto, on or off, got {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for ResolveSymlinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::On => "on",
+            Self::Off => "off",
+        })
+    }
+}
+
+impl ResolveSymlinks {
+    /// Directory to detect from: `cwd` when given, else the process's
+    /// current directory (spelled as `$PWD` under [`Self::Off`])
+    #[must_use]
+    pub fn start_dir(self, cwd: Option<PathBuf>) -> Option<PathBuf> {
+        cwd.or_else(|| (self == Self::Off).then(logical_cwd)?)
+            .or_else(|| env::current_dir().ok())
+    }
+}
+
+/// `$PWD` when it names the process's current directory
+fn logical_cwd() -> Option<PathBuf> {
+    let pwd = PathBuf::from(env::var_os("PWD")?);
+    let physical = env::current_dir().ok()?;
+    (pwd.is_absolute() && pwd.canonicalize().ok()? == physical.canonicalize().ok()?).then_some(pwd)
+}
+
 /// Result of repo detection
-#[derive(Debug)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DetectResult {
     pub repo_type: RepoType,
+    /// Workspace root: the directory holding the repo's marker
     pub repo_root: Option<PathBuf>,
+    /// Underlying repo storage, shared by all workspaces of the repo: the JJ
+    /// repo dir (`.jj/repo`, or the one a secondary workspace points to), the
+    /// Git common dir (`.git`, or the main repo's for a worktree), or the
+    /// `.hg`/`.sl`/`.pijul` dir
+    pub store_path: Option<PathBuf>,
+    /// The repo's own metadata dir (`.jj`, `.git`, ...) when the start
+    /// directory is inside it rather than in the working copy
+    pub dot_dir: Option<String>,
+    /// Roots of every repo the walk found, innermost first. The walk stops
+    /// at the first repo unless [`Boundaries::nesting`] looks further up.
+    pub nested_roots: Vec<PathBuf>,
 }
 
 /// Detect repo type by walking up from the given path
 #[must_use = "returns detection result, does not modify state"]
-pub fn detect(start: &Path) -> DetectResult {
-    let mut current = start.to_path_buf();
+pub fn detect(start: &Path, boundaries: &Boundaries) -> DetectResult {
+    if boundaries.is_ignored(start) {
+        return DetectResult::default();
+    }
+    let resolved = match boundaries.resolve_symlinks {
+        ResolveSymlinks::On => start.canonicalize().ok(),
+        ResolveSymlinks::Auto | ResolveSymlinks::Off => None,
+    };
+    let start = strip_verbatim(resolved.unwrap_or_else(|| start.to_path_buf()));
+    if boundaries.is_ignored(&start) {
+        return DetectResult::default();
+    }
 
-    loop {
-        let has_jj = current.join(".jj").is_dir();
-        let has_git = current.join(".git").exists(); // can be file (worktree) or dir
-
-        let repo_type = match (has_jj, has_git) {
-            (true, true) => RepoType::JjColocated,
-            (true, false) => RepoType::Jj,
-            (false, true) => RepoType::Git,
-            (false, false) => RepoType::None,
-        };
+    let mut current = start.clone();
+    let start_device = if boundaries.same_filesystem {
+        device_id(&start)
+    } else {
+        None
+    };
+    let mut depth = 0;
+    let mut found: Vec<(RepoType, PathBuf)> = Vec::new();
 
+    loop {
+        let repo_type = repo_type_at(&current);
         if repo_type != RepoType::None {
-            return DetectResult {
-                repo_type,
-                repo_root: Some(current),
+            // An opted-out repo hides any repo around it too, rather than
+            // showing the enclosing one for a vendored checkout
+            if current.join(IGNORE_MARKER).exists() {
+                return DetectResult::default();
+            }
+            found.push((repo_type, current.clone()));
+            let done = match boundaries.nesting {
+                Nesting::Innermost => true,
+                Nesting::Outermost => false,
+                Nesting::PreferJj => repo_type.is_jj(),
             };
+            if done {
+                break;
+            }
+        }
+
+        if boundaries
+            .stop_at
+            .as_deref()
+            .is_some_and(|stop| same_path(stop, &current))
+            || boundaries.max_depth.is_some_and(|max| depth >= max)
+        {
+            break;
         }
 
         // Walk up
         if !current.pop() {
             break;
         }
+        depth += 1;
+
+        if start_device.is_some() && device_id(&current) != start_device {
+            break;
+        }
     }
 
+    // A separated work tree has no marker for the walk to find; it ranks
+    // among the found repos by depth, and a real repo at the same root wins
+    let separate = boundaries.separate_repo(&start).filter(|(_, work_tree)| {
+        cfg!(feature = "git") && !found.iter().any(|(_, root)| root == work_tree)
+    });
+    if let Some((_, work_tree)) = &separate {
+        let at = found
+            .iter()
+            .position(|(_, root)| work_tree.starts_with(root))
+            .unwrap_or(found.len());
+        found.insert(at, (RepoType::Git, work_tree.clone()));
+    }
+
+    let chosen = match boundaries.nesting {
+        Nesting::Innermost => found.first(),
+        Nesting::Outermost => found.last(),
+        Nesting::PreferJj => found
+            .iter()
+            .find(|(repo_type, _)| repo_type.is_jj())
+            .or(found.first()),
+    };
+    let Some((repo_type, repo_root)) = chosen.cloned() else {
+        return DetectResult::default();
+    };
+    let store_path = match separate {
+        Some((git_dir, work_tree)) if work_tree == repo_root => {
+            Some(lexically_normal(&common_dir(git_dir)))
+        }
+        _ => store_path(repo_type, &repo_root),
+    };
     DetectResult {
-        repo_type: RepoType::None,
-        repo_root: None,
+        repo_type,
+        store_path,
+        dot_dir: dot_dir(&start, &repo_root),
+        repo_root: Some(repo_root),
+        nested_roots: found.into_iter().map(|(_, root)| root).collect(),
+    }
+}
+
+/// `path` without a Windows verbatim prefix (`\\?\C:\x` is `C:\x`,
+/// `\\?\UNC\server\share` is `\\server\share`). `canonicalize` returns
+/// verbatim paths there, but `$HOME`, ignore globs and Git spell them plainly.
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path;
+    };
+    let plain = match prefix.kind() {
+        Prefix::VerbatimDisk(drive) => OsString::from(format!("{}:", char::from(drive))),
+        Prefix::VerbatimUNC(server, share) => {
+            let mut unc = OsString::from(r"\\");
+            unc.push(server);
+            unc.push(r"\");
+            unc.push(share);
+            unc
+        }
+        _ => return path,
+    };
+    let mut out = PathBuf::from(plain);
+    out.extend(components);
+    out
+}
+
+/// Storage shared by a Git dir's worktrees, per its `commondir` file
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim_end()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Work tree of a separated Git dir: `core.worktree` from its config
+/// (relative to the Git dir), else the dir holding it, as for `~/.cfg`
+/// checked out over `~`. None when `git_dir` has no config.
+fn separate_work_tree(git_dir: &Path) -> Option<PathBuf> {
+    let config = fs::read_to_string(git_dir.join("config")).ok()?;
+    let mut in_core = false;
+    for line in config.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[') {
+            in_core = section
+                .trim_end_matches(']')
+                .trim()
+                .eq_ignore_ascii_case("core");
+        } else if in_core
+            && let Some((key, value)) = line.split_once('=')
+            && key.trim().eq_ignore_ascii_case("worktree")
+        {
+            let value = value.trim().trim_matches('"');
+            return Some(lexically_normal(&git_dir.join(value)));
+        }
+    }
+    git_dir.parent().map(Path::to_path_buf)
+}
+
+/// Most symlinks and `gitdir:` files followed before giving up on a loop
+const MAX_GITDIR_HOPS: usize = 8;
+
+/// Git dir of the work tree at `root`, resolved like git does: `.git`
+/// itself when it's a directory, else the target of a symlink or of a
+/// gitfile's `gitdir:` line, through any chain of them. None when `.git`
+/// is missing, dangling or not a valid gitfile.
+#[must_use]
+pub fn git_dir(root: &Path) -> Option<PathBuf> {
+    let mut path = root.join(".git");
+    for _ in 0..MAX_GITDIR_HOPS {
+        let metadata = fs::symlink_metadata(&path).ok()?;
+        if metadata.is_dir() {
+            return Some(lexically_normal(&path));
+        }
+        // Resolved in full, so a relative gitdir in the linked file is
+        // taken relative to where that file lives
+        if metadata.is_symlink() {
+            path = path.canonicalize().ok()?;
+            continue;
+        }
+        let contents = fs::read_to_string(&path).ok()?;
+        let target = contents.strip_prefix("gitdir:")?.trim();
+        path = path.parent()?.join(target);
+    }
+    None
+}
+
+/// Path equality, ignoring ASCII case on Windows like its filesystems do
+fn same_path(a: &Path, b: &Path) -> bool {
+    if !cfg!(windows) {
+        return a == b;
+    }
+    let (a, b): (Vec<_>, Vec<_>) = (a.components().collect(), b.components().collect());
+    a.len() == b.len()
+        && a.iter()
+            .zip(&b)
+            .all(|(a, b)| a.as_os_str().eq_ignore_ascii_case(b.as_os_str()))
+}
+
+/// Marker dir of the repo at `root` that `start` lies within, if any
+fn dot_dir(start: &Path, root: &Path) -> Option<String> {
+    let Component::Normal(name) = start.strip_prefix(root).ok()?.components().next()? else {
+        return None;
+    };
+    [".jj", ".git", ".hg", ".sl", ".pijul"]
+        .into_iter()
+        .find(|marker| name == *marker)
+        .map(String::from)
+}
+
+/// Storage dir behind the workspace at `root`, following the pointer files
+/// secondary workspaces, worktrees and shares leave in place of the real dir
+fn store_path(repo_type: RepoType, root: &Path) -> Option<PathBuf> {
+    let path = match repo_type {
+        RepoType::Jj | RepoType::JjColocated => {
+            let jj_dir = root.join(".jj");
+            let repo = jj_dir.join("repo");
+            if repo.is_file() {
+                Some(jj_dir.join(fs::read_to_string(&repo).ok()?.trim_end()))
+            } else {
+                Some(repo)
+            }
+        }
+        RepoType::Git => Some(common_dir(&git_dir(root)?)),
+        RepoType::Hg => {
+            let hg_dir = root.join(".hg");
+            match fs::read_to_string(hg_dir.join("sharedpath")) {
+                Ok(shared) => Some(hg_dir.join(shared.trim_end())),
+                Err(_) => Some(hg_dir),
+            }
+        }
+        RepoType::Sapling => Some(root.join(".sl")),
+        RepoType::Pijul => Some(root.join(".pijul")),
+        RepoType::None => None,
+    };
+    path.map(|path| lexically_normal(&path))
+}
+
+/// Drop `.` and fold `name/..` so relative pointers read like the real path
+fn lexically_normal(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            component => out.push(component),
+        }
+    }
+    out
+}
+
+/// Repo type whose markers sit directly in `dir`
+fn repo_type_at(dir: &Path) -> RepoType {
+    // Markers for compiled-out backends are ignored, so the walk continues
+    // to a repo this build can render rather than stopping on a blank one
+    let has_jj = cfg!(feature = "jj") && dir.join(".jj").is_dir();
+    // `.git` can be a dir, a symlink or a gitfile (worktrees, submodules)
+    let has_git = (has_jj || cfg!(feature = "git")) && git_dir(dir).is_some();
+
+    match (has_jj, has_git) {
+        (true, true) => RepoType::JjColocated,
+        (true, false) => RepoType::Jj,
+        (false, true) => RepoType::Git,
+        // Basic backends need no feature, but a .git beside them wins
+        (false, false) if dir.join(".hg").is_dir() => RepoType::Hg,
+        (false, false) if dir.join(".sl").is_dir() => RepoType::Sapling,
+        (false, false) if dir.join(".pijul").is_dir() => RepoType::Pijul,
+        (false, false) => RepoType::None,
     }
 }
 
 /// Returns true if in any repo (for `jj-starship detect` command)
 #[must_use = "returns detection result, does not modify state"]
-pub fn in_repo(start: &Path) -> bool {
-    detect(start).repo_type != RepoType::None
+pub fn in_repo(start: &Path, boundaries: &Boundaries) -> bool {
+    detect(start, boundaries).repo_type != RepoType::None
+}
+
+/// Match `glob` against a leading run of `path`'s components
+///
+/// `*` and `?` match within one component and a `**` component matches any
+/// number of them, so `/mnt/nas` and `/mnt/nas/**` both cover everything
+/// below `/mnt/nas`.
+fn glob_matches(glob: &Path, path: &Path) -> bool {
+    let glob: Vec<_> = glob.components().collect();
+    let path: Vec<_> = path.components().collect();
+    prefix_matches(&glob, &path)
+}
+
+fn prefix_matches(glob: &[Component<'_>], path: &[Component<'_>]) -> bool {
+    let Some((first, rest)) = glob.split_first() else {
+        return true;
+    };
+    if first.as_os_str() == "**" {
+        return (0..=path.len()).any(|skip| prefix_matches(rest, &path[skip..]));
+    }
+    let Some((name, path_rest)) = path.split_first() else {
+        return false;
+    };
+    let matched = match (first.as_os_str().to_str(), name.as_os_str().to_str()) {
+        (Some(pattern), Some(name)) => wildcard_matches(pattern.as_bytes(), name.as_bytes()),
+        _ => first == name,
+    };
+    matched && prefix_matches(rest, path_rest)
+}
+
+/// Single-component match supporting `*` and `?`
+fn wildcard_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| wildcard_matches(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && wildcard_matches(rest, &name[1..]),
+        Some((c, rest)) => {
+            name.first()
+                .is_some_and(|n| n == c || cfg!(windows) && n.eq_ignore_ascii_case(c))
+                && wildcard_matches(rest, &name[1..])
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    path.metadata().ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `<tmp>/repo/.jj` with a nested `a/b/c` directory
+    #[cfg(feature = "jj")]
+    fn nested_repo() -> (tempfile::TempDir, PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("repo/.jj")).unwrap();
+        let leaf = tmp.path().join("repo/a/b/c");
+        fs::create_dir_all(&leaf).unwrap();
+        (tmp, leaf)
+    }
+
+    #[test]
+    fn colocated_repo_uses_compiled_backends() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".jj")).unwrap();
+        fs::create_dir_all(tmp.path().join(".git")).unwrap();
+        let expected = if cfg!(feature = "jj") {
+            RepoType::JjColocated
+        } else if cfg!(feature = "git") {
+            RepoType::Git
+        } else {
+            RepoType::None
+        };
+        assert_eq!(
+            detect(tmp.path(), &Boundaries::default()).repo_type,
+            expected
+        );
+    }
+
+    #[test]
+    fn sapling_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".sl")).unwrap();
+        fs::create_dir_all(tmp.path().join("a/b")).unwrap();
+        let result = detect(&tmp.path().join("a/b"), &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Sapling);
+        assert_eq!(result.repo_root.as_deref(), Some(tmp.path()));
+    }
+
+    #[test]
+    fn store_path_follows_pointer_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("shared/.hg")).unwrap();
+        fs::create_dir_all(tmp.path().join("share/.hg")).unwrap();
+        fs::write(tmp.path().join("share/.hg/sharedpath"), "../../shared/.hg").unwrap();
+        let result = detect(&tmp.path().join("share"), &Boundaries::default());
+        assert_eq!(result.repo_root, Some(tmp.path().join("share")));
+        assert_eq!(result.store_path, Some(tmp.path().join("shared/.hg")));
+
+        let result = detect(&tmp.path().join("shared"), &Boundaries::default());
+        assert_eq!(result.store_path, Some(tmp.path().join("shared/.hg")));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn git_worktree_store_is_common_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let worktree_dir = tmp.path().join("main/.git/worktrees/wt");
+        fs::create_dir_all(&worktree_dir).unwrap();
+        fs::write(worktree_dir.join("commondir"), "../..\n").unwrap();
+        fs::create_dir_all(tmp.path().join("wt")).unwrap();
+        fs::write(
+            tmp.path().join("wt/.git"),
+            format!("gitdir: {}\n", worktree_dir.display()),
+        )
+        .unwrap();
+        let result = detect(&tmp.path().join("wt"), &Boundaries::default());
+        assert_eq!(result.repo_root, Some(tmp.path().join("wt")));
+        assert_eq!(result.store_path, Some(tmp.path().join("main/.git")));
+    }
+
+    #[cfg(all(feature = "git", unix))]
+    #[test]
+    fn git_dir_follows_symlinks_and_gitfile_chains() {
+        let tmp = tempfile::tempdir().unwrap();
+        let real = tmp.path().join("store/real.git");
+        fs::create_dir_all(&real).unwrap();
+        // wt/.git -> link -> hop (gitfile) -> real.git
+        fs::write(tmp.path().join("store/hop"), "gitdir: real.git\n").unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("store/hop"), tmp.path().join("link")).unwrap();
+        fs::create_dir(tmp.path().join("wt")).unwrap();
+        fs::write(tmp.path().join("wt/.git"), "gitdir: ../link\n").unwrap();
+
+        let real = real.canonicalize().unwrap();
+        assert_eq!(git_dir(&tmp.path().join("wt")), Some(real.clone()));
+        let result = detect(&tmp.path().join("wt"), &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Git);
+        assert_eq!(result.store_path, Some(real));
+    }
+
+    #[cfg(all(feature = "git", unix))]
+    #[test]
+    fn dangling_git_symlink_is_not_a_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("outer/.hg")).unwrap();
+        let inner = tmp.path().join("outer/inner");
+        fs::create_dir(&inner).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("gone"), inner.join(".git")).unwrap();
+
+        let result = detect(&inner, &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Hg);
+        assert_eq!(git_dir(&inner), None);
+        // A gitfile pointing at itself gives up rather than spinning
+        fs::remove_file(inner.join(".git")).unwrap();
+        fs::write(inner.join(".git"), "gitdir: .git\n").unwrap();
+        assert_eq!(git_dir(&inner), None);
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn separated_git_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("home");
+        let cfg = home.join(".cfg");
+        fs::create_dir_all(&cfg).unwrap();
+        fs::write(cfg.join("config"), "[core]\n\tbare = true\n").unwrap();
+        let yadm = tmp.path().join("yadm/repo.git");
+        fs::create_dir_all(&yadm).unwrap();
+        fs::write(
+            yadm.join("config"),
+            "[core]\n\tworktree = ../../home/dots\n",
+        )
+        .unwrap();
+        fs::create_dir_all(home.join("dots/nvim")).unwrap();
+        fs::create_dir_all(home.join("src/proj/.hg")).unwrap();
+
+        let boundaries = Boundaries {
+            git_dirs: vec![cfg.clone(), yadm.clone()],
+            ..Boundaries::default()
+        };
+        // The dir holding the Git dir is its work tree by default
+        let result = detect(&home, &boundaries);
+        assert_eq!(result.repo_type, RepoType::Git);
+        assert_eq!(result.repo_root, Some(home.clone()));
+        assert_eq!(result.store_path, Some(cfg.clone()));
+        assert_eq!(boundaries.separate_git_dir(&home), Some(cfg.as_path()));
+
+        // core.worktree is relative to the Git dir; the deeper tree wins
+        let result = detect(&home.join("dots/nvim"), &boundaries);
+        assert_eq!(result.repo_root, Some(home.join("dots")));
+        assert_eq!(result.store_path, Some(yadm));
+
+        // A real repo inside the work tree is innermost
+        let result = detect(&home.join("src/proj"), &boundaries);
+        assert_eq!(result.repo_type, RepoType::Hg);
+        let outermost = Boundaries {
+            nesting: Nesting::Outermost,
+            ..boundaries
+        };
+        let result = detect(&home.join("src/proj"), &outermost);
+        assert_eq!(result.repo_root, Some(home.clone()));
+        assert_eq!(result.nested_roots, [home.join("src/proj"), home]);
+    }
+
+    #[test]
+    fn inside_dot_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".hg/store")).unwrap();
+        fs::create_dir_all(tmp.path().join("src")).unwrap();
+        let result = detect(&tmp.path().join(".hg/store"), &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Hg);
+        assert_eq!(result.dot_dir.as_deref(), Some(".hg"));
+        let result = detect(&tmp.path().join("src"), &Boundaries::default());
+        assert_eq!(result.dot_dir, None);
+    }
+
+    #[test]
+    fn hg_and_pijul_repos() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("hg/.hg")).unwrap();
+        let result = detect(&tmp.path().join("hg"), &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Hg);
+
+        fs::create_dir_all(tmp.path().join("pijul/.pijul")).unwrap();
+        let result = detect(&tmp.path().join("pijul"), &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Pijul);
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn unbounded_walk_finds_repo() {
+        let (tmp, leaf) = nested_repo();
+        let result = detect(&leaf, &Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::Jj);
+        assert_eq!(result.repo_root, Some(tmp.path().join("repo")));
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn max_depth_limits_walk() {
+        let (_tmp, leaf) = nested_repo();
+        let shallow = Boundaries {
+            max_depth: Some(2),
+            ..Boundaries::default()
+        };
+        let deep = Boundaries {
+            max_depth: Some(3),
+            ..Boundaries::default()
+        };
+        assert_eq!(detect(&leaf, &shallow).repo_type, RepoType::None);
+        assert_eq!(detect(&leaf, &deep).repo_type, RepoType::Jj);
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn stop_at_excludes_parents() {
+        let (tmp, leaf) = nested_repo();
+        let below_repo = Boundaries {
+            stop_at: Some(tmp.path().join("repo/a")),
+            ..Boundaries::default()
+        };
+        let at_repo = Boundaries {
+            stop_at: Some(tmp.path().join("repo")),
+            ..Boundaries::default()
+        };
+        assert_eq!(detect(&leaf, &below_repo).repo_type, RepoType::None);
+        assert_eq!(detect(&leaf, &at_repo).repo_type, RepoType::Jj);
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn ignored_paths_skip_detection() {
+        let (tmp, leaf) = nested_repo();
+        let ignore = |glob: &str| Boundaries {
+            ignore: vec![tmp.path().join(glob)],
+            ..Boundaries::default()
+        };
+        assert_eq!(detect(&leaf, &ignore("repo/**")).repo_type, RepoType::None);
+        assert_eq!(detect(&leaf, &ignore("repo/a")).repo_type, RepoType::None);
+        assert_eq!(detect(&leaf, &ignore("*/a/b")).repo_type, RepoType::None);
+        assert_eq!(detect(&leaf, &ignore("**/c")).repo_type, RepoType::None);
+        assert_eq!(detect(&leaf, &ignore("repo/b*")).repo_type, RepoType::Jj);
+        assert_eq!(detect(&leaf, &ignore("other/**")).repo_type, RepoType::Jj);
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn ignore_marker_hides_repo() {
+        let (tmp, leaf) = nested_repo();
+        fs::create_dir_all(tmp.path().join("repo/a/.hg")).unwrap();
+        fs::write(tmp.path().join("repo/a/.jj-starship-ignore"), "").unwrap();
+        assert_eq!(
+            detect(&leaf, &Boundaries::default()).repo_type,
+            RepoType::None
+        );
+        assert_eq!(
+            detect(&tmp.path().join("repo"), &Boundaries::default()).repo_type,
+            RepoType::Jj
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn nesting_policy_picks_repo() {
+        let (tmp, leaf) = nested_repo();
+        fs::create_dir_all(tmp.path().join("repo/a/.hg")).unwrap();
+        fs::create_dir_all(tmp.path().join(".pijul")).unwrap();
+        let nesting = |nesting| Boundaries {
+            nesting,
+            stop_at: Some(tmp.path().to_path_buf()),
+            ..Boundaries::default()
+        };
+
+        let inner = detect(&leaf, &nesting(Nesting::Innermost));
+        assert_eq!(inner.repo_type, RepoType::Hg);
+        assert_eq!(inner.nested_roots, [tmp.path().join("repo/a")]);
+
+        let prefer_jj = detect(&leaf, &nesting(Nesting::PreferJj));
+        assert_eq!(prefer_jj.repo_type, RepoType::Jj);
+        assert_eq!(prefer_jj.repo_root, Some(tmp.path().join("repo")));
+        assert_eq!(prefer_jj.nested_roots.len(), 2);
+
+        let outer = detect(&leaf, &nesting(Nesting::Outermost));
+        assert_eq!(outer.repo_type, RepoType::Pijul);
+        assert_eq!(
+            outer.nested_roots,
+            [
+                tmp.path().join("repo/a"),
+                tmp.path().join("repo"),
+                tmp.path().to_path_buf()
+            ]
+        );
+    }
+
+    #[cfg(all(unix, feature = "jj"))]
+    #[test]
+    fn resolve_symlinks_reports_physical_root() {
+        let (tmp, _leaf) = nested_repo();
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(tmp.path().join("repo/a"), &link).unwrap();
+
+        let logical = detect(&link, &Boundaries::default());
+        assert_eq!(logical.repo_type, RepoType::None);
+
+        let resolve = Boundaries {
+            resolve_symlinks: ResolveSymlinks::On,
+            ..Boundaries::default()
+        };
+        let physical = detect(&link, &resolve);
+        assert_eq!(physical.repo_type, RepoType::Jj);
+        assert_eq!(
+            physical.repo_root,
+            Some(tmp.path().canonicalize().unwrap().join("repo"))
+        );
+    }
+
+    #[cfg(all(unix, feature = "jj"))]
+    #[test]
+    fn same_filesystem_walks_within_device() {
+        let (_tmp, leaf) = nested_repo();
+        let boundaries = Boundaries {
+            same_filesystem: true,
+            ..Boundaries::default()
+        };
+        assert_eq!(detect(&leaf, &boundaries).repo_type, RepoType::Jj);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn verbatim_prefixes_are_stripped() {
+        assert_eq!(
+            strip_verbatim(PathBuf::from(r"\\?\C:\src\repo")),
+            Path::new(r"C:\src\repo")
+        );
+        assert_eq!(
+            strip_verbatim(PathBuf::from(r"\\?\UNC\server\share\repo")),
+            Path::new(r"\\server\share\repo")
+        );
+        assert_eq!(
+            strip_verbatim(PathBuf::from(r"D:\repo")),
+            Path::new(r"D:\repo")
+        );
+        assert!(same_path(
+            Path::new(r"C:\Users\Me"),
+            Path::new(r"c:\users\me")
+        ));
+    }
 }
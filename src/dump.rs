@@ -0,0 +1,165 @@
+//! `debug dump` subcommand - one report to paste into bug reports
+//!
+//! Everything a performance or rendering issue needs in one place: build,
+//! platform, environment, config, detection, the raw collected info, the
+//! collection error and per-phase timings. The home directory is replaced
+//! with `~` throughout; repo contents beyond bookmark and branch names
+//! never appear.
+
+use jj_starship::config::{Config, home_dir};
+use jj_starship::detect::{self, DetectResult};
+use jj_starship::error::Error;
+use jj_starship::timings::Timings;
+use jj_starship::{RepoInfo, output, try_collect};
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// Variables outside `JJ_STARSHIP_*` that change detection or rendering
+const EXTRA_VARS: &[&str] = &["NO_COLOR", "TERM", "STARSHIP_SHELL", "XDG_CACHE_HOME"];
+
+/// Detect, collect and render once, then write the redacted report
+pub fn run(cwd: &Path, config: &Config, version: &str, out: &mut impl Write) -> io::Result<()> {
+    let start = Instant::now();
+    let mut timings = Timings::default();
+    let result = timings.time("detect", || detect::detect(cwd, &config.boundaries));
+    let collected = try_collect(&result, config, &mut timings);
+    let rendered = match &collected {
+        Ok(info) => output::render(&result, info.as_ref(), config),
+        Err(e) => output::render_error(&result, e, config),
+    };
+    timings.record("total", start.elapsed());
+
+    let report = report(
+        version,
+        cwd,
+        config,
+        &result,
+        collected.as_ref().map(Option::as_ref),
+        rendered.as_deref(),
+        &timings,
+    );
+    let home = home_dir();
+    out.write_all(redact(&report, home.as_deref()).as_bytes())
+}
+
+/// The report before redaction
+fn report(
+    version: &str,
+    cwd: &Path,
+    config: &Config,
+    result: &DetectResult,
+    collected: Result<Option<&RepoInfo>, &Error>,
+    rendered: Option<&str>,
+    timings: &Timings,
+) -> String {
+    let mut s = String::with_capacity(4096);
+    let _ = writeln!(s, "## version\n{version}");
+    let _ = writeln!(s, "## platform\n{} {}", env::consts::OS, env::consts::ARCH);
+
+    let _ = writeln!(s, "## environment");
+    let mut vars: Vec<_> = env::vars()
+        .filter(|(name, _)| name.starts_with("JJ_STARSHIP_") || EXTRA_VARS.contains(&name.as_str()))
+        .collect();
+    vars.sort();
+    for (name, value) in vars {
+        let _ = writeln!(s, "{name}={value}");
+    }
+
+    let _ = writeln!(s, "## detection\ncwd: {}\n{result:#?}", cwd.display());
+    let _ = writeln!(s, "## config\n{config:#?}");
+    let _ = match collected {
+        Ok(info) => writeln!(s, "## info\n{info:#?}"),
+        Err(e) => writeln!(s, "## error\nerror[{}]: {e}", e.code()),
+    };
+    let _ = writeln!(s, "## output\n{rendered:?}");
+
+    let _ = writeln!(s, "## timings");
+    let mut phases = Vec::new();
+    let _ = timings.report(&mut phases);
+    s.push_str(&String::from_utf8_lossy(&phases));
+    s
+}
+
+/// `text` with the home directory shown as `~`
+fn redact(text: &str, home: Option<&Path>) -> String {
+    match home.and_then(Path::to_str).filter(|home| home.len() > 1) {
+        Some(home) => text.replace(home.trim_end_matches(std::path::is_separator), "~"),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_starship::detect::RepoType;
+    use jj_starship::hg::HgInfo;
+
+    #[test]
+    fn home_is_redacted() {
+        let home = Path::new("/home/alice/");
+        assert_eq!(
+            redact("cwd: /home/alice/src/app\nroot: /srv/x", Some(home)),
+            "cwd: ~/src/app\nroot: /srv/x"
+        );
+        assert_eq!(redact("/a/b", Some(Path::new("/"))), "/a/b");
+        assert_eq!(redact("/a/b", None), "/a/b");
+    }
+
+    #[test]
+    fn report_sections() {
+        let result = DetectResult {
+            repo_type: RepoType::Hg,
+            repo_root: Some("/repo".into()),
+            store_path: Some("/repo/.hg".into()),
+            dot_dir: None,
+            nested_roots: vec!["/repo".into()],
+        };
+        let info = RepoInfo::Hg(HgInfo {
+            branch: "default".into(),
+            bookmark: None,
+            node: "abcdef12".into(),
+        });
+        let mut timings = Timings::default();
+        timings.record("detect", std::time::Duration::from_millis(1));
+        let text = report(
+            "jj-starship 1.0",
+            Path::new("/repo"),
+            &Config::default(),
+            &result,
+            Ok(Some(&info)),
+            Some("on ☿ default"),
+            &timings,
+        );
+        let sections: Vec<_> = text.lines().filter(|l| l.starts_with("## ")).collect();
+        assert_eq!(
+            sections,
+            [
+                "## version",
+                "## platform",
+                "## environment",
+                "## detection",
+                "## config",
+                "## info",
+                "## output",
+                "## timings"
+            ]
+        );
+        assert!(text.contains("node: \"abcdef12\""));
+        assert!(text.ends_with("detect     1.000ms\n"));
+
+        let error = Error::Hg("dirstate is truncated".into());
+        let text = report(
+            "v",
+            Path::new("/repo"),
+            &Config::default(),
+            &result,
+            Err(&error),
+            None,
+            &timings,
+        );
+        assert!(text.contains("## error\nerror[hg]: hg: dirstate is truncated\n"));
+    }
+}
@@ -5,15 +5,45 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "jj")]
     #[error("jj: {0}")]
     Jj(String),
 
+    /// The repo was written by a newer `jj` than jj-lib understands
+    #[cfg(feature = "jj")]
+    #[error("jj: repo format is newer than this jj-starship supports: {0}")]
+    JjFormat(String),
+
     #[cfg(feature = "git")]
     #[error("git: {0}")]
     Git(String),
 
+    #[error("hg: {0}")]
+    Hg(String),
+
+    #[error("sapling: {0}")]
+    Sapling(String),
+
     #[error("io: {0}")]
     Io(#[from] std::io::Error),
 }
 
+impl Error {
+    /// Stable code for machine-readable error reports (`--strict`)
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "jj")]
+            Self::Jj(_) => "jj",
+            #[cfg(feature = "jj")]
+            Self::JjFormat(_) => "jj-format",
+            #[cfg(feature = "git")]
+            Self::Git(_) => "git",
+            Self::Hg(_) => "hg",
+            Self::Sapling(_) => "sapling",
+            Self::Io(_) => "io",
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,396 @@
+//! `explain` subcommand - the prompt, then what each of its elements means
+
+use jj_starship::RepoInfo;
+use jj_starship::config::{Config, DisplayConfig};
+use jj_starship::detect::DetectResult;
+#[cfg(feature = "jj")]
+use jj_starship::detect::RepoType;
+#[cfg(feature = "git")]
+use jj_starship::git::GitInfo;
+#[cfg(feature = "jj")]
+use jj_starship::jj::JjInfo;
+#[cfg(feature = "jj")]
+use jj_starship::output;
+use std::io::{self, Write};
+
+/// One element of the segment: its text and what it says about the repo
+#[derive(Debug, PartialEq, Eq)]
+pub struct Element {
+    pub text: String,
+    pub meaning: String,
+}
+
+impl Element {
+    fn new(text: impl Into<String>, meaning: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            meaning: meaning.into(),
+        }
+    }
+}
+
+/// Elements of the segment for `info` in display order, following the
+/// same display flags as the prompt
+pub fn elements(result: &DetectResult, info: &RepoInfo, config: &Config) -> Vec<Element> {
+    let mut out = Vec::new();
+    let root = result
+        .repo_root
+        .as_deref()
+        .map_or_else(String::new, |root| format!(" at {}", root.display()));
+    let mut symbol = |symbol: &str, display: DisplayConfig, kind: &str| {
+        if display.show_prefix && !symbol.trim().is_empty() {
+            out.push(Element::new(symbol.trim(), format!("{kind} repo{root}")));
+        }
+    };
+    match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(jj) => {
+            let kind = if result.repo_type == RepoType::JjColocated {
+                "colocated JJ+Git"
+            } else {
+                "JJ"
+            };
+            symbol(&config.jj_symbol, config.jj_display, kind);
+            jj_elements(jj, config, &mut out);
+            if config.jj_display.show_status {
+                jj_status(jj, config, &mut out);
+            }
+        }
+        #[cfg(feature = "git")]
+        RepoInfo::Git(git) => {
+            symbol(&config.git_symbol, config.git_display, "Git");
+            git_elements(git, config, &mut out);
+            if config.git_display.show_status {
+                git_status(git, config, &mut out);
+            }
+        }
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => {
+            use jj_starship::config::Backend;
+            let colocated = config.colocated;
+            if colocated.view == Backend::Jj {
+                symbol(&config.jj_symbol, config.jj_display, "colocated JJ+Git");
+                jj_elements(jj, config, &mut out);
+            } else {
+                symbol(&config.git_symbol, config.git_display, "colocated JJ+Git");
+                git_elements(git, config, &mut out);
+            }
+            match colocated.status {
+                Backend::Jj => jj_status(jj, config, &mut out),
+                Backend::Git => git_status(git, config, &mut out),
+            }
+        }
+        RepoInfo::Hg(hg) => {
+            symbol(&config.hg_symbol, config.hg_display, "Mercurial");
+            if config.hg_display.show_name {
+                out.push(Element::new(&hg.branch, "named branch"));
+                if let Some(bookmark) = &hg.bookmark {
+                    out.push(Element::new(bookmark, "active bookmark"));
+                }
+            }
+            if config.hg_display.show_id {
+                out.push(Element::new(&hg.node, "working copy parent"));
+            }
+        }
+        RepoInfo::Sapling(sl) => {
+            symbol(&config.sl_symbol, config.sl_display, "Sapling");
+            if config.sl_display.show_name
+                && let Some(bookmark) = &sl.bookmark
+            {
+                out.push(Element::new(bookmark, "active bookmark"));
+            }
+            if config.sl_display.show_id {
+                out.push(Element::new(&sl.node, "working copy parent"));
+            }
+        }
+        RepoInfo::Pijul(pijul) => {
+            symbol(&config.pijul_symbol, config.pijul_display, "Pijul");
+            if config.pijul_display.show_name {
+                out.push(Element::new(&pijul.channel, "current channel"));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(feature = "jj")]
+fn jj_elements(info: &JjInfo, config: &Config, out: &mut Vec<Element>) {
+    if config.jj_display.show_id && !info.change_id.is_empty() {
+        let meaning = match info.change_id_prefix_len {
+            0 => "change ID of the working copy".to_string(),
+            1 => "change ID of the working copy; the first char is unique".to_string(),
+            n => format!("change ID of the working copy; the first {n} chars are unique"),
+        };
+        out.push(Element::new(&info.change_id, meaning));
+    }
+    if config.jj_display.show_name {
+        for (name, distance) in &info.bookmarks {
+            let meaning = match distance {
+                0 => "bookmark on the working copy".to_string(),
+                1 => "bookmark on the parent of the working copy".to_string(),
+                n => format!("bookmark {n} commits up from the working copy"),
+            };
+            out.push(Element::new(
+                output::bookmark_label(name, *distance, config),
+                meaning,
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "jj")]
+fn jj_status(info: &JjInfo, config: &Config, out: &mut Vec<Element>) {
+    let symbols = &config.symbols;
+    let flag = |set: bool, glyph: &str, meaning: &str| set.then(|| Element::new(glyph, meaning));
+    let commits = info.conflicted_commits;
+    let conflicts = (commits > 0).then(|| {
+        let scope = config.conflict_count.as_deref().unwrap_or_default();
+        let noun = if commits == 1 { "commit" } else { "commits" };
+        Element::new(
+            format!("{}{commits}", symbols.conflicts),
+            format!("{commits} {noun} with conflicts in {scope}"),
+        )
+    });
+    let elements = [
+        flag(
+            info.op_in_progress,
+            &symbols.busy,
+            "a jj command was running; this is the state from before it",
+        ),
+        flag(
+            info.conflict,
+            &symbols.conflict,
+            "working copy has conflicts",
+        ),
+        conflicts,
+        flag(
+            info.divergent,
+            &symbols.divergent,
+            "change ID has more than one visible commit",
+        ),
+        flag(
+            info.divergence_timed_out,
+            &config.budgets.placeholder,
+            "divergence check ran out of time",
+        ),
+        flag(
+            info.empty_desc,
+            &symbols.undescribed,
+            "working copy has no description",
+        ),
+        flag(
+            info.has_remote && !info.is_synced,
+            &symbols.unsynced,
+            "closest bookmark differs from its remote",
+        ),
+    ];
+    out.extend(elements.into_iter().flatten());
+}
+
+#[cfg(feature = "git")]
+fn git_elements(info: &GitInfo, config: &Config, out: &mut Vec<Element>) {
+    if config.git_display.show_name {
+        out.push(match &info.branch {
+            Some(branch) => Element::new(config.truncate(branch), "checked-out branch"),
+            None => Element::new("HEAD", "detached HEAD"),
+        });
+        if info.lfs {
+            out.push(Element::new(
+                &*config.symbols.lfs,
+                "repo keeps files in Git LFS",
+            ));
+        }
+    }
+    if config.git_display.show_id {
+        out.push(Element::new(&info.head_short, "HEAD commit"));
+    }
+}
+
+#[cfg(feature = "git")]
+fn git_status(info: &GitInfo, config: &Config, out: &mut Vec<Element>) {
+    let symbols = &config.symbols;
+    if info.op_in_progress {
+        out.push(Element::new(
+            &*symbols.busy,
+            "a git command held index.lock; file status is skipped until it finishes",
+        ));
+    }
+    if info.lfs_missing {
+        out.push(Element::new(
+            &*symbols.lfs_missing,
+            "repo uses Git LFS but its filter isn't installed (run git lfs install)",
+        ));
+    }
+    if info.status_timed_out {
+        out.push(Element::new(
+            &*config.budgets.placeholder,
+            "status scan ran out of time",
+        ));
+    }
+    for (count, glyph, what) in [
+        (info.conflicted, &symbols.conflicted, "conflicted"),
+        (info.staged, &symbols.staged, "staged"),
+        (info.modified, &symbols.modified, "modified"),
+        (info.untracked, &symbols.untracked, "untracked"),
+        (info.deleted, &symbols.deleted, "deleted"),
+    ] {
+        if count > 0 {
+            out.push(Element::new(&**glyph, format!("{} {what}", files(count))));
+        }
+    }
+    if info.oversized > 0 {
+        out.push(Element::new(
+            &*symbols.oversized,
+            format!(
+                "{} too large for jj to snapshot (snapshot.max-new-file-size)",
+                files(info.oversized)
+            ),
+        ));
+    }
+    for (count, glyph, direction) in [
+        (info.ahead, &symbols.ahead, "ahead of"),
+        (info.behind, &symbols.behind, "behind"),
+    ] {
+        if count > 0 {
+            let commits = if count == 1 { "commit" } else { "commits" };
+            out.push(Element::new(
+                format!("{glyph}{count}"),
+                format!("{count} {commits} {direction} upstream"),
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "git")]
+fn files(count: usize) -> String {
+    if count == 1 {
+        "1 file".into()
+    } else {
+        format!("{count} files")
+    }
+}
+
+/// Write the prompt, a blank line, then one aligned line per element
+pub fn report(prompt: &str, elements: &[Element], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{prompt}")?;
+    writeln!(out)?;
+    let width = elements
+        .iter()
+        .map(|e| e.text.chars().count())
+        .max()
+        .unwrap_or(0);
+    for e in elements {
+        let pad = width - e.text.chars().count();
+        writeln!(out, "  {}{:pad$}  {}", e.text, "", e.meaning)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "git")]
+    use jj_starship::config::DEFAULT_GIT_SYMBOL;
+    #[cfg(not(feature = "jj"))]
+    use jj_starship::detect::RepoType;
+
+    fn result(repo_type: RepoType) -> DetectResult {
+        DetectResult {
+            repo_type,
+            repo_root: Some("/repo".into()),
+            store_path: None,
+            dot_dir: None,
+            nested_roots: vec!["/repo".into()],
+        }
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn git_counts_and_upstream() {
+        let info = RepoInfo::Git(GitInfo {
+            branch: Some("main".into()),
+            head_short: "abc1234".into(),
+            staged: 0,
+            modified: 3,
+            untracked: 1,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 2,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        });
+        let elements = elements(&result(RepoType::Git), &info, &Config::default());
+        let lines: Vec<_> = elements
+            .iter()
+            .map(|e| format!("{} = {}", e.text, e.meaning))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                format!("{} = Git repo at /repo", DEFAULT_GIT_SYMBOL.trim()).as_str(),
+                "main = checked-out branch",
+                "abc1234 = HEAD commit",
+                "! = 3 files modified",
+                "? = 1 file untracked",
+                "⇡2 = 2 commits ahead of upstream",
+            ]
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn jj_flags_follow_display() {
+        let info = RepoInfo::Jj(JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 2)],
+            empty_desc: false,
+            conflict: true,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: false,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        });
+        let mut config = Config::default();
+        let texts = |config: &Config| -> Vec<String> {
+            elements(&result(RepoType::Jj), &info, config)
+                .into_iter()
+                .map(|e| e.text)
+                .collect()
+        };
+        assert_eq!(
+            texts(&config),
+            [
+                config.jj_symbol.trim(),
+                "yzxv1234",
+                "main~2",
+                &config.symbols.conflict
+            ]
+        );
+        config.jj_display.show_status = false;
+        config.jj_display.show_prefix = false;
+        assert_eq!(texts(&config), ["yzxv1234", "main~2"]);
+    }
+
+    #[test]
+    fn report_aligns_meanings() {
+        let elements = [
+            Element::new("main", "branch"),
+            Element::new("!", "modified"),
+        ];
+        let mut out = Vec::new();
+        report("on main [!]", &elements, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "on main [!]\n\n  main  branch\n  !     modified\n"
+        );
+    }
+}
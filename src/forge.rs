@@ -0,0 +1,240 @@
+//! Forge links - OSC 8 hyperlinks from the branch and commit id to their
+//! pages on GitHub, GitLab, Bitbucket or a Gitea/Forgejo host
+//!
+//! The remote URL comes from the Git config of the repo, or of the Git repo
+//! backing a JJ repo (`store/git_target`). No `git` subprocess is run.
+
+use crate::color::Markup;
+use crate::detect::{DetectResult, RepoType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How a forge lays out its branch and commit pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// `/tree/NAME`, `/commit/ID` (also the fallback for unknown hosts)
+    GitHub,
+    /// `/-/tree/NAME`, `/-/commit/ID`
+    GitLab,
+    /// `/branch/NAME`, `/commits/ID`
+    Bitbucket,
+    /// `/src/branch/NAME`, `/commit/ID`
+    Gitea,
+}
+
+/// Web pages of the repo's remote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forge {
+    pub kind: Kind,
+    /// `https://host/owner/repo`
+    pub base: String,
+}
+
+impl Forge {
+    /// Forge of the detected repo's `origin` remote (else its first remote),
+    /// None for non-Git repos or without a web-reachable remote
+    #[must_use]
+    pub fn for_repo(result: &DetectResult) -> Option<Self> {
+        let store = result.store_path.as_deref()?;
+        let git_dir = match result.repo_type {
+            RepoType::Git => store.to_path_buf(),
+            RepoType::Jj | RepoType::JjColocated => jj_git_dir(store)?,
+            _ => return None,
+        };
+        let config = fs::read_to_string(git_dir.join("config")).ok()?;
+        Self::from_remote(&remote_url(&config)?)
+    }
+
+    /// Forge for a remote URL: `https://`, `ssh://` or scp-like
+    /// `git@host:owner/repo.git`
+    #[must_use]
+    pub fn from_remote(url: &str) -> Option<Self> {
+        let rest = match url.split_once("://") {
+            Some(("https" | "http" | "ssh" | "git+ssh", rest)) => rest,
+            Some(_) => return None,
+            None => &url.replacen(':', "/", 1),
+        };
+        // Drop the user and, for ssh, the port
+        let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+        let (host, path) = rest.split_once('/')?;
+        let host = host.split(':').next()?;
+        let path = path.trim_end_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        if host.is_empty() || path.is_empty() {
+            return None;
+        }
+        let kind = if host.contains("gitlab") {
+            Kind::GitLab
+        } else if host.contains("bitbucket") {
+            Kind::Bitbucket
+        } else if host.contains("codeberg") || host.contains("gitea") || host.contains("forgejo") {
+            Kind::Gitea
+        } else {
+            Kind::GitHub
+        };
+        Some(Self {
+            kind,
+            base: format!("https://{host}/{path}"),
+        })
+    }
+
+    /// Page of a branch or bookmark
+    #[must_use]
+    pub fn branch_url(&self, name: &str) -> String {
+        let page = match self.kind {
+            Kind::GitHub => "tree",
+            Kind::GitLab => "-/tree",
+            Kind::Bitbucket => "branch",
+            Kind::Gitea => "src/branch",
+        };
+        format!("{}/{page}/{}", self.base, encode(name))
+    }
+
+    /// Page of a commit, by full or abbreviated hash
+    #[must_use]
+    pub fn commit_url(&self, id: &str) -> String {
+        let page = match self.kind {
+            Kind::GitHub | Kind::Gitea => "commit",
+            Kind::GitLab => "-/commit",
+            Kind::Bitbucket => "commits",
+        };
+        format!("{}/{page}/{}", self.base, encode(id))
+    }
+}
+
+/// `text` as an OSC 8 hyperlink to `url`; only ANSI markup can carry one
+#[must_use]
+pub fn hyperlink(text: &str, url: &str, markup: Markup) -> String {
+    let (Markup::Ansi(escapes) | Markup::Powerline(escapes)) = markup else {
+        return text.to_string();
+    };
+    // zsh expands `%` even inside `%{...%}`, and URLs are percent-encoded
+    let wrap = |escape: &str| escapes.wrap(&markup.escape(escape));
+    format!(
+        "{}{text}{}",
+        wrap(&format!("\x1b]8;;{url}\x1b\\")),
+        wrap("\x1b]8;;\x1b\\")
+    )
+}
+
+/// Git dir backing a JJ repo, from `store/git_target` (relative to `store`)
+fn jj_git_dir(repo_dir: &Path) -> Option<PathBuf> {
+    let store = repo_dir.join("store");
+    let target = fs::read_to_string(store.join("git_target")).ok()?;
+    Some(store.join(target.trim()))
+}
+
+/// `url` of the `origin` remote in a Git config, else of the first remote
+fn remote_url(config: &str) -> Option<String> {
+    let mut section = None;
+    let mut first = None;
+    for line in config.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[') {
+            section = header
+                .strip_prefix("remote \"")
+                .and_then(|h| h.split_once('"'))
+                .map(|(name, _)| name.to_string());
+            continue;
+        }
+        let Some(remote) = &section else { continue };
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !key.trim().eq_ignore_ascii_case("url") {
+            continue;
+        }
+        let url = value.trim().trim_matches('"').to_string();
+        if remote == "origin" {
+            return Some(url);
+        }
+        first.get_or_insert(url);
+    }
+    first
+}
+
+/// Percent-encode the characters a ref name may hold that end or escape a
+/// URL path
+fn encode(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '%' => out.push_str("%25"),
+            '#' => out.push_str("%23"),
+            '?' => out.push_str("%3F"),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Escapes;
+
+    #[test]
+    fn remote_urls() {
+        let github = Forge::from_remote("git@github.com:dmmulroy/jj-starship.git").unwrap();
+        assert_eq!(github.kind, Kind::GitHub);
+        assert_eq!(github.base, "https://github.com/dmmulroy/jj-starship");
+        assert_eq!(
+            github.branch_url("feat/a#1"),
+            "https://github.com/dmmulroy/jj-starship/tree/feat/a%231"
+        );
+        assert_eq!(
+            Forge::from_remote("ssh://git@gitlab.example.com:2222/group/sub/repo.git")
+                .unwrap()
+                .commit_url("a3b4c5d"),
+            "https://gitlab.example.com/group/sub/repo/-/commit/a3b4c5d"
+        );
+        assert_eq!(
+            Forge::from_remote("https://codeberg.org/owner/repo/").unwrap(),
+            Forge {
+                kind: Kind::Gitea,
+                base: "https://codeberg.org/owner/repo".into(),
+            }
+        );
+        assert_eq!(Forge::from_remote("file:///srv/repo.git"), None);
+        assert_eq!(Forge::from_remote("/srv/repo.git"), None);
+    }
+
+    #[test]
+    fn origin_wins_over_first_remote() {
+        let config = r#"
+[core]
+	bare = false
+[remote "fork"]
+	url = git@github.com:me/repo.git
+[remote "origin"]
+	url = https://github.com/owner/repo.git
+	fetch = +refs/heads/*:refs/remotes/origin/*
+"#;
+        assert_eq!(
+            remote_url(config).as_deref(),
+            Some("https://github.com/owner/repo.git")
+        );
+        assert_eq!(
+            remote_url("[remote \"fork\"]\n\turl = git@host:me/r\n").as_deref(),
+            Some("git@host:me/r")
+        );
+    }
+
+    #[test]
+    fn hyperlinks_per_markup() {
+        let url = "https://github.com/o/r/tree/main";
+        assert_eq!(
+            hyperlink("main", url, Markup::Ansi(Escapes::None)),
+            format!("\x1b]8;;{url}\x1b\\main\x1b]8;;\x1b\\")
+        );
+        assert_eq!(
+            hyperlink(
+                "main",
+                "https://h/o/r/tree/100%25",
+                Markup::Ansi(Escapes::Zsh)
+            ),
+            "%{\x1b]8;;https://h/o/r/tree/100%%25\x1b\\%}main%{\x1b]8;;\x1b\\%}"
+        );
+        assert_eq!(hyperlink("main", url, Markup::Tmux), "main");
+    }
+}
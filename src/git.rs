@@ -1,15 +1,29 @@
 //! Git repository info collection using git2
 
+use crate::budget;
+use crate::detect;
 use crate::error::{Error, Result};
-use git2::{Repository, Status, StatusOptions};
+use crate::scale::CollectMode;
+use crate::text;
+use crate::timings::Timings;
+use git2::{ConfigLevel, Repository, Status, StatusOptions};
+use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
 /// Git repository status info
+///
+/// With the `serde` feature, serializes to the `git` object of the
+/// `--output json` model.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::struct_excessive_bools)]
 pub struct GitInfo {
-    /// Branch name (None if detached)
+    /// Branch name (None if detached), lossily decoded when the ref name
+    /// isn't UTF-8
     pub branch: Option<String>,
     /// Short commit hash (7 chars)
+    #[cfg_attr(feature = "serde", serde(rename = "head"))]
     pub head_short: String,
     /// Count of staged files
     pub staged: usize,
@@ -17,78 +31,93 @@ pub struct GitInfo {
     pub modified: usize,
     /// Count of untracked files
     pub untracked: usize,
+    /// Untracked files over jj's `snapshot.max-new-file-size`, which jj
+    /// will refuse to snapshot (colocated repos only)
+    pub oversized: usize,
     /// Count of deleted files
     pub deleted: usize,
     /// Count of conflicted files
     pub conflicted: usize,
+    /// Status scan ran over its time budget (counts are zero)
+    pub status_timed_out: bool,
     /// Commits ahead of upstream
     pub ahead: usize,
     /// Commits behind upstream
     pub behind: usize,
+    /// A `git` command held `index.lock`; the status scan was skipped (counts
+    /// are zero)
+    pub op_in_progress: bool,
+    /// The repo keeps files in Git LFS
+    pub lfs: bool,
+    /// The repo uses LFS but the `lfs` filter isn't installed, so checkouts
+    /// leave pointer files in place of the content
+    pub lfs_missing: bool,
 }
 
 /// Collect Git repo info from the given path
+///
+/// `git_dir` names a Git dir kept apart from the work tree (see
+/// [`detect::Boundaries::git_dirs`]); None finds it from `repo_root`.
+/// Untracked files larger than `new_file_limit` are counted as
+/// [`GitInfo::oversized`]; files inside untracked dirs aren't checked, as the
+/// scan doesn't descend into them.
+///
+/// # Errors
+///
+/// Returns an error if the repo can't be opened or HEAD can't be read.
 #[must_use = "returns collected repo info, does not modify state"]
-pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
-    let repo = Repository::open(repo_root).map_err(|e| Error::Git(format!("open: {e}")))?;
+pub fn collect(
+    repo_root: &Path,
+    git_dir: Option<&Path>,
+    id_length: usize,
+    mode: CollectMode,
+    status_budget: Option<Duration>,
+    new_file_limit: Option<u64>,
+    timings: &mut Timings,
+) -> Result<GitInfo> {
+    let repo = timings.time("git open", || {
+        open(repo_root, git_dir).map_err(|e| Error::Git(format!("open: {e}")))
+    })?;
+    let checks = mode.checks(repo.path());
+    let op_in_progress = repo.path().join("index.lock").exists();
+    tracing::debug!(?checks, "collection checks");
+    // LFS detection reads .gitattributes and the config; it belongs to the
+    // status checks, so the fast path and large repos skip it too
+    let (lfs, lfs_missing) = if checks.dirty {
+        timings.time("git lfs", || lfs_state(&repo, repo_root))
+    } else {
+        (false, false)
+    };
 
     // Status counts - compute once for both empty and normal repos
-    let mut opts = StatusOptions::new();
-    opts.include_untracked(true)
-        .recurse_untracked_dirs(false)
-        .include_ignored(false)
-        .exclude_submodules(true);
-
-    let statuses = repo
-        .statuses(Some(&mut opts))
-        .map_err(|e| Error::Git(format!("statuses: {e}")))?;
-
-    let mut staged = 0usize;
-    let mut modified = 0usize;
-    let mut untracked = 0usize;
-    let mut deleted = 0usize;
-    let mut conflicted = 0usize;
-
-    for entry in statuses.iter() {
-        let status = entry.status();
-
-        // Conflicted
-        if status.contains(Status::CONFLICTED) {
-            conflicted += 1;
-            continue;
-        }
-
-        // Staged (index changes)
-        if status.intersects(
-            Status::INDEX_NEW
-                | Status::INDEX_MODIFIED
-                | Status::INDEX_DELETED
-                | Status::INDEX_RENAMED
-                | Status::INDEX_TYPECHANGE,
-        ) {
-            staged += 1;
-        }
-
-        // Working tree changes
-        if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
-            modified += 1;
-        }
-        if status.contains(Status::WT_DELETED) {
-            deleted += 1;
-        }
-        if status.contains(Status::WT_NEW) {
-            untracked += 1;
-        }
-    }
+    // Skipped entirely above the large-repo file threshold or on the fast path,
+    // and while a git command holds index.lock: the index and worktree are
+    // mid-update, so counts would be bogus
+    let status = if checks.dirty && !op_in_progress {
+        timings.time("git status", || {
+            budgeted_status(&repo, repo_root, git_dir, status_budget, new_file_limit)
+        })?
+    } else {
+        Some(StatusCounts::default())
+    };
+    let status_timed_out = status.is_none();
+    let StatusCounts {
+        staged,
+        modified,
+        untracked,
+        oversized,
+        deleted,
+        conflicted,
+    } = status.unwrap_or_default();
 
     // Get HEAD - may fail if no commits yet
     let Ok(head) = repo.head() else {
         // No commits yet - try to get branch from HEAD reference
-        let branch = repo
-            .find_reference("HEAD")
-            .ok()
-            .and_then(|r| r.symbolic_target().map(std::string::ToString::to_string))
-            .and_then(|s| s.strip_prefix("refs/heads/").map(String::from));
+        let branch = repo.find_reference("HEAD").ok().and_then(|r| {
+            r.symbolic_target_bytes()?
+                .strip_prefix(b"refs/heads/")
+                .map(lossy)
+        });
 
         return Ok(GitInfo {
             branch,
@@ -96,10 +125,15 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
             staged,
             modified,
             untracked,
+            oversized,
             deleted,
             conflicted,
+            status_timed_out,
             ahead: 0,
             behind: 0,
+            op_in_progress,
+            lfs,
+            lfs_missing,
         });
     };
 
@@ -111,7 +145,7 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
     let branch = if detached {
         None
     } else {
-        head.shorthand().map(String::from)
+        Some(lossy(head.shorthand_bytes()))
     };
 
     // Short commit hash
@@ -119,10 +153,17 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
         .peel_to_commit()
         .map_err(|e| Error::Git(format!("peel_to_commit: {e}")))?;
     let full_hash = head_commit.id().to_string();
-    let head_short = full_hash[..id_length.min(full_hash.len())].to_string();
+    let head_short = text::prefix(&full_hash, id_length).to_string();
 
-    // Ahead/behind upstream
-    let (ahead, behind) = get_ahead_behind(&repo, &head).unwrap_or((0, 0));
+    // Ahead/behind upstream (skipped above the large-repo object threshold
+    // or on the fast path)
+    let (ahead, behind) = if checks.ahead_behind {
+        timings.time("git ahead/behind", || {
+            get_ahead_behind(&repo, &head).unwrap_or((0, 0))
+        })
+    } else {
+        (0, 0)
+    };
 
     Ok(GitInfo {
         branch,
@@ -130,11 +171,189 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
         staged,
         modified,
         untracked,
+        oversized,
         deleted,
         conflicted,
+        status_timed_out,
         ahead,
         behind,
+        op_in_progress,
+        lfs,
+        lfs_missing,
+    })
+}
+
+/// Worktree file counts by status
+#[derive(Debug, Default)]
+struct StatusCounts {
+    staged: usize,
+    modified: usize,
+    untracked: usize,
+    oversized: usize,
+    deleted: usize,
+    conflicted: usize,
+}
+
+/// Open the repo whose work tree is `repo_root`
+///
+/// libgit2 follows only a single gitfile, so a `.git` that isn't a plain
+/// dir is resolved first (see [`detect::git_dir`]), and the work tree of
+/// that or a separated `git_dir` set explicitly.
+fn open(repo_root: &Path, git_dir: Option<&Path>) -> std::result::Result<Repository, git2::Error> {
+    match git_dir
+        .map(Path::to_path_buf)
+        .or_else(|| detect::git_dir(repo_root))
+    {
+        Some(git_dir) if git_dir != repo_root.join(".git") => {
+            let repo = Repository::open(&git_dir)?;
+            repo.set_workdir(repo_root, false)?;
+            Ok(repo)
+        }
+        _ => Repository::open(repo_root),
+    }
+}
+
+/// The bounded status scan (see [`budgeted_status`])
+static STATUS_PHASE: budget::Phase = budget::Phase::new();
+
+/// Status counts within `budget`, None if the scan ran over (or a previous
+/// scan that did is still running)
+///
+/// A bounded scan opens its own handle on the worker thread, since a
+/// `Repository` can't be shared across threads.
+fn budgeted_status(
+    repo: &Repository,
+    repo_root: &Path,
+    git_dir: Option<&Path>,
+    budget: Option<Duration>,
+    new_file_limit: Option<u64>,
+) -> Result<Option<StatusCounts>> {
+    if budget.is_none() {
+        return status_counts(repo, new_file_limit).map(Some);
+    }
+    let repo_root = repo_root.to_path_buf();
+    let git_dir = git_dir.map(Path::to_path_buf);
+    budget::run(&STATUS_PHASE, budget, move || {
+        let repo =
+            open(&repo_root, git_dir.as_deref()).map_err(|e| Error::Git(format!("open: {e}")))?;
+        status_counts(&repo, new_file_limit)
     })
+    .transpose()
+}
+
+fn status_counts(repo: &Repository, new_file_limit: Option<u64>) -> Result<StatusCounts> {
+    // Dotfile repos over $HOME set `status.showUntrackedFiles=no`
+    let untracked = repo
+        .config()
+        .and_then(|config| config.get_string("status.showUntrackedFiles"))
+        .map_or(true, |show| show != "no");
+    let mut opts = StatusOptions::new();
+    // Never write refreshed stat info back to the index: a prompt must not
+    // race real git commands for index.lock
+    opts.include_untracked(untracked)
+        .recurse_untracked_dirs(false)
+        .include_ignored(false)
+        .exclude_submodules(true)
+        .update_index(false);
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| Error::Git(format!("statuses: {e}")))?;
+
+    let mut counts = StatusCounts::default();
+    for entry in &statuses {
+        let status = entry.status();
+
+        // Conflicted
+        if status.contains(Status::CONFLICTED) {
+            counts.conflicted += 1;
+            continue;
+        }
+
+        // Staged (index changes)
+        if status.intersects(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE,
+        ) {
+            counts.staged += 1;
+        }
+
+        // Working tree changes
+        if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
+            counts.modified += 1;
+        }
+        if status.contains(Status::WT_DELETED) {
+            counts.deleted += 1;
+        }
+        if status.contains(Status::WT_NEW) {
+            counts.untracked += 1;
+            if let Some(limit) = new_file_limit
+                && oversized(repo, &entry, limit)
+            {
+                counts.oversized += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Whether an untracked entry is a file larger than `limit`
+///
+/// An untracked dir is one entry (with a trailing `/`) and isn't descended
+/// into; a non-UTF-8 path is skipped.
+fn oversized(repo: &Repository, entry: &git2::StatusEntry<'_>, limit: u64) -> bool {
+    let (Some(workdir), Some(path)) = (repo.workdir(), entry.path()) else {
+        return false;
+    };
+    fs::symlink_metadata(workdir.join(path)).is_ok_and(|meta| meta.is_file() && meta.len() > limit)
+}
+
+/// Whether the repo uses Git LFS, and whether its `lfs` filter is missing
+///
+/// A repo uses LFS when its root `.gitattributes` routes paths through the
+/// `lfs` filter, or it has an `.lfsconfig` or `lfs.*` entries in its own
+/// config (written by `git lfs` on clone). The filter counts as installed
+/// when `git lfs install` has configured it at any level; the `git-lfs`
+/// binary itself isn't looked up.
+fn lfs_state(repo: &Repository, repo_root: &Path) -> (bool, bool) {
+    let config = repo.config().ok();
+    let lfs = fs::read(repo_root.join(".gitattributes")).is_ok_and(|attrs| routes_to_lfs(&attrs))
+        || repo_root.join(".lfsconfig").exists()
+        || config
+            .as_ref()
+            .and_then(|config| config.open_level(ConfigLevel::Local).ok())
+            .is_some_and(|local| {
+                local
+                    .entries(Some("lfs\\..*"))
+                    .is_ok_and(|mut entries| entries.next().is_some())
+            });
+    let missing = lfs && !config.as_ref().is_some_and(lfs_filter_installed);
+    (lfs, missing)
+}
+
+/// Whether a `.gitattributes` file sets `filter=lfs` on any pattern
+fn routes_to_lfs(attrs: &[u8]) -> bool {
+    attrs
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.trim_ascii_start().starts_with(b"#"))
+        .any(|line| {
+            line.split(u8::is_ascii_whitespace)
+                .any(|attr| attr == b"filter=lfs")
+        })
+}
+
+/// Whether `git lfs install` has set up the `lfs` filter
+fn lfs_filter_installed(config: &git2::Config) -> bool {
+    ["filter.lfs.process", "filter.lfs.smudge"]
+        .into_iter()
+        .any(|key| config.get_entry(key).is_ok())
+}
+
+/// Ref name bytes as text, replacing invalid UTF-8 (Git allows any bytes)
+fn lossy(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).into_owned()
 }
 
 /// Get ahead/behind counts relative to upstream
@@ -147,12 +366,9 @@ fn get_ahead_behind(
         return Ok((0, 0));
     }
 
-    // Get the branch
-    let branch = repo.find_branch(
-        head.shorthand()
-            .ok_or_else(|| git2::Error::from_str("no branch name"))?,
-        git2::BranchType::Local,
-    )?;
+    // Wrap HEAD's own ref rather than looking the branch up by name, which
+    // git2 only accepts as UTF-8
+    let branch = git2::Branch::wrap(repo.head()?);
 
     // Get upstream
     let upstream = branch.upstream()?;
@@ -162,3 +378,148 @@ fn get_ahead_behind(
 
     repo.graph_ahead_behind(local_oid, upstream_oid)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::scale::Thresholds;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+
+    /// Repo with one commit, HEAD on a branch named by raw `name` bytes
+    fn repo_on_branch(name: &[u8]) -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = Repository::init(tmp.path()).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo.commit(None, &sig, &sig, "init", &tree, &[]).unwrap();
+
+        let git_dir = tmp.path().join(".git");
+        fs::write(
+            git_dir.join("refs/heads").join(OsStr::from_bytes(name)),
+            format!("{commit}\n"),
+        )
+        .unwrap();
+        let mut head = b"ref: refs/heads/".to_vec();
+        head.extend(name);
+        head.push(b'\n');
+        fs::write(git_dir.join("HEAD"), head).unwrap();
+        tmp
+    }
+
+    fn branch(repo_root: &Path) -> Option<String> {
+        collect(
+            repo_root,
+            None,
+            7,
+            CollectMode::Scaled(Thresholds {
+                files: 0,
+                objects: 0,
+            }),
+            None,
+            None,
+            &mut Timings::default(),
+        )
+        .unwrap()
+        .branch
+    }
+
+    #[test]
+    fn latin1_branch_is_lossy() {
+        let tmp = repo_on_branch(b"caf\xe9");
+        assert_eq!(branch(tmp.path()).as_deref(), Some("caf\u{fffd}"));
+    }
+
+    #[test]
+    fn invalid_utf8_branch_is_lossy() {
+        let tmp = repo_on_branch(b"fix-\xff\xfe");
+        assert_eq!(branch(tmp.path()).as_deref(), Some("fix-\u{fffd}\u{fffd}"));
+    }
+
+    #[test]
+    fn chained_gitfiles_open_the_real_repo() {
+        let tmp = repo_on_branch(b"main");
+        let wt = tmp.path().join("wt");
+        fs::create_dir(&wt).unwrap();
+        fs::write(tmp.path().join("hop"), "gitdir: .git\n").unwrap();
+        fs::write(wt.join(".git"), "gitdir: ../hop\n").unwrap();
+        assert_eq!(branch(&wt).as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn unborn_invalid_utf8_branch_is_lossy() {
+        let tmp = tempfile::tempdir().unwrap();
+        Repository::init(tmp.path()).unwrap();
+        fs::write(tmp.path().join(".git/HEAD"), b"ref: refs/heads/\xe9t\xe9\n").unwrap();
+        assert_eq!(branch(tmp.path()).as_deref(), Some("\u{fffd}t\u{fffd}"));
+    }
+
+    #[test]
+    fn untracked_files_over_the_limit_are_oversized() {
+        let tmp = repo_on_branch(b"main");
+        fs::write(tmp.path().join("small.txt"), "ok").unwrap();
+        fs::write(tmp.path().join("dump.bin"), vec![0; 64]).unwrap();
+        fs::create_dir(tmp.path().join("build")).unwrap();
+        fs::write(tmp.path().join("build/big.bin"), vec![0; 64]).unwrap();
+        let repo = Repository::open(tmp.path()).unwrap();
+        let counts = status_counts(&repo, Some(32)).unwrap();
+        assert_eq!((counts.untracked, counts.oversized), (3, 1));
+        assert_eq!(status_counts(&repo, None).unwrap().oversized, 0);
+    }
+
+    #[test]
+    fn index_lock_skips_the_status_scan() {
+        let tmp = repo_on_branch(b"main");
+        fs::write(tmp.path().join("new.txt"), "new").unwrap();
+        fs::write(tmp.path().join(".git/index.lock"), "").unwrap();
+        let info = collect(
+            tmp.path(),
+            None,
+            7,
+            CollectMode::Scaled(Thresholds {
+                files: 0,
+                objects: 0,
+            }),
+            None,
+            None,
+            &mut Timings::default(),
+        )
+        .unwrap();
+        assert!(info.op_in_progress);
+        assert_eq!((info.untracked, info.status_timed_out), (0, false));
+        assert_eq!(info.branch.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn lfs_is_detected_from_attributes_and_config() {
+        assert!(routes_to_lfs(
+            b"*.psd filter=lfs diff=lfs merge=lfs -text\n"
+        ));
+        assert!(!routes_to_lfs(b"# *.psd filter=lfs\n*.sh text eol=lf\n"));
+
+        let tmp = repo_on_branch(b"main");
+        let repo = Repository::open(tmp.path()).unwrap();
+        assert!(!lfs_state(&repo, tmp.path()).0);
+        repo.config()
+            .unwrap()
+            .set_i32("lfs.repositoryformatversion", 0)
+            .unwrap();
+        assert!(lfs_state(&repo, tmp.path()).0);
+    }
+
+    #[test]
+    fn lfs_filter_is_installed_by_either_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config");
+        fs::write(&path, "[core]\n\tbare = false\n").unwrap();
+        let mut config = git2::Config::open(&path).unwrap();
+        assert!(!lfs_filter_installed(&config));
+        config
+            .set_str("filter.lfs.process", "git-lfs filter-process")
+            .unwrap();
+        assert!(lfs_filter_installed(&config));
+    }
+}
@@ -0,0 +1,153 @@
+//! Mercurial info collection - direct reads of `.hg`, no `hg` subprocess
+//!
+//! The readers are shared with Sapling, whose `.sl` keeps the same files.
+
+use crate::error::{Error, Result};
+use crate::text;
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Mercurial repo info
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HgInfo {
+    /// Named branch of the working copy
+    pub branch: String,
+    /// Active bookmark, None when no bookmark is active
+    pub bookmark: Option<String>,
+    /// Working copy parent hash, truncated to `id_length`
+    pub node: String,
+}
+
+/// Collect Mercurial info for the repo rooted at `repo_root`
+///
+/// # Errors
+///
+/// Returns an error if the dirstate can't be read or is truncated.
+pub fn collect(repo_root: &Path, id_length: usize) -> Result<HgInfo> {
+    let dot_dir = repo_root.join(".hg");
+    let node = read_parent(&dot_dir).map_err(|e| dirstate_error(e, Error::Hg))?;
+    let node = text::prefix(&node, id_length).to_string();
+    Ok(HgInfo {
+        branch: read_branch(&dot_dir)?,
+        bookmark: read_bookmark(&dot_dir)?,
+        node,
+    })
+}
+
+/// Marker opening a dirstate-v2 docket, followed by the padded parents
+const DIRSTATE_V2_MARKER: &[u8] = b"dirstate-v2\n";
+
+/// First parent of the working copy as hex
+///
+/// Classic dirstate and treestate files start with the two 20-byte parent
+/// hashes; a dirstate-v2 docket has them right after its marker. A file too
+/// short to hold a parent fails with `UnexpectedEof`.
+pub(crate) fn read_parent(dot_dir: &Path) -> io::Result<String> {
+    let mut head = [0u8; DIRSTATE_V2_MARKER.len() + 20];
+    let mut file = fs::File::open(dot_dir.join("dirstate"))?;
+    file.read_exact(&mut head[..20])?;
+    let p1 = if head.starts_with(DIRSTATE_V2_MARKER) {
+        file.read_exact(&mut head[20..])?;
+        &head[DIRSTATE_V2_MARKER.len()..]
+    } else {
+        &head[..20]
+    };
+    let mut hex = String::with_capacity(40);
+    for b in p1 {
+        let _ = write!(hex, "{b:02x}");
+    }
+    Ok(hex)
+}
+
+/// Map a `read_parent` failure, naming truncation with the repo's variant
+pub(crate) fn dirstate_error(e: io::Error, vcs: fn(String) -> Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => vcs("dirstate is truncated".into()),
+        _ => Error::Io(e),
+    }
+}
+
+/// Active bookmark from `bookmarks.current`, None when absent or empty
+pub(crate) fn read_bookmark(dot_dir: &Path) -> io::Result<Option<String>> {
+    Ok(read_optional(&dot_dir.join("bookmarks.current"))?.filter(|name| !name.is_empty()))
+}
+
+/// Named branch; Mercurial only writes `branch` off the default branch
+fn read_branch(dot_dir: &Path) -> io::Result<String> {
+    Ok(read_optional(&dot_dir.join("branch"))?
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "default".to_string()))
+}
+
+/// Trimmed file contents, None when the file doesn't exist. Names aren't
+/// necessarily UTF-8, so invalid bytes are replaced rather than failing.
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(String::from_utf8_lossy(&contents).trim().to_string())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(dirstate: &[u8]) -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join(".hg")).unwrap();
+        fs::write(tmp.path().join(".hg/dirstate"), dirstate).unwrap();
+        tmp
+    }
+
+    #[test]
+    fn default_branch_without_bookmark() {
+        let tmp = repo(&[0xab; 40]);
+        assert_eq!(
+            collect(tmp.path(), 12).unwrap(),
+            HgInfo {
+                branch: "default".into(),
+                bookmark: None,
+                node: "abababababab".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn named_branch_and_bookmark() {
+        let tmp = repo(&[0x01; 40]);
+        fs::write(tmp.path().join(".hg/branch"), "stable\n").unwrap();
+        fs::write(tmp.path().join(".hg/bookmarks.current"), "feat").unwrap();
+        let info = collect(tmp.path(), 8).unwrap();
+        assert_eq!(info.branch, "stable");
+        assert_eq!(info.bookmark.as_deref(), Some("feat"));
+    }
+
+    #[test]
+    fn non_utf8_names_are_lossy() {
+        let tmp = repo(&[0x01; 40]);
+        fs::write(tmp.path().join(".hg/branch"), b"caf\xe9\n").unwrap();
+        fs::write(tmp.path().join(".hg/bookmarks.current"), b"\xff\xfeok").unwrap();
+        let info = collect(tmp.path(), 8).unwrap();
+        assert_eq!(info.branch, "caf\u{fffd}");
+        assert_eq!(info.bookmark.as_deref(), Some("\u{fffd}\u{fffd}ok"));
+    }
+
+    #[test]
+    fn dirstate_v2_docket() {
+        let mut docket = DIRSTATE_V2_MARKER.to_vec();
+        docket.extend([0xcd; 20]);
+        docket.extend([0; 12 + 32]);
+        let tmp = repo(&docket);
+        assert_eq!(collect(tmp.path(), 8).unwrap().node, "cdcdcdcd");
+    }
+
+    #[test]
+    fn truncated_dirstate_is_an_error() {
+        let tmp = repo(b"dirstate-v2\n\x01\x02\x03\x04\x05\x06\x07\x08");
+        assert!(matches!(collect(tmp.path(), 8), Err(Error::Hg(_))));
+    }
+}
@@ -0,0 +1,271 @@
+//! Hooks - external commands from `[hooks.NAME]` config file tables whose
+//! output is spliced into the segment
+//!
+//! Hooks run through the shell in the repo root, all at once and alongside
+//! collection, each killed (with anything it started) when its timeout runs
+//! out. The trimmed first line
+//! of a hook's stdout is appended to the segment in name order; a hook that
+//! fails, times out or prints nothing adds nothing.
+
+use crate::config::Config;
+use crate::output::OutputFormat;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use toml::Value;
+
+/// Time a hook gets without `timeout_ms`
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How often a running hook is polled for exit
+const POLL: Duration = Duration::from_millis(2);
+
+/// One `[hooks.NAME]` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub name: String,
+    /// Shell command line
+    pub command: String,
+    pub timeout: Duration,
+}
+
+impl Hook {
+    /// Hook from its config file table
+    ///
+    /// # Errors
+    ///
+    /// Returns a message when `command` is missing or a key is unknown or
+    /// of the wrong type.
+    pub fn from_table(name: &str, table: &Value) -> Result<Self, String> {
+        let Value::Table(table) = table else {
+            return Err(format!("expected a table, found {}", table.type_str()));
+        };
+        let mut command = None;
+        let mut timeout = DEFAULT_TIMEOUT;
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("command", Value::String(s)) if !s.trim().is_empty() => command = Some(s.clone()),
+                ("timeout_ms", Value::Integer(ms)) if *ms >= 0 => {
+                    timeout = Duration::from_millis(ms.unsigned_abs());
+                }
+                ("command", _) => return Err("`command`: expected a command line".to_string()),
+                ("timeout_ms", _) => {
+                    return Err("`timeout_ms`: expected a non-negative number".to_string());
+                }
+                (key, _) => return Err(format!("unknown key `{key}`")),
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            command: command.ok_or("missing `command`")?,
+            timeout,
+        })
+    }
+}
+
+/// Output of one hook that printed something
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutput {
+    pub name: String,
+    pub text: String,
+}
+
+/// Hooks started in the background, to [`Running::finish`] once the
+/// segment is rendered
+#[derive(Debug)]
+pub struct Running {
+    started: Instant,
+    children: Vec<(Hook, Option<Child>)>,
+}
+
+/// Start every hook in `dir`
+#[must_use]
+pub fn start(hooks: &[Hook], dir: &Path) -> Running {
+    let children = hooks
+        .iter()
+        .map(|hook| {
+            let child = shell(&hook.command)
+                .current_dir(dir)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .inspect_err(|e| tracing::warn!("hook {}: {e}", hook.name))
+                .ok();
+            (hook.clone(), child)
+        })
+        .collect();
+    Running {
+        started: Instant::now(),
+        children,
+    }
+}
+
+impl Running {
+    /// Wait for the hooks, killing those past their timeout
+    #[must_use]
+    pub fn finish(self) -> Vec<HookOutput> {
+        let started = self.started;
+        self.children
+            .into_iter()
+            .filter_map(|(hook, child)| {
+                let text = wait(child?, started + hook.timeout);
+                if text.is_none() {
+                    tracing::debug!(hook = hook.name, "no output");
+                }
+                Some(HookOutput {
+                    name: hook.name,
+                    text: text?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Trimmed first line of a successful hook's stdout, None on failure,
+/// timeout or blank output
+fn wait(mut child: Child, deadline: Instant) -> Option<String> {
+    let mut stdout = child.stdout.take()?;
+    // Read on a thread so a chatty hook can't block on a full pipe
+    let (tx, rx) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stdout.read_to_string(&mut text);
+        let _ = tx.send(text);
+    });
+    let succeeded = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status.success(),
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL),
+            _ => {
+                kill(&mut child);
+                let _ = child.wait();
+                break false;
+            }
+        }
+    };
+    // A background grandchild may hold stdout open past the hook's exit
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let text = rx.recv_timeout(remaining.max(POLL)).ok()?;
+    let line = text.lines().next()?.trim();
+    (succeeded && !line.is_empty()).then(|| line.to_string())
+}
+
+/// Kill a hook and, on Unix, every process it started (its process group)
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = i32::try_from(child.id())
+        .ok()
+        .and_then(rustix::process::Pid::from_raw)
+    {
+        let _ = rustix::process::kill_process_group(pid, rustix::process::Signal::KILL);
+    }
+    let _ = child.kill();
+}
+
+/// The shell running `command`, leading its own process group so a timeout
+/// can kill whatever it starts
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).process_group(0);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// `prompt` with the hook outputs appended, space-separated and escaped
+/// for the markup; only inline formats take them
+#[must_use]
+pub fn splice(mut prompt: String, outputs: &[HookOutput], config: &Config) -> String {
+    if !matches!(
+        config.output,
+        OutputFormat::Prompt | OutputFormat::Omp | OutputFormat::Tmux | OutputFormat::Zellij
+    ) || prompt.is_empty()
+    {
+        return prompt;
+    }
+    let markup = config.output.markup(config.escapes);
+    for output in outputs {
+        let text: String = output
+            .text
+            .chars()
+            .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+            .collect();
+        prompt.push(' ');
+        prompt.push_str(&markup.escape(&text));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(name: &str, command: &str, timeout_ms: u64) -> Hook {
+        Hook {
+            name: name.into(),
+            command: command.into(),
+            timeout: Duration::from_millis(timeout_ms),
+        }
+    }
+
+    #[test]
+    fn tables() {
+        let table: toml::Table = "command = \"ci-status --short\"\ntimeout_ms = 50\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Hook::from_table("ci", &Value::Table(table)),
+            Ok(hook("ci", "ci-status --short", 50))
+        );
+        let table: toml::Table = "cmd = \"x\"\n".parse().unwrap();
+        assert_eq!(
+            Hook::from_table("ci", &Value::Table(table)),
+            Err("unknown key `cmd`".to_string())
+        );
+        assert!(Hook::from_table("ci", &Value::from("ci-status")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn outputs_failures_and_timeouts() {
+        let hooks = [
+            hook("ci", "printf '  passing \\nmore\\n'", 2000),
+            hook("failed", "echo no; exit 1", 2000),
+            hook("blank", "echo", 2000),
+            hook("slow", "sleep 5; echo late", 50),
+        ];
+        let started = Instant::now();
+        let outputs = start(&hooks, Path::new(".")).finish();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(
+            outputs,
+            [HookOutput {
+                name: "ci".into(),
+                text: "passing".into(),
+            }]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_kills_what_the_hook_started() {
+        let tmp = tempfile::tempdir().unwrap();
+        let marker = tmp.path().join("survived");
+        let command = format!("(sleep 1; touch '{}') & sleep 5", marker.display());
+        let outputs = start(&[hook("slow", &command, 50)], Path::new(".")).finish();
+        assert!(outputs.is_empty());
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+    }
+}
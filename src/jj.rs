@@ -1,45 +1,169 @@
 //! JJ repository info collection
 
+use crate::budget;
+use crate::color::{Style, TermColor};
+use crate::config::{DisplayConfig, home_dir};
 use crate::error::{Error, Result};
-use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+use crate::scale::{Checks, CollectMode};
+use crate::text;
+use crate::timings::Timings;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::config::{
+    ConfigGetResultExt as _, ConfigLayer, ConfigSource, ConfigValue, StackedConfig,
+};
+use jj_lib::git::REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+use jj_lib::git_backend::GitBackend;
 use jj_lib::hex_util::encode_reverse_hex;
 use jj_lib::object_id::ObjectId;
-use jj_lib::ref_name::RefName;
-use jj_lib::repo::{Repo, StoreFactories};
-use jj_lib::settings::UserSettings;
+use jj_lib::operation::Operation;
+use jj_lib::ref_name::{RefName, RemoteName};
+use jj_lib::repo::{ReadonlyRepo, Repo, RepoLoader, StoreFactories, StoreLoadError};
+use jj_lib::repo_path::RepoPathUiConverter;
+use jj_lib::revset::{
+    self, RevsetAliasesMap, RevsetDiagnostics, RevsetExtensions, RevsetParseContext,
+    RevsetWorkspaceContext, SymbolResolver,
+};
+use jj_lib::settings::{HumanByteSize, UserSettings};
+use jj_lib::store::Store;
 use jj_lib::str_util::{StringMatcher, StringPattern};
-use jj_lib::workspace::{Workspace, default_working_copy_factories};
-use std::path::Path;
-use std::sync::Arc;
+use jj_lib::view::View;
+use jj_lib::workspace::{Workspace, WorkspaceLoadError, default_working_copy_factories};
+use pollster::FutureExt as _;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
 /// JJ repository status info
 ///
 /// Bool fields are independent, orthogonal status flags - each can be
 /// true/false independently. Bitflags would add complexity without benefit.
-#[derive(Debug)]
+/// Fields for hidden display elements are left empty/false.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::struct_excessive_bools)]
 pub struct JjInfo {
     /// Short change ID (8 chars)
     pub change_id: String,
-    /// Shortest unique prefix length for `change_id`
+    /// Shortest unique prefix length for `change_id` (0 if prefix coloring is off)
     pub change_id_prefix_len: usize,
     /// Bookmarks with distances: vec of (name, distance). Empty if none found.
     /// Distance 0 = directly on WC, 1+ = ancestor distance
+    #[cfg_attr(feature = "serde", serde(with = "bookmark_records"))]
     pub bookmarks: Vec<(String, usize)>,
     /// Description is empty (needs commit message)
     pub empty_desc: bool,
     /// Has conflicts in tree
     pub conflict: bool,
+    /// Visible commits with conflicts in the `--conflict-count` revset (0
+    /// when off)
+    pub conflicted_commits: usize,
     /// Multiple commits for same `change_id`
     pub divergent: bool,
+    /// Divergence lookup ran over its time budget
+    pub divergence_timed_out: bool,
     /// Whether any bookmark has a remote
     pub has_remote: bool,
     /// Whether any bookmark is synced with remote
     pub is_synced: bool,
+    /// A `jj` command held the working copy or op heads lock; the info is
+    /// from before it finished
+    pub op_in_progress: bool,
+    /// Read from the snapshot of an earlier op head under
+    /// `--stale-while-revalidate`; rendered as usual, and refreshed by the
+    /// binary in the background. Not part of the `--output json` model
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub stale: bool,
+    /// Commit backend of the store, as named in `.jj/repo/store/type`:
+    /// `git`, `Simple` (jj's native backend), or another backend's name
+    pub backend: String,
 }
 
-/// Create minimal `UserSettings` for read-only operations
-fn create_user_settings() -> Result<UserSettings> {
+/// `(name, distance)` pairs as `{"name", "distance"}` records, matching the
+/// `--output json` model
+#[cfg(feature = "serde")]
+mod bookmark_records {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct BookmarkRef<'a> {
+        name: &'a str,
+        distance: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Bookmark {
+        name: String,
+        distance: usize,
+    }
+
+    #[allow(clippy::ptr_arg)] // signature fixed by `serde(with)`
+    pub fn serialize<S: Serializer>(
+        bookmarks: &Vec<(String, usize)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(bookmarks.iter().map(|(name, distance)| BookmarkRef {
+            name,
+            distance: *distance,
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(String, usize)>, D::Error> {
+        let bookmarks = Vec::<Bookmark>::deserialize(deserializer)?;
+        Ok(bookmarks
+            .into_iter()
+            .map(|bookmark| (bookmark.name, bookmark.distance))
+            .collect())
+    }
+}
+
+/// Create `UserSettings` for read-only operations: jj's defaults, a
+/// placeholder identity, the user's config and the repo's own config
+///
+/// The user config is found as jj finds it ([`user_config_paths`]); the
+/// repo config (`.jj/repo/config.toml`, shared by every workspace) sits
+/// above it, as in jj, so per-repo settings such as revset aliases apply.
+/// A file that doesn't parse is logged and skipped.
+fn create_user_settings(repo_root: &Path) -> Result<UserSettings> {
+    settings_with(
+        repo_root,
+        &user_config_paths(env::var_os("JJ_CONFIG").as_deref()),
+    )
+}
+
+/// [`create_user_settings`], parsed once while `repo_root` and its config
+/// files are unchanged
+///
+/// A prompt reads the settings for the new-file limit, the workspace load
+/// and the change id colors; long-lived hosts (`watch`, the C library)
+/// reparse after a config edit or for another repo.
+fn user_settings(repo_root: &Path) -> Result<UserSettings> {
+    type Parsed = (
+        PathBuf,
+        Vec<(PathBuf, u64, Option<SystemTime>)>,
+        UserSettings,
+    );
+    static LAST: Mutex<Option<Parsed>> = Mutex::new(None);
+    let stamp = config_stamp(repo_root);
+    let mut last = LAST.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((root, last_stamp, settings)) = last.as_ref()
+        && root == repo_root
+        && *last_stamp == stamp
+    {
+        return Ok(settings.clone());
+    }
+    let settings = create_user_settings(repo_root)?;
+    *last = Some((repo_root.to_path_buf(), stamp, settings.clone()));
+    Ok(settings)
+}
+
+fn settings_with(repo_root: &Path, user_paths: &[PathBuf]) -> Result<UserSettings> {
     let mut config = StackedConfig::with_defaults();
 
     // Minimal config required by UserSettings
@@ -52,28 +176,366 @@ fn create_user_settings() -> Result<UserSettings> {
         .map_err(|e| Error::Jj(format!("set user.email: {e}")))?;
     config.add_layer(user_layer);
 
+    for path in user_paths {
+        load_config(&mut config, ConfigSource::User, path);
+    }
+    if let Some(dir) = repo_dir(repo_root) {
+        load_config(&mut config, ConfigSource::Repo, &dir.join("config.toml"));
+    }
+
     UserSettings::from_config(config).map_err(|e| Error::Jj(format!("settings: {e}")))
 }
 
+/// User config files and directories of `*.toml` files, as jj finds them:
+/// every path in `jj_config` (`$JJ_CONFIG`, a list like `$PATH`) when set,
+/// else `~/.jjconfig.toml`, `jj/config.toml` and `jj/conf.d` under
+/// `$XDG_CONFIG_HOME` or `~/.config`
+fn user_config_paths(jj_config: Option<&OsStr>) -> Vec<PathBuf> {
+    if let Some(paths) = jj_config {
+        return env::split_paths(paths)
+            .filter(|path| !path.as_os_str().is_empty())
+            .collect();
+    }
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")));
+    home_dir()
+        .map(|home| home.join(".jjconfig.toml"))
+        .into_iter()
+        .chain(config_dir.into_iter().flat_map(|dir| {
+            let jj = dir.join("jj");
+            [jj.join("config.toml"), jj.join("conf.d")]
+        }))
+        .collect()
+}
+
+/// Size and mtime of every jj config file collection reads, for the
+/// snapshot cache to notice an edit (a new `trunk()` alias changes the
+/// ancestor walk)
+#[must_use]
+pub fn config_stamp(repo_root: &Path) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    let mut paths = user_config_paths(env::var_os("JJ_CONFIG").as_deref());
+    paths.extend(repo_dir(repo_root).map(|dir| dir.join("config.toml")));
+    let mut files = Vec::new();
+    for path in paths {
+        if let Ok(entries) = fs::read_dir(&path) {
+            let mut toml: Vec<_> = entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect();
+            toml.sort();
+            files.extend(toml);
+        } else {
+            files.push(path);
+        }
+    }
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let meta = fs::metadata(&path).ok()?;
+            Some((path, meta.len(), meta.modified().ok()))
+        })
+        .collect()
+}
+
+/// Add a config file, or each `*.toml` file of a directory, as layers;
+/// missing paths are skipped and unreadable ones logged
+fn load_config(config: &mut StackedConfig, source: ConfigSource, path: &Path) {
+    let loaded = if path.is_dir() {
+        config.load_dir(source, path)
+    } else if path.is_file() {
+        config.load_file(source, path)
+    } else {
+        return;
+    };
+    if let Err(e) = loaded {
+        tracing::warn!("{}: {e}", path.display());
+    }
+}
+
+/// Styles of the change id's unique prefix and rest from jj's `colors`
+/// settings (user and repo config), as `jj log` paints them; None for a
+/// part no rule colors
+///
+/// Rules apply most general first: `change_id`, then `prefix` or `rest`,
+/// then `change_id prefix` or `change_id rest`, each overriding what the
+/// ones before it set.
+#[must_use]
+pub fn change_id_styles(repo_root: &Path) -> [Option<Style>; 2] {
+    user_settings(repo_root).map_or([None, None], |settings| id_styles(&settings))
+}
+
+/// jj's `snapshot.max-new-file-size` when unset
+pub const DEFAULT_MAX_NEW_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Size above which jj refuses to snapshot a new file, from
+/// `snapshot.max-new-file-size` (user and repo config); None when it's 0,
+/// which jj takes as no limit
+#[must_use]
+pub fn max_new_file_size(repo_root: &Path) -> Option<u64> {
+    let limit = user_settings(repo_root).map_or(DEFAULT_MAX_NEW_FILE_SIZE, |settings| {
+        new_file_limit(&settings)
+    });
+    (limit > 0).then_some(limit)
+}
+
+fn new_file_limit(settings: &UserSettings) -> u64 {
+    match settings
+        .get_value_with("snapshot.max-new-file-size", HumanByteSize::try_from)
+        .optional()
+    {
+        Ok(Some(HumanByteSize(limit))) => limit,
+        Ok(None) => DEFAULT_MAX_NEW_FILE_SIZE,
+        Err(e) => {
+            tracing::warn!("{e}");
+            DEFAULT_MAX_NEW_FILE_SIZE
+        }
+    }
+}
+
+fn id_styles(settings: &UserSettings) -> [Option<Style>; 2] {
+    ["prefix", "rest"].map(|part| {
+        let mut style = None;
+        for label in [
+            "change_id".to_string(),
+            part.to_string(),
+            format!("change_id {part}"),
+        ] {
+            if let Ok(value) = settings.get_value(["colors", label.as_str()]) {
+                apply_color_rule(&value, style.get_or_insert_with(Style::default));
+            }
+        }
+        style
+    })
+}
+
+/// Merge one jj color rule (a color, or a table of `fg`, `bg`, `bold`,
+/// `italic`, `underline`, `dim`) into `style`
+fn apply_color_rule(value: &ConfigValue, style: &mut Style) {
+    if let Some(color) = value.as_str() {
+        style.fg = jj_color(color);
+        return;
+    }
+    let Some(table) = value.as_inline_table() else {
+        return;
+    };
+    let flag = |key: &str| table.get(key).and_then(ConfigValue::as_bool);
+    if let Some(fg) = table.get("fg").and_then(ConfigValue::as_str) {
+        style.fg = jj_color(fg);
+    }
+    if let Some(bg) = table.get("bg").and_then(ConfigValue::as_str) {
+        style.bg = jj_color(bg);
+    }
+    for (key, slot) in [
+        ("bold", &mut style.bold),
+        ("italic", &mut style.italic),
+        ("underline", &mut style.underline),
+        ("dim", &mut style.dimmed),
+    ] {
+        if let Some(set) = flag(key) {
+            *slot = set;
+        }
+    }
+}
+
+/// A jj color: a name (`magenta`, `bright black`), `ansi-color-N` or
+/// `#rrggbb`; None for `default` and anything unknown
+fn jj_color(spec: &str) -> Option<TermColor> {
+    if let Some(n) = spec.strip_prefix("ansi-color-") {
+        return n.parse().ok().map(TermColor::Indexed);
+    }
+    if let Some(name) = spec.strip_prefix("bright ") {
+        return match name.parse() {
+            Ok(TermColor::Indexed(n)) if n < 8 && !name.starts_with(char::is_numeric) => {
+                Some(TermColor::Indexed(n + 8))
+            }
+            _ => None,
+        };
+    }
+    spec.parse()
+        .ok()
+        .filter(|_| !spec.starts_with(char::is_numeric))
+}
+
+/// The pseudo-remote under which a Git-backed repo tracks the backing Git
+/// repo's refs; None for other backends, where a remote named `git` is real
+fn git_tracking_remote(store: &Store) -> Option<&'static RemoteName> {
+    store
+        .backend_impl::<GitBackend>()
+        .map(|_| REMOTE_NAME_FOR_LOCAL_GIT_REPO)
+}
+
+/// The repo directory shared by a workspace (`.jj/repo`, which secondary
+/// workspaces hold as a file pointing at the main one's)
+pub(crate) fn repo_dir(repo_root: &Path) -> Option<PathBuf> {
+    let jj_dir = repo_root.join(".jj");
+    let repo_dir = jj_dir.join("repo");
+    if repo_dir.is_file() {
+        return Some(jj_dir.join(fs::read_to_string(&repo_dir).ok()?));
+    }
+    Some(repo_dir)
+}
+
+/// jj's own definitions of `immutable_heads()`, `mutable()` and the aliases
+/// behind them, as jj ships them; the user's and repo's `revset-aliases`
+/// override them
+const BUILTIN_ALIASES: [(&str, &str); 5] = [
+    (
+        "trunk()",
+        "latest(
+            remote_bookmarks(exact:\"main\", exact:\"origin\") |
+            remote_bookmarks(exact:\"master\", exact:\"origin\") |
+            remote_bookmarks(exact:\"trunk\", exact:\"origin\") |
+            remote_bookmarks(exact:\"main\", exact:\"upstream\") |
+            remote_bookmarks(exact:\"master\", exact:\"upstream\") |
+            remote_bookmarks(exact:\"trunk\", exact:\"upstream\") |
+            root()
+        )",
+    ),
+    (
+        "builtin_immutable_heads()",
+        "present(trunk()) | tags() | untracked_remote_bookmarks()",
+    ),
+    ("immutable_heads()", "builtin_immutable_heads()"),
+    ("immutable()", "::(immutable_heads() | root())"),
+    ("mutable()", "~immutable()"),
+];
+
+/// Aliases whose redefinition changes `immutable_heads()`
+const IMMUTABLE_HEADS_ALIASES: [&str; 3] =
+    ["trunk()", "builtin_immutable_heads()", "immutable_heads()"];
+
+/// Commits the ancestor walk stops at
+///
+/// When the config redefines an alias behind `immutable_heads()`, such as
+/// `trunk()` for a repo whose trunk is `develop`, the revset is evaluated
+/// with the config's aliases, which needs the index (`repo`). Otherwise, and
+/// when the index isn't loaded, jj's built-in definition is mirrored from
+/// the view alone.
+fn immutable_heads(
+    settings: &UserSettings,
+    repo: Option<&ReadonlyRepo>,
+    view: &View,
+    workspace: &Workspace,
+) -> HashSet<CommitId> {
+    let git_tracking = git_tracking_remote(workspace.repo_loader().store());
+    match repo {
+        Some(repo) if redefines_immutable_heads(settings) => {
+            evaluate_revset(settings, repo, workspace, "immutable_heads()").map_or_else(
+                |e| {
+                    tracing::warn!("{e}; using the built-in immutable heads");
+                    find_immutable_heads(view, git_tracking)
+                },
+                HashSet::from_iter,
+            )
+        }
+        _ => find_immutable_heads(view, git_tracking),
+    }
+}
+
+/// Whether the config defines one of [`IMMUTABLE_HEADS_ALIASES`]
+fn redefines_immutable_heads(settings: &UserSettings) -> bool {
+    settings
+        .table_keys("revset-aliases")
+        .any(|decl| IMMUTABLE_HEADS_ALIASES.contains(&decl))
+}
+
+/// The config's `revset-aliases` over [`BUILTIN_ALIASES`]; a declaration
+/// that doesn't parse is logged and skipped
+fn revset_aliases(settings: &UserSettings) -> RevsetAliasesMap {
+    let mut aliases = RevsetAliasesMap::new();
+    for (decl, defn) in BUILTIN_ALIASES {
+        let _ = aliases.insert(decl, defn);
+    }
+    for decl in settings.table_keys("revset-aliases") {
+        let inserted = settings
+            .get_string(["revset-aliases", decl])
+            .map_err(|e| e.to_string())
+            .and_then(|defn| aliases.insert(decl, defn).map_err(|e| e.to_string()));
+        if let Err(e) = inserted {
+            tracing::warn!("revset-aliases.{decl}: {e}");
+        }
+    }
+    aliases
+}
+
+/// Commits of `revset`, evaluated with [`revset_aliases`]
+fn evaluate_revset(
+    settings: &UserSettings,
+    repo: &ReadonlyRepo,
+    workspace: &Workspace,
+    revset: &str,
+) -> Result<Vec<CommitId>> {
+    let revset_error = |e: &dyn std::fmt::Display| Error::Jj(format!("{revset}: {e}"));
+    let aliases = revset_aliases(settings);
+    let extensions = RevsetExtensions::default();
+    let root = workspace.workspace_root().to_path_buf();
+    let path_converter = RepoPathUiConverter::Fs {
+        cwd: root.clone(),
+        base: root,
+    };
+    let context = RevsetParseContext {
+        aliases_map: &aliases,
+        local_variables: HashMap::new(),
+        user_email: settings.user_email(),
+        date_pattern_context: chrono::Local::now().into(),
+        default_ignored_remote: git_tracking_remote(repo.store()),
+        use_glob_by_default: settings
+            .get_bool("ui.revsets-use-glob-by-default")
+            .unwrap_or(true),
+        extensions: &extensions,
+        workspace: Some(RevsetWorkspaceContext {
+            path_converter: &path_converter,
+            workspace_name: workspace.workspace_name(),
+        }),
+    };
+    let expression = revset::parse(&mut RevsetDiagnostics::new(), revset, &context)
+        .map_err(|e| revset_error(&e))?;
+    let resolver = SymbolResolver::new(repo, extensions.symbol_resolvers());
+    let commits = expression
+        .resolve_user_expression(repo, &resolver)
+        .map_err(|e| revset_error(&e))?
+        .evaluate(repo)
+        .map_err(|e| revset_error(&e))?;
+    commits
+        .iter()
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| revset_error(&e))
+}
+
+/// Visible commits with conflicts within `scope`
+///
+/// 0 without a scope or a loaded repo, or if the revset fails (logged).
+fn count_conflicts(
+    settings: &UserSettings,
+    repo: Option<&ReadonlyRepo>,
+    workspace: &Workspace,
+    scope: Option<&str>,
+) -> usize {
+    let (Some(repo), Some(scope)) = (repo, scope) else {
+        return 0;
+    };
+    let revset = format!("conflicts() & ({scope})");
+    evaluate_revset(settings, repo, workspace, &revset)
+        .inspect_err(|e| tracing::warn!("{e}"))
+        .map_or(0, |commits| commits.len())
+}
+
 /// Find immutable head commits (trunk + tags + untracked remote bookmarks)
 /// Mirrors jj's `builtin_immutable_heads()` without revset evaluation
-fn find_immutable_heads(
-    view: &jj_lib::view::View,
-) -> std::collections::HashSet<jj_lib::backend::CommitId> {
-    use std::collections::HashSet;
-
+fn find_immutable_heads(view: &View, git_tracking: Option<&RemoteName>) -> HashSet<CommitId> {
     let mut immutable = HashSet::new();
 
     // Single pass over all remote bookmarks
     for (symbol, remote_ref) in
         view.remote_bookmarks_matching(&StringMatcher::All, &StringMatcher::All)
     {
-        let name = symbol.name.as_str();
-        let remote = symbol.remote.as_str();
-
-        if remote == "git" {
+        if Some(symbol.remote) == git_tracking {
             continue;
         }
+        let name = symbol.name.as_str();
+        let remote = symbol.remote.as_str();
 
         // trunk: main/master/trunk on origin/upstream
         let is_trunk =
@@ -102,23 +564,20 @@ fn find_immutable_heads(
 /// Search for all bookmarks on ancestor commits using BFS
 /// Returns bookmarks sorted by distance (closest first)
 fn find_ancestor_bookmarks(
-    repo: &Arc<jj_lib::repo::ReadonlyRepo>,
+    store: &Arc<Store>,
     view: &jj_lib::view::View,
     wc_id: &jj_lib::backend::CommitId,
     max_depth: usize,
+    immutable_heads: &HashSet<CommitId>,
 ) -> Result<Vec<(String, usize)>> {
-    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::collections::VecDeque;
 
     let mut queue: VecDeque<(jj_lib::backend::CommitId, usize)> = VecDeque::new();
     let mut visited = HashSet::new();
     let mut bookmarks_with_distances: HashMap<String, usize> = HashMap::new();
 
-    // Pre-compute immutable heads to stop traversal at trunk/tags/untracked remotes
-    let immutable_heads = find_immutable_heads(view);
-
     // Start BFS from WC commit parents
-    let wc_commit = repo
-        .store()
+    let wc_commit = store
         .get_commit(wc_id)
         .map_err(|e| Error::Jj(format!("get commit: {e}")))?;
 
@@ -151,8 +610,7 @@ fn find_ancestor_bookmarks(
 
         // Add parents to queue for next level
         if depth < max_depth {
-            let commit = repo
-                .store()
+            let commit = store
                 .get_commit(&commit_id)
                 .map_err(|e| Error::Jj(format!("get commit: {e}")))?;
 
@@ -168,102 +626,379 @@ fn find_ancestor_bookmarks(
     Ok(result)
 }
 
-/// Collect JJ repo info from the given path
-#[must_use = "returns collected repo info, does not modify state"]
-pub fn collect(repo_root: &Path, id_length: usize, ancestor_depth: usize) -> Result<JjInfo> {
-    let settings = create_user_settings()?;
+/// Direct bookmarks on WC (distance 0) followed by ancestor bookmarks, the
+/// search stopping at `immutable_heads`
+fn collect_bookmarks(
+    store: &Arc<Store>,
+    view: &jj_lib::view::View,
+    wc_id: &jj_lib::backend::CommitId,
+    ancestor_depth: usize,
+    immutable_heads: &HashSet<CommitId>,
+) -> Result<Vec<(String, usize)>> {
+    let mut bookmarks: Vec<(String, usize)> = view
+        .local_bookmarks_for_commit(wc_id)
+        .map(|(name, _)| (name.as_str().to_string(), 0))
+        .collect();
 
-    let workspace = Workspace::load(
+    // Always search ancestors if enabled (useful for stacked PR context)
+    // Ancestor bookmarks are disjoint from direct bookmarks (different commits)
+    if ancestor_depth > 0 {
+        let ancestors =
+            find_ancestor_bookmarks(store, view, wc_id, ancestor_depth, immutable_heads)?;
+        bookmarks.extend(ancestors);
+    }
+
+    Ok(bookmarks)
+}
+
+/// Check remote sync status for first (closest) bookmark only
+/// For stacked PRs, this reflects whether current stack position needs pushing
+/// Returns `(has_remote, is_synced)`
+fn remote_sync_status(
+    view: &jj_lib::view::View,
+    bookmarks: &[(String, usize)],
+    git_tracking: Option<&RemoteName>,
+) -> (bool, bool) {
+    let Some((bm_name, _)) = bookmarks.first() else {
+        return (false, true);
+    };
+    let local_target = view.get_local_bookmark(RefName::new(bm_name));
+
+    let name_matcher = StringPattern::exact(bm_name).to_matcher();
+    let mut has_remote = false;
+    let mut is_synced = false;
+
+    for (symbol, remote_ref) in view.remote_bookmarks_matching(&name_matcher, &StringMatcher::All) {
+        if Some(symbol.remote) == git_tracking {
+            continue;
+        }
+        has_remote = true;
+        if remote_ref.target == *local_target {
+            is_synced = true;
+            break;
+        }
+    }
+
+    (has_remote, is_synced || !has_remote)
+}
+
+/// The workspace at `repo_root` and the settings it was loaded with
+fn load_workspace(repo_root: &Path) -> Result<(UserSettings, Workspace)> {
+    open_workspace(user_settings(repo_root)?, repo_root)
+}
+
+/// The workspace at `repo_root`, loaded with `settings`
+fn open_workspace(settings: UserSettings, repo_root: &Path) -> Result<(UserSettings, Workspace)> {
+    Workspace::load(
         &settings,
         repo_root,
         &StoreFactories::default(),
         &default_working_copy_factories(),
     )
-    .map_err(|e| Error::Jj(format!("load workspace: {e}")))?;
+    .map(|workspace| (settings, workspace))
+    .map_err(load_error)
+}
 
-    let repo: Arc<jj_lib::repo::ReadonlyRepo> = workspace
-        .repo_loader()
-        .load_at_head()
-        .map_err(|e| Error::Jj(format!("load repo: {e}")))?;
+/// Map a workspace load failure, singling out stores whose type this
+/// jj-lib doesn't know: the repo was written by a newer `jj`
+fn load_error(e: WorkspaceLoadError) -> Error {
+    match e {
+        WorkspaceLoadError::StoreLoadError(e @ StoreLoadError::UnsupportedType { .. }) => {
+            Error::JjFormat(e.to_string())
+        }
+        e => Error::Jj(format!("load workspace: {e}")),
+    }
+}
 
-    let view = repo.view();
+/// Load the view at the single head operation without building the index
+///
+/// Concurrent operations can leave multiple op heads; merging them needs the
+/// full `load_at_head` path, so that case falls back to it. Merging takes the
+/// op heads lock and writes a merge operation, so read-only mode fails instead.
+fn load_head_view(loader: &RepoLoader, read_only: bool) -> Result<View> {
+    let mut heads = loader
+        .op_heads_store()
+        .get_op_heads()
+        .block_on()
+        .map_err(|e| Error::Jj(format!("op heads: {e}")))?;
 
-    // Get WC commit ID
-    let wc_id = view
-        .wc_commit_ids()
-        .get(workspace.workspace_name())
-        .ok_or_else(|| Error::Jj("no working copy".into()))?;
+    if let (Some(id), true) = (heads.pop(), heads.is_empty()) {
+        let data = loader
+            .op_store()
+            .read_operation(&id)
+            .block_on()
+            .map_err(|e| Error::Jj(format!("read operation: {e}")))?;
+        return Operation::new(loader.op_store().clone(), id, data)
+            .view()
+            .map_err(|e| Error::Jj(format!("read view: {e}")));
+    }
+    if read_only {
+        return Err(Error::Jj(
+            "multiple operation heads, not merging in read-only mode".into(),
+        ));
+    }
 
-    // Load commit
-    let commit = repo
-        .store()
-        .get_commit(wc_id)
-        .map_err(|e| Error::Jj(format!("get commit: {e}")))?;
+    let repo = loader
+        .load_at_head()
+        .map_err(|e| Error::Jj(format!("load repo: {e}")))?;
+    Ok(repo.view().clone())
+}
 
-    // Change ID in JJ's reverse hex format
-    let change_id_full = encode_reverse_hex(commit.change_id().as_bytes());
-    let change_id = change_id_full[..id_length.min(change_id_full.len())].to_string();
+/// Checks affordable for the repo's store
+///
+/// Scale is estimated from the backing git store; other backends only honor
+/// the fast path.
+fn store_checks(store: &Store, mode: CollectMode) -> Checks {
+    match store.backend_impl::<GitBackend>() {
+        Some(git) => mode.checks(git.git_repo_path()),
+        None if matches!(mode, CollectMode::FastPath) => Checks::fast_path(),
+        None => Checks::all(),
+    }
+}
 
-    // Compute shortest unique prefix length for change_id coloring
-    // Uses direct repo API (faster than IdPrefixContext which requires revset evaluation)
-    let change_id_prefix_len = repo
-        .shortest_unique_change_id_prefix_len(commit.change_id())
-        .unwrap_or(id_length)
-        .min(change_id.len());
+/// The bounded divergence lookup (see [`index_lookups`])
+static DIVERGENCE_PHASE: budget::Phase = budget::Phase::new();
 
-    // Empty description check
-    let empty_desc = commit.description().trim().is_empty();
+/// Index-backed lookups: unique prefix length and divergence
+///
+/// Returns `(prefix_len, divergent, timings)`, with `divergent` None if the
+/// lookup ran over `divergence_budget` (or a previous lookup that did is
+/// still running). Both are skipped without a loaded
+/// repo (index not needed for the displayed fields).
+fn index_lookups(
+    repo: Option<&Arc<ReadonlyRepo>>,
+    commit: Option<&Commit>,
+    max_prefix_len: usize,
+    prefix_color: bool,
+    wants_divergence: bool,
+    divergence_budget: Option<Duration>,
+) -> (usize, Option<bool>, Timings) {
+    let mut timings = Timings::default();
+    let (Some(repo), Some(commit)) = (repo, commit) else {
+        return (0, Some(false), timings);
+    };
 
-    // Conflict check
-    let conflict = commit.has_conflict();
+    // Shortest unique prefix length for change_id coloring
+    // Uses direct repo API (faster than IdPrefixContext which requires revset evaluation)
+    let prefix_len = if prefix_color {
+        timings.time("jj unique prefix", || {
+            repo.shortest_unique_change_id_prefix_len(commit.change_id())
+                .map_or(max_prefix_len, |len| len.min(max_prefix_len))
+        })
+    } else {
+        0
+    };
 
     // Divergent check - multiple visible commits for same change_id
-    let divergent = repo
-        .resolve_change_id(commit.change_id())
-        .ok()
-        .flatten()
-        .is_some_and(|resolved| resolved.visible_with_offsets().count() > 1);
+    // Skipped above the large-repo object threshold or on the fast path
+    let divergent = if wants_divergence {
+        let repo = Arc::clone(repo);
+        let change_id = commit.change_id().clone();
+        timings.time("jj divergence", || {
+            budget::run(&DIVERGENCE_PHASE, divergence_budget, move || {
+                repo.resolve_change_id(&change_id)
+                    .ok()
+                    .flatten()
+                    .is_some_and(|resolved| resolved.visible_with_offsets().count() > 1)
+            })
+        })
+    } else {
+        Some(false)
+    };
 
-    // Find bookmarks - first check direct bookmarks on WC (distance 0)
-    let mut bookmarks: Vec<(String, usize)> = view
-        .local_bookmarks_for_commit(wc_id)
-        .map(|(name, _)| (name.as_str().to_string(), 0))
-        .collect();
+    (prefix_len, divergent, timings)
+}
 
-    // Always search ancestors if enabled (useful for stacked PR context)
-    // Ancestor bookmarks are disjoint from direct bookmarks (different commits)
-    if ancestor_depth > 0 {
-        let ancestors = find_ancestor_bookmarks(&repo, view, wc_id, ancestor_depth)?;
-        bookmarks.extend(ancestors);
+/// Whether a `jj` command is mid-flight in the workspace at `repo_root`,
+/// holding the working-copy or op heads lock
+///
+/// The op heads lock is the shared repo's, so a command in any workspace of
+/// it counts. With `read_only`, the locks aren't probed (see [`lock_held`]).
+#[must_use = "returns lock state, does not modify state"]
+pub fn operation_in_progress(repo_root: &Path, read_only: bool) -> bool {
+    let held = |path: &Path| lock_held(path, read_only);
+    held(&repo_root.join(".jj/working_copy/working_copy.lock"))
+        || repo_dir(repo_root).is_some_and(|repo_dir| held(&repo_dir.join("op_heads/lock")))
+}
+
+/// Whether another process holds the jj lock file at `path`, without waiting
+/// for it
+///
+/// On Unix jj holds an exclusive `flock` on the file and removes it when
+/// done, so a file left by a crashed command is stale: a non-blocking shared
+/// lock attempt that would block tells a live holder apart. That attempt is
+/// itself a lock, so with `read_only`, and on other platforms where jj's
+/// lock is the file's existence, the file existing counts.
+fn lock_held(path: &Path, read_only: bool) -> bool {
+    #[cfg(unix)]
+    if !read_only {
+        return fs::File::open(path)
+            .is_ok_and(|file| matches!(file.try_lock_shared(), Err(fs::TryLockError::WouldBlock)));
     }
+    #[cfg(not(unix))]
+    let _ = read_only;
+    path.exists()
+}
+
+/// Collect JJ repo info from the given path
+///
+/// With `read_only`, nothing under `.jj` is written or locked: the index is
+/// never loaded (loading one for an unindexed operation builds and saves it),
+/// so prefix coloring and divergence are skipped.
+///
+/// With a `conflict_scope` revset, its conflicted commits are counted; the
+/// count is skipped where divergence is (read-only, the fast path and large
+/// repos), as it reads every commit in the scope.
+///
+/// # Errors
+///
+/// Returns an error if the workspace, repo or working-copy commit can't be
+/// loaded.
+#[must_use = "returns collected repo info, does not modify state"]
+#[allow(clippy::too_many_arguments)]
+pub fn collect(
+    repo_root: &Path,
+    id_length: usize,
+    ancestor_depth: usize,
+    display: DisplayConfig,
+    mode: CollectMode,
+    divergence_budget: Option<Duration>,
+    conflict_scope: Option<&str>,
+    read_only: bool,
+    timings: &mut Timings,
+) -> Result<JjInfo> {
+    let (settings, workspace) = timings.time("jj workspace load", || load_workspace(repo_root))?;
+    collect_workspace(
+        &settings,
+        &workspace,
+        id_length,
+        ancestor_depth,
+        display,
+        mode,
+        divergence_budget,
+        conflict_scope,
+        read_only,
+        timings,
+    )
+}
+
+/// [`collect`] from a loaded workspace and its settings
+#[allow(clippy::too_many_arguments)]
+fn collect_workspace(
+    settings: &UserSettings,
+    workspace: &Workspace,
+    id_length: usize,
+    ancestor_depth: usize,
+    display: DisplayConfig,
+    mode: CollectMode,
+    divergence_budget: Option<Duration>,
+    conflict_scope: Option<&str>,
+    read_only: bool,
+    timings: &mut Timings,
+) -> Result<JjInfo> {
+    let loader = workspace.repo_loader();
+    let store = loader.store();
 
-    // Check remote sync status for first (closest) bookmark only
-    // For stacked PRs, this reflects whether current stack position needs pushing
-    let (has_remote, is_synced) = if bookmarks.is_empty() {
-        (false, true)
+    let checks = store_checks(store, mode);
+    tracing::debug!(?checks, "collection checks");
+
+    // Only compute fields that will be displayed. Status needs the commit
+    // (conflict, description) and the closest bookmark (sync state).
+    let wants_commit = display.show_id || display.show_status;
+    let wants_bookmarks = display.show_name || display.show_status;
+    let wants_divergence = display.show_status && checks.divergence && !read_only;
+    let prefix_color = display.uses_prefix_color() && !read_only;
+    let walks_ancestors = wants_bookmarks && ancestor_depth > 0;
+    let custom_heads = walks_ancestors && !read_only && redefines_immutable_heads(settings);
+    let conflict_scope = conflict_scope.filter(|scope| wants_divergence && !scope.is_empty());
+
+    // The index is only needed for unique-prefix coloring, divergence and
+    // redefined immutable heads; without it, read the head view directly and
+    // skip index loading
+    let view_start = Instant::now();
+    let repo = (prefix_color || wants_divergence || custom_heads)
+        .then(|| loader.load_at_head())
+        .transpose()
+        .map_err(|e| Error::Jj(format!("load repo: {e}")))?;
+    let head_view;
+    let view = if let Some(repo) = &repo {
+        repo.view()
     } else {
-        let (bm_name, _) = &bookmarks[0];
-        let local_target = view.get_local_bookmark(RefName::new(bm_name));
+        head_view = load_head_view(loader, read_only)?;
+        &head_view
+    };
+    timings.record("jj view read", view_start.elapsed());
+
+    // Get the WC commit
+    let wc_id = view
+        .wc_commit_ids()
+        .get(workspace.workspace_name())
+        .ok_or_else(|| Error::Jj("no working copy".into()))?;
+    let commit = wants_commit
+        .then(|| timings.time("jj commit fetch", || store.get_commit(wc_id)))
+        .transpose()
+        .map_err(|e| Error::Jj(format!("get commit: {e}")))?;
 
-        let name_matcher = StringPattern::exact(bm_name).to_matcher();
-        let mut has_remote = false;
-        let mut is_synced = false;
+    // Change ID in JJ's reverse hex format
+    let change_id = commit.as_ref().map_or_else(String::new, |commit| {
+        let change_id_full = encode_reverse_hex(commit.change_id().as_bytes());
+        text::prefix(&change_id_full, id_length).to_string()
+    });
 
-        for (symbol, remote_ref) in
-            view.remote_bookmarks_matching(&name_matcher, &StringMatcher::All)
-        {
-            if symbol.remote.as_str() == "git" {
-                continue;
-            }
-            has_remote = true;
-            if remote_ref.target == *local_target {
-                is_synced = true;
-                break;
-            }
-        }
+    // Status checks: empty description, conflict
+    let empty_desc = commit
+        .as_ref()
+        .is_some_and(|commit| commit.description().trim().is_empty());
+    let conflict = commit.as_ref().is_some_and(Commit::has_conflict);
 
-        (has_remote, is_synced || !has_remote)
+    let immutable_heads = if walks_ancestors {
+        timings.time("jj immutable heads", || {
+            immutable_heads(settings, repo.as_deref(), view, workspace)
+        })
+    } else {
+        HashSet::new()
     };
+    let conflicted_commits = timings.time("jj conflict count", || {
+        count_conflicts(settings, repo.as_deref(), workspace, conflict_scope)
+    });
+
+    // Index lookups (unique prefix, divergence) are independent of the bookmark
+    // walk and remote matching, so run them on a scoped thread alongside it
+    // Worker-thread phases are timed locally and appended after the join
+    let (index_lookups, bookmarks_and_sync) = std::thread::scope(|s| {
+        let index_lookups = s.spawn(|| {
+            index_lookups(
+                repo.as_ref(),
+                commit.as_ref(),
+                id_length.min(change_id.len()),
+                prefix_color,
+                wants_divergence,
+                divergence_budget,
+            )
+        });
+
+        let bookmarks_and_sync = if wants_bookmarks {
+            timings
+                .time("jj bookmarks", || {
+                    collect_bookmarks(store, view, wc_id, ancestor_depth, &immutable_heads)
+                })
+                .map(|bookmarks| {
+                    let sync = timings.time("jj remote matching", || {
+                        remote_sync_status(view, &bookmarks, git_tracking_remote(store))
+                    });
+                    (bookmarks, sync)
+                })
+        } else {
+            Ok((Vec::new(), (false, true)))
+        };
+        let index_lookups = index_lookups
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e));
+        (index_lookups, bookmarks_and_sync)
+    });
+    let (change_id_prefix_len, divergent, thread_timings) = index_lookups;
+    timings.extend(thread_timings);
+    let (bookmarks, (has_remote, is_synced)) = bookmarks_and_sync?;
 
     Ok(JjInfo {
         change_id,
@@ -271,8 +1006,372 @@ pub fn collect(repo_root: &Path, id_length: usize, ancestor_depth: usize) -> Res
         bookmarks,
         empty_desc,
         conflict,
-        divergent,
+        conflicted_commits,
+        divergent: divergent.unwrap_or(false),
+        divergence_timed_out: divergent.is_none(),
         has_remote,
         is_synced,
+        op_in_progress: false,
+        stale: false,
+        backend: store.backend().name().to_string(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scale::Thresholds;
+    use jj_lib::conflict_labels::ConflictLabels;
+    use jj_lib::merge::Merge;
+    use jj_lib::merged_tree::MergedTree;
+    use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState};
+    use std::collections::BTreeMap;
+
+    /// (len, mtime) of every file and directory under `dir`
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, (u64, SystemTime)> {
+        let mut out = BTreeMap::new();
+        let mut stack = vec![dir.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            for entry in fs::read_dir(&dir).unwrap() {
+                let entry = entry.unwrap();
+                let meta = entry.metadata().unwrap();
+                if meta.is_dir() {
+                    stack.push(entry.path());
+                }
+                out.insert(entry.path(), (meta.len(), meta.modified().unwrap()));
+            }
+        }
+        out
+    }
+
+    /// [`collect`] everything, without the developer's own jj config
+    fn collect_all(repo_root: &Path, read_only: bool) -> Result<JjInfo> {
+        let (settings, workspace) = open_workspace(settings_with(repo_root, &[])?, repo_root)?;
+        collect_workspace(
+            &settings,
+            &workspace,
+            8,
+            10,
+            DisplayConfig::all_visible(),
+            CollectMode::FastPath,
+            None,
+            None,
+            read_only,
+            &mut Timings::default(),
+        )
+    }
+
+    #[test]
+    fn read_only_leaves_repo_untouched() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        // Unlink the index from its operations so a normal load rebuilds it
+        for links in ["op_links", "operations"] {
+            let dir = tmp.path().join(".jj/repo/index").join(links);
+            fs::remove_dir_all(&dir).unwrap();
+            fs::create_dir(&dir).unwrap();
+        }
+
+        let before = snapshot(tmp.path());
+        let info = collect_all(tmp.path(), true).unwrap();
+        assert_eq!(snapshot(tmp.path()), before);
+        assert_eq!(info.change_id_prefix_len, 0);
+        assert!(info.empty_desc);
+
+        // Sanity check: the same collection without read-only does write
+        collect_all(tmp.path(), false).unwrap();
+        assert_ne!(snapshot(tmp.path()), before);
+    }
+
+    #[test]
+    fn held_locks_mark_operation_in_progress() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        assert!(!operation_in_progress(tmp.path(), false));
+        assert!(!operation_in_progress(tmp.path(), true));
+        for lock in ["working_copy/working_copy.lock", "repo/op_heads/lock"] {
+            let file = fs::File::create(tmp.path().join(".jj").join(lock)).unwrap();
+            // A lock file left by a crashed command is stale on Unix, unless
+            // read-only mode can't probe it
+            assert_eq!(operation_in_progress(tmp.path(), false), cfg!(not(unix)));
+            assert!(operation_in_progress(tmp.path(), true));
+            file.lock().unwrap();
+            assert!(operation_in_progress(tmp.path(), false));
+            assert!(operation_in_progress(tmp.path(), true));
+            drop(file);
+            fs::remove_file(tmp.path().join(".jj").join(lock)).unwrap();
+        }
+
+        // A secondary workspace shares the main one's op heads lock
+        let secondary = tmp.path().join("secondary");
+        fs::create_dir_all(secondary.join(".jj")).unwrap();
+        fs::write(
+            secondary.join(".jj/repo"),
+            tmp.path().join(".jj/repo").to_str().unwrap(),
+        )
+        .unwrap();
+        assert!(!operation_in_progress(&secondary, false));
+        let file = fs::File::create(tmp.path().join(".jj/repo/op_heads/lock")).unwrap();
+        file.lock().unwrap();
+        assert!(operation_in_progress(&secondary, false));
+    }
+
+    #[test]
+    fn native_backend_keeps_a_remote_named_git() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (workspace, repo) =
+            Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        let wc_id = repo.view().wc_commit_ids()[workspace.workspace_name()].clone();
+        let mut tx = repo.start_transaction();
+        let main = RefName::new("main");
+        tx.repo_mut()
+            .set_local_bookmark_target(main, RefTarget::normal(wc_id.clone()));
+        tx.repo_mut().set_remote_bookmark(
+            main.to_remote_symbol(RemoteName::new("git")),
+            RemoteRef {
+                target: RefTarget::normal(wc_id),
+                state: RemoteRefState::Tracked,
+            },
+        );
+        tx.commit("push to a remote named git").unwrap();
+
+        let info = collect_all(tmp.path(), true).unwrap();
+        assert_eq!(info.backend, "Simple");
+        assert_eq!(info.bookmarks, [("main".to_string(), 0)]);
+        assert!(info.has_remote && info.is_synced);
+    }
+
+    #[test]
+    fn trunk_alias_stops_the_ancestor_walk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (workspace, repo) =
+            Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        let root = repo.store().root_commit();
+        let mut tx = repo.start_transaction();
+        let mut stack = Vec::new();
+        let mut parent = root.id().clone();
+        for name in ["base", "dev"] {
+            let commit = tx
+                .repo_mut()
+                .new_commit(vec![parent], root.tree())
+                .write()
+                .unwrap();
+            tx.repo_mut().set_local_bookmark_target(
+                RefName::new(name),
+                RefTarget::normal(commit.id().clone()),
+            );
+            parent = commit.id().clone();
+            stack.push(commit);
+        }
+        tx.repo_mut().set_remote_bookmark(
+            RefName::new("dev").to_remote_symbol(RemoteName::new("origin")),
+            RemoteRef {
+                target: RefTarget::normal(parent),
+                state: RemoteRefState::Tracked,
+            },
+        );
+        tx.repo_mut()
+            .check_out(workspace.workspace_name().to_owned(), &stack[1])
+            .unwrap();
+        tx.repo_mut().rebase_descendants().unwrap();
+        tx.commit("stack on dev").unwrap();
+
+        let bookmarks = |read_only| collect_all(tmp.path(), read_only).unwrap().bookmarks;
+        let both = [("dev".to_string(), 1), ("base".to_string(), 2)];
+        assert_eq!(bookmarks(false), both);
+        fs::write(
+            tmp.path().join(".jj/repo/config.toml"),
+            "[revset-aliases]\n'trunk()' = 'dev@origin'\n",
+        )
+        .unwrap();
+        assert_eq!(bookmarks(false), both[..1]);
+        // Without the index the built-in definition is mirrored
+        assert_eq!(bookmarks(true), both);
+    }
+
+    #[test]
+    fn conflicted_commits_are_counted_in_scope() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (workspace, repo) =
+            Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        let store = repo.store();
+        let empty = store.empty_tree_id().clone();
+        let conflicted = MergedTree::new(
+            Arc::clone(store),
+            Merge::from_vec(vec![empty.clone(), empty.clone(), empty]),
+            ConflictLabels::unlabeled(),
+        );
+        let mut tx = repo.start_transaction();
+        let mut parent = store.root_commit_id().clone();
+        for _ in 0..2 {
+            let commit = tx
+                .repo_mut()
+                .new_commit(vec![parent], conflicted.clone())
+                .write()
+                .unwrap();
+            parent = commit.id().clone();
+        }
+        let tip = tx.repo_mut().store().get_commit(&parent).unwrap();
+        tx.repo_mut()
+            .check_out(workspace.workspace_name().to_owned(), &tip)
+            .unwrap();
+        tx.repo_mut().rebase_descendants().unwrap();
+        tx.commit("two conflicted commits").unwrap();
+
+        let count = |scope| {
+            collect(
+                tmp.path(),
+                8,
+                10,
+                DisplayConfig::all_visible(),
+                CollectMode::Scaled(Thresholds {
+                    files: 0,
+                    objects: 0,
+                }),
+                None,
+                scope,
+                false,
+                &mut Timings::default(),
+            )
+            .unwrap()
+            .conflicted_commits
+        };
+        assert_eq!(count(None), 0);
+        assert_eq!(
+            count(Some("all()")),
+            3,
+            "the working copy inherits the conflict"
+        );
+        assert_eq!(count(Some("@-")), 1);
+        assert_eq!(count(Some("mutable()")), 3);
+        assert_eq!(count(Some("not a revset(")), 0);
+    }
+
+    #[test]
+    fn repo_config_joins_the_settings() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        fs::write(
+            tmp.path().join(".jj/repo/config.toml"),
+            "[revset-aliases]\n'trunk()' = 'dev@origin'\n",
+        )
+        .unwrap();
+        let settings = settings_with(tmp.path(), &[]).unwrap();
+        assert_eq!(
+            settings.get_string(["revset-aliases", "trunk()"]).unwrap(),
+            "dev@origin"
+        );
+
+        fs::write(tmp.path().join(".jj/repo/config.toml"), "revset-aliases = ").unwrap();
+        assert!(
+            settings_with(tmp.path(), &[]).is_ok(),
+            "bad config is skipped"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn jj_config_replaces_the_user_config_paths() {
+        assert_eq!(
+            user_config_paths(Some(OsStr::new("/work/jj.toml::/conf.d"))),
+            [PathBuf::from("/work/jj.toml"), PathBuf::from("/conf.d")]
+        );
+        assert!(user_config_paths(Some(OsStr::new(""))).is_empty());
+    }
+
+    #[test]
+    fn user_config_below_repo_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        let conf_d = tmp.path().join("conf.d");
+        fs::create_dir(&conf_d).unwrap();
+        fs::write(
+            conf_d.join("aliases.toml"),
+            "[revset-aliases]\n'trunk()' = 'main@work'\n'mine()' = 'author(me)'\n",
+        )
+        .unwrap();
+        fs::write(
+            tmp.path().join(".jj/repo/config.toml"),
+            "[revset-aliases]\n'trunk()' = 'dev@origin'\n",
+        )
+        .unwrap();
+        let settings =
+            settings_with(tmp.path(), &[conf_d, tmp.path().join("missing.toml")]).unwrap();
+        let alias = |name: &str| settings.get_string(["revset-aliases", name]).unwrap();
+        assert_eq!(alias("trunk()"), "dev@origin");
+        assert_eq!(alias("mine()"), "author(me)");
+    }
+
+    #[test]
+    fn new_file_limit_reads_human_sizes() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        let limit = || new_file_limit(&settings_with(tmp.path(), &[]).unwrap());
+        assert_eq!(limit(), DEFAULT_MAX_NEW_FILE_SIZE);
+        let config = tmp.path().join(".jj/repo/config.toml");
+        fs::write(&config, "snapshot.max-new-file-size = \"10KiB\"\n").unwrap();
+        assert_eq!(limit(), 10 * 1024);
+        fs::write(&config, "snapshot.max-new-file-size = 512\n").unwrap();
+        assert_eq!(limit(), 512);
+        fs::write(&config, "snapshot.max-new-file-size = \"lots\"\n").unwrap();
+        assert_eq!(limit(), DEFAULT_MAX_NEW_FILE_SIZE);
+    }
+
+    #[test]
+    fn parsed_settings_follow_config_edits() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        let config = tmp.path().join(".jj/repo/config.toml");
+        fs::write(&config, "snapshot.max-new-file-size = \"10KiB\"\n").unwrap();
+        assert_eq!(max_new_file_size(tmp.path()), Some(10 * 1024));
+        assert_eq!(max_new_file_size(tmp.path()), Some(10 * 1024));
+        fs::write(&config, "snapshot.max-new-file-size = 0\n").unwrap();
+        assert_eq!(max_new_file_size(tmp.path()), None);
+    }
+
+    #[test]
+    fn change_id_colors_cascade() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        fs::write(
+            tmp.path().join(".jj/repo/config.toml"),
+            r##"[colors]
+change_id = { fg = "blue", bold = true }
+rest = "bright black"
+"change_id rest" = { fg = "ansi-color-245", bold = false }
+"change_id prefix" = "#ff8800"
+"##,
+        )
+        .unwrap();
+        assert_eq!(
+            id_styles(&settings_with(tmp.path(), &[]).unwrap()),
+            [
+                Some(Style {
+                    fg: Some(TermColor::Rgb(0xff, 0x88, 0x00)),
+                    bold: true,
+                    ..Style::default()
+                }),
+                Some(Style {
+                    fg: Some(TermColor::Indexed(245)),
+                    ..Style::default()
+                }),
+            ]
+        );
+        assert_eq!(jj_color("bright magenta"), Some(TermColor::Indexed(13)));
+        assert_eq!(jj_color("default"), None);
+        assert_eq!(jj_color("7"), None);
+    }
+
+    #[test]
+    fn newer_store_type_is_a_format_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        Workspace::init_simple(&settings_with(tmp.path(), &[]).unwrap(), tmp.path()).unwrap();
+        fs::write(
+            tmp.path().join(".jj/repo/op_store/type"),
+            "simple_op_store_v2",
+        )
+        .unwrap();
+        let err = collect_all(tmp.path(), true).unwrap_err();
+        assert!(matches!(err, Error::JjFormat(_)), "{err}");
+    }
+}
@@ -0,0 +1,748 @@
+//! Versioned JSON model for `--output json` and `--output nuon`
+//!
+//! Compatibility: within a `schema_version`, fields are only ever added.
+//! Removing, renaming or retyping a field bumps `SCHEMA_VERSION`. The schema
+//! itself is printed by `jj-starship schema`.
+//!
+//! With the `serde` feature, each backend's info struct serializes to exactly
+//! the object written here under its key (checked by the tests below). The
+//! writer itself stays hand-rolled so default builds don't pull in serde.
+//!
+//! NUON (nushell object notation) is the same model with bare record keys, so
+//! `from nuon` yields a record whose fields nushell prompts can use directly.
+
+use crate::detect::DetectResult;
+#[cfg(feature = "git")]
+use crate::git::GitInfo;
+use crate::hg::HgInfo;
+#[cfg(feature = "jj")]
+use crate::jj::JjInfo;
+use crate::output::RepoInfo;
+use crate::pijul::PijulInfo;
+use crate::sapling::SaplingInfo;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Bumped on any breaking change to the JSON model
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// JSON Schema (draft 2020-12) describing the model
+pub const SCHEMA: &str = include_str!("schema.json");
+
+/// Record key style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// `"key":value`
+    Json,
+    /// `key: value` (every key is a valid bare identifier)
+    Nuon,
+}
+
+/// Render detection and collected info as one line of JSON or NUON
+///
+/// `jj`/`git`/`hg`/`sapling`/`pijul` are null when the backend doesn't apply or collection failed;
+/// every key is always present.
+#[must_use = "returns formatted string, does not print"]
+pub fn render(result: &DetectResult, info: Option<&RepoInfo>, syntax: Syntax) -> String {
+    let mut out = String::with_capacity(512);
+    let mut obj = Object::new(&mut out, syntax);
+    obj.key("schema_version");
+    let _ = write!(obj.out, "{SCHEMA_VERSION}");
+    obj.key("repo_type");
+    match result.repo_type.name() {
+        Some(name) => string(obj.out, name, syntax),
+        None => obj.out.push_str("null"),
+    }
+    obj.key("repo_root");
+    match &result.repo_root {
+        Some(root) => string(obj.out, &root.to_string_lossy(), syntax),
+        None => obj.out.push_str("null"),
+    }
+    obj.key("store_path");
+    match &result.store_path {
+        Some(path) => string(obj.out, &path.to_string_lossy(), syntax),
+        None => obj.out.push_str("null"),
+    }
+
+    obj.key("jj");
+    match info {
+        #[cfg(feature = "jj")]
+        Some(RepoInfo::Jj(info)) => jj(obj.out, info, syntax),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        Some(RepoInfo::Colocated(info, _)) => jj(obj.out, info, syntax),
+        _ => obj.out.push_str("null"),
+    }
+    obj.key("git");
+    match info {
+        #[cfg(feature = "git")]
+        Some(RepoInfo::Git(info)) => git(obj.out, info, syntax),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        Some(RepoInfo::Colocated(_, info)) => git(obj.out, info, syntax),
+        _ => obj.out.push_str("null"),
+    }
+    obj.key("hg");
+    match info {
+        Some(RepoInfo::Hg(info)) => hg(obj.out, info, syntax),
+        _ => obj.out.push_str("null"),
+    }
+    obj.key("sapling");
+    match info {
+        Some(RepoInfo::Sapling(info)) => sapling(obj.out, info, syntax),
+        _ => obj.out.push_str("null"),
+    }
+    obj.key("pijul");
+    match info {
+        Some(RepoInfo::Pijul(info)) => pijul(obj.out, info, syntax),
+        _ => obj.out.push_str("null"),
+    }
+    obj.finish();
+    out.push('\n');
+    out
+}
+
+#[cfg(feature = "jj")]
+fn jj(out: &mut String, info: &JjInfo, syntax: Syntax) {
+    let mut obj = Object::new(out, syntax);
+    obj.str("change_id", &info.change_id);
+    obj.num("change_id_prefix_len", info.change_id_prefix_len);
+    obj.key("bookmarks");
+    obj.out.push('[');
+    for (i, (name, distance)) in info.bookmarks.iter().enumerate() {
+        if i > 0 {
+            obj.out.push_str(match syntax {
+                Syntax::Json => ",",
+                Syntax::Nuon => ", ",
+            });
+        }
+        let mut bookmark = Object::new(obj.out, syntax);
+        bookmark.str("name", name);
+        bookmark.num("distance", *distance);
+        bookmark.finish();
+    }
+    obj.out.push(']');
+    obj.bool("empty_desc", info.empty_desc);
+    obj.bool("conflict", info.conflict);
+    obj.num("conflicted_commits", info.conflicted_commits);
+    obj.bool("divergent", info.divergent);
+    obj.bool("divergence_timed_out", info.divergence_timed_out);
+    obj.bool("has_remote", info.has_remote);
+    obj.bool("is_synced", info.is_synced);
+    obj.bool("op_in_progress", info.op_in_progress);
+    obj.str("backend", &info.backend);
+    obj.finish();
+}
+
+#[cfg(feature = "git")]
+fn git(out: &mut String, info: &GitInfo, syntax: Syntax) {
+    let mut obj = Object::new(out, syntax);
+    obj.key("branch");
+    match &info.branch {
+        Some(branch) => string(obj.out, branch, syntax),
+        None => obj.out.push_str("null"),
+    }
+    obj.str("head", &info.head_short);
+    obj.num("staged", info.staged);
+    obj.num("modified", info.modified);
+    obj.num("untracked", info.untracked);
+    obj.num("oversized", info.oversized);
+    obj.num("deleted", info.deleted);
+    obj.num("conflicted", info.conflicted);
+    obj.bool("status_timed_out", info.status_timed_out);
+    obj.num("ahead", info.ahead);
+    obj.num("behind", info.behind);
+    obj.bool("op_in_progress", info.op_in_progress);
+    obj.bool("lfs", info.lfs);
+    obj.bool("lfs_missing", info.lfs_missing);
+    obj.finish();
+}
+
+fn hg(out: &mut String, info: &HgInfo, syntax: Syntax) {
+    let mut obj = Object::new(out, syntax);
+    obj.str("branch", &info.branch);
+    obj.key("bookmark");
+    match &info.bookmark {
+        Some(bookmark) => string(obj.out, bookmark, syntax),
+        None => obj.out.push_str("null"),
+    }
+    obj.str("node", &info.node);
+    obj.finish();
+}
+
+fn sapling(out: &mut String, info: &SaplingInfo, syntax: Syntax) {
+    let mut obj = Object::new(out, syntax);
+    obj.key("bookmark");
+    match &info.bookmark {
+        Some(bookmark) => string(obj.out, bookmark, syntax),
+        None => obj.out.push_str("null"),
+    }
+    obj.str("node", &info.node);
+    obj.finish();
+}
+
+fn pijul(out: &mut String, info: &PijulInfo, syntax: Syntax) {
+    let mut obj = Object::new(out, syntax);
+    obj.str("channel", &info.channel);
+    obj.finish();
+}
+
+/// Writes `{"key":value,...}` (or `{key: value, ...}`), tracking the separators
+struct Object<'a> {
+    out: &'a mut String,
+    syntax: Syntax,
+    first: bool,
+}
+
+impl<'a> Object<'a> {
+    fn new(out: &'a mut String, syntax: Syntax) -> Self {
+        out.push('{');
+        Self {
+            out,
+            syntax,
+            first: true,
+        }
+    }
+
+    /// Write the key and separator; the caller writes the value
+    fn key(&mut self, key: &str) {
+        match (self.syntax, self.first) {
+            (_, true) => {}
+            (Syntax::Json, false) => self.out.push(','),
+            (Syntax::Nuon, false) => self.out.push_str(", "),
+        }
+        self.first = false;
+        match self.syntax {
+            Syntax::Json => {
+                string(self.out, key, Syntax::Json);
+                self.out.push(':');
+            }
+            Syntax::Nuon => {
+                self.out.push_str(key);
+                self.out.push_str(": ");
+            }
+        }
+    }
+
+    fn str(&mut self, key: &str, value: &str) {
+        self.key(key);
+        string(self.out, value, self.syntax);
+    }
+
+    fn num(&mut self, key: &str, value: usize) {
+        self.key(key);
+        let _ = write!(self.out, "{value}");
+    }
+
+    fn bool(&mut self, key: &str, value: bool) {
+        self.key(key);
+        self.out.push_str(if value { "true" } else { "false" });
+    }
+
+    fn finish(self) {
+        self.out.push('}');
+    }
+}
+
+/// Write `s` as a JSON or nushell string literal
+fn string(out: &mut String, s: &str, syntax: Syntax) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = match syntax {
+                    Syntax::Json => write!(out, "\\u{:04x}", u32::from(c)),
+                    Syntax::Nuon => write!(out, "\\u{{{:x}}}", u32::from(c)),
+                };
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// jq-style path into the model for the `query` subcommand: `.` for the
+/// whole model, `.key` to descend into an object, `[N]` (or `.N`) to index
+/// an array, as in `.jj.bookmarks[0].name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query(Vec<Step>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || format!("expected a path like .jj.bookmarks[0].name, got {s:?}");
+        if !s.starts_with(['.', '[']) {
+            return Err(malformed());
+        }
+        let mut steps = Vec::new();
+        let mut rest = s;
+        while !rest.is_empty() {
+            if let Some(index) = rest.strip_prefix('[') {
+                let (index, after) = index.split_once(']').ok_or_else(malformed)?;
+                steps.push(Step::Index(index.parse().map_err(|_| malformed())?));
+                rest = after;
+                continue;
+            }
+            let after = rest.strip_prefix('.').ok_or_else(malformed)?;
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let (word, after) = after.split_at(end);
+            match word.parse() {
+                Ok(index) => steps.push(Step::Index(index)),
+                Err(_) if word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+                    if !word.is_empty() {
+                        steps.push(Step::Key(word.to_string()));
+                    } else if !(after.is_empty() || after.starts_with('[')) {
+                        return Err(malformed());
+                    }
+                }
+                Err(_) => return Err(malformed()),
+            }
+            rest = after;
+        }
+        Ok(Self(steps))
+    }
+}
+
+impl Query {
+    /// Whether the path reaches into collected info, rather than only the
+    /// detection keys (`repo_type`, `repo_root`, ...)
+    #[must_use]
+    pub fn needs_info(&self) -> bool {
+        !matches!(
+            self.0.first(),
+            Some(Step::Key(key))
+                if matches!(key.as_str(), "schema_version" | "repo_type" | "repo_root" | "store_path")
+        )
+    }
+
+    /// Value at the path in a model written by [`render`] with
+    /// [`Syntax::Json`], raw: strings unquoted and unescaped, numbers and
+    /// booleans as written, objects and arrays as JSON; None for null or a
+    /// key or index that isn't there
+    #[must_use]
+    pub fn select(&self, model: &str) -> Option<String> {
+        let mut value = model.trim();
+        for step in &self.0 {
+            value = match step {
+                Step::Key(key) => {
+                    members(value, '{')?
+                        .find(|(name, _)| name.is_some_and(|name| unescape(name) == *key))?
+                        .1
+                }
+                Step::Index(index) => members(value, '[')?.nth(*index)?.1,
+            };
+        }
+        match value {
+            "null" => None,
+            string if string.starts_with('"') => Some(unescape(string)),
+            value => Some(value.to_string()),
+        }
+    }
+}
+
+/// Members of the object (`{`) or array (`[`) in `value`: the key literal
+/// (None in arrays) and the value text of each; None when `value` is not
+/// that kind of container
+fn members(value: &str, open: char) -> Option<impl Iterator<Item = (Option<&str>, &str)>> {
+    let mut rest = value.strip_prefix(open)?.trim_start();
+    Some(std::iter::from_fn(move || {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() || rest.starts_with(['}', ']']) {
+            return None;
+        }
+        let mut key = None;
+        if open == '{' {
+            let end = token_len(rest)?;
+            key = Some(&rest[..end]);
+            rest = rest[end..].trim_start().strip_prefix(':')?.trim_start();
+        }
+        let end = token_len(rest)?;
+        let (value, after) = rest.split_at(end);
+        rest = after;
+        Some((key, value))
+    }))
+}
+
+/// Length of the JSON value at the start of `s`
+fn token_len(s: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' if depth == 0 => return Some(i + 1),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' | ',' if depth == 0 => return Some(i),
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(s.trim_end().len()).filter(|&len| len > 0 && depth == 0 && !in_string)
+}
+
+/// Contents of a JSON string literal, with escapes resolved
+fn unescape(literal: &str) -> String {
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(literal);
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                out.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+            }
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::RepoType;
+    use std::path::PathBuf;
+
+    fn not_in_repo() -> DetectResult {
+        DetectResult {
+            repo_type: RepoType::None,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn outside_repo_has_every_key() {
+        assert_eq!(
+            render(&not_in_repo(), None, Syntax::Json),
+            "{\"schema_version\":1,\"repo_type\":null,\"repo_root\":null,\"store_path\":null,\"jj\":null,\"git\":null,\"hg\":null,\"sapling\":null,\"pijul\":null}\n"
+        );
+    }
+
+    #[test]
+    fn nuon_uses_bare_keys() {
+        assert_eq!(
+            render(&not_in_repo(), None, Syntax::Nuon),
+            "{schema_version: 1, repo_type: null, repo_root: null, store_path: null, jj: null, git: null, hg: null, sapling: null, pijul: null}\n"
+        );
+    }
+
+    #[test]
+    fn escapes_strings() {
+        let mut out = String::new();
+        string(&mut out, "a\"b\\c\nd\u{1b}", Syntax::Json);
+        assert_eq!(out, r#""a\"b\\c\nd\u001b""#);
+
+        let mut out = String::new();
+        string(&mut out, "d\u{1b}", Syntax::Nuon);
+        assert_eq!(out, r#""d\u{1b}""#);
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn jj_model() {
+        let result = DetectResult {
+            repo_type: RepoType::JjColocated,
+            repo_root: Some(PathBuf::from("/src/repo")),
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let info = RepoInfo::Jj(JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 2,
+            bookmarks: vec![("main".into(), 0), ("feat".into(), 3)],
+            empty_desc: false,
+            conflict: true,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        });
+        assert_eq!(
+            render(&result, Some(&info), Syntax::Json),
+            concat!(
+                "{\"schema_version\":1,\"repo_type\":\"jj-colocated\",\"repo_root\":\"/src/repo\",\"store_path\":null,",
+                "\"jj\":{\"change_id\":\"yzxv1234\",\"change_id_prefix_len\":2,",
+                "\"bookmarks\":[{\"name\":\"main\",\"distance\":0},{\"name\":\"feat\",\"distance\":3}],",
+                "\"empty_desc\":false,\"conflict\":true,\"conflicted_commits\":0,\"divergent\":false,",
+                "\"divergence_timed_out\":false,\"has_remote\":true,\"is_synced\":true,\"op_in_progress\":false,\"backend\":\"git\"},",
+                "\"git\":null,\"hg\":null,\"sapling\":null,\"pijul\":null}\n"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn git_model() {
+        let result = DetectResult {
+            repo_type: RepoType::Git,
+            repo_root: Some(PathBuf::from("/src/repo")),
+            store_path: Some(PathBuf::from("/src/repo/.git")),
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let info = RepoInfo::Git(GitInfo {
+            branch: None,
+            head_short: "abc1234".into(),
+            staged: 1,
+            modified: 2,
+            untracked: 3,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 4,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        });
+        assert_eq!(
+            render(&result, Some(&info), Syntax::Json),
+            concat!(
+                "{\"schema_version\":1,\"repo_type\":\"git\",\"repo_root\":\"/src/repo\",",
+                "\"store_path\":\"/src/repo/.git\",\"jj\":null,",
+                "\"git\":{\"branch\":null,\"head\":\"abc1234\",\"staged\":1,\"modified\":2,",
+                "\"untracked\":3,\"oversized\":0,\"deleted\":0,\"conflicted\":0,\"status_timed_out\":false,",
+                "\"ahead\":4,\"behind\":0,\"op_in_progress\":false,\"lfs\":false,\"lfs_missing\":false},\"hg\":null,\"sapling\":null,\"pijul\":null}\n"
+            )
+        );
+    }
+
+    #[cfg(all(feature = "serde", feature = "jj"))]
+    #[test]
+    fn serde_matches_jj_model() {
+        let info = || JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 2,
+            bookmarks: vec![("main".into(), 0), ("feat".into(), 3)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: false,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let result = DetectResult {
+            repo_type: RepoType::Jj,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let model: serde_json::Value =
+            serde_json::from_str(&render(&result, Some(&RepoInfo::Jj(info())), Syntax::Json))
+                .unwrap();
+        let derived = serde_json::to_value(info()).unwrap();
+        assert_eq!(model["jj"], derived);
+        assert_eq!(serde_json::from_value::<JjInfo>(derived).unwrap(), info());
+    }
+
+    #[cfg(all(feature = "serde", feature = "git"))]
+    #[test]
+    fn serde_matches_git_model() {
+        let info = || GitInfo {
+            branch: Some("main".into()),
+            head_short: "abc1234".into(),
+            staged: 1,
+            modified: 0,
+            untracked: 2,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 3,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        let result = DetectResult {
+            repo_type: RepoType::Git,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let model: serde_json::Value =
+            serde_json::from_str(&render(&result, Some(&RepoInfo::Git(info())), Syntax::Json))
+                .unwrap();
+        assert_eq!(model["git"], serde_json::to_value(info()).unwrap());
+    }
+
+    #[test]
+    fn sapling_model() {
+        let result = DetectResult {
+            repo_type: RepoType::Sapling,
+            repo_root: Some(PathBuf::from("/src/repo")),
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let info = RepoInfo::Sapling(SaplingInfo {
+            bookmark: Some("main".into()),
+            node: "0123abcd".into(),
+        });
+        assert_eq!(
+            render(&result, Some(&info), Syntax::Nuon),
+            concat!(
+                "{schema_version: 1, repo_type: \"sapling\", repo_root: \"/src/repo\", store_path: null, jj: null, ",
+                "git: null, hg: null, sapling: {bookmark: \"main\", node: \"0123abcd\"}, pijul: null}\n"
+            )
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_matches_hg_model() {
+        let info = || HgInfo {
+            branch: "default".into(),
+            bookmark: Some("feat".into()),
+            node: "0123abcd".into(),
+        };
+        let result = DetectResult {
+            repo_type: RepoType::Hg,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let model: serde_json::Value =
+            serde_json::from_str(&render(&result, Some(&RepoInfo::Hg(info())), Syntax::Json))
+                .unwrap();
+        assert_eq!(
+            model["hg"],
+            serde_json::json!({"branch": "default", "bookmark": "feat", "node": "0123abcd"})
+        );
+        assert_eq!(model["hg"], serde_json::to_value(info()).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_matches_sapling_model() {
+        let info = || SaplingInfo {
+            bookmark: None,
+            node: "0123abcd".into(),
+        };
+        let result = DetectResult {
+            repo_type: RepoType::Sapling,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let model: serde_json::Value = serde_json::from_str(&render(
+            &result,
+            Some(&RepoInfo::Sapling(info())),
+            Syntax::Json,
+        ))
+        .unwrap();
+        assert_eq!(model["sapling"], serde_json::to_value(info()).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_matches_pijul_model() {
+        let info = || PijulInfo {
+            channel: "main".into(),
+        };
+        let result = DetectResult {
+            repo_type: RepoType::Pijul,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let model: serde_json::Value = serde_json::from_str(&render(
+            &result,
+            Some(&RepoInfo::Pijul(info())),
+            Syntax::Json,
+        ))
+        .unwrap();
+        assert_eq!(model["pijul"], serde_json::to_value(info()).unwrap());
+    }
+
+    #[test]
+    fn schema_matches_version() {
+        assert!(SCHEMA.contains(&format!("\"const\": {SCHEMA_VERSION}")));
+    }
+
+    #[test]
+    fn query_paths() {
+        let model = concat!(
+            "{\"schema_version\":1,\"repo_root\":\"/src/my \\\"repo\\\"\",\"store_path\":null,",
+            "\"jj\":{\"change_id\":\"yzxv1234\",",
+            "\"bookmarks\":[{\"name\":\"main\",\"distance\":0},{\"name\":\"fe,at]\",\"distance\":3}],",
+            "\"conflict\":true},\"git\":null}\n"
+        );
+        let select = |path: &str| path.parse::<Query>().unwrap().select(model);
+        assert_eq!(select(".jj.change_id").as_deref(), Some("yzxv1234"));
+        assert_eq!(select(".jj.bookmarks[1].name").as_deref(), Some("fe,at]"));
+        assert_eq!(select(".jj.bookmarks.0.distance").as_deref(), Some("0"));
+        assert_eq!(select(".jj.conflict").as_deref(), Some("true"));
+        assert_eq!(select(".repo_root").as_deref(), Some("/src/my \"repo\""));
+        assert_eq!(
+            select(".jj.bookmarks[0]").as_deref(),
+            Some("{\"name\":\"main\",\"distance\":0}")
+        );
+        assert_eq!(select(".").as_deref(), Some(model.trim()));
+        assert_eq!(select(".store_path"), None);
+        assert_eq!(select(".git.branch"), None);
+        assert_eq!(select(".jj.bookmarks[2]"), None);
+        assert_eq!(select(".jj.nope"), None);
+        assert_eq!(select(".jj.change_id.x"), None);
+
+        assert!(!".repo_type".parse::<Query>().unwrap().needs_info());
+        assert!(".jj".parse::<Query>().unwrap().needs_info());
+        for bad in ["jj", ".jj..id", ".jj[x]", ".jj[0", ".j-j"] {
+            assert!(bad.parse::<Query>().is_err(), "{bad}");
+        }
+    }
+}
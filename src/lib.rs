@@ -0,0 +1,312 @@
+//! jj-starship - Unified Git/JJ prompt data
+//!
+//! Library half of the `jj-starship` binary, for prompts and status bars
+//! that want the same data without spawning a process:
+//!
+//! 1. [`detect()`] finds the repo above a directory
+//! 2. [`collect()`] (or [`collect_jj`]/[`collect_git`]) gathers its info
+//! 3. [`output::render`] formats it like the CLI would
+//!
+//! Everything is driven by a [`Config`], usually built with
+//! `Config::default()` and adjusted field by field.
+
+#[cfg(not(any(feature = "jj", feature = "git")))]
+compile_error!("at least one of the `jj` or `git` features must be enabled");
+
+pub mod budget;
+pub mod cache;
+pub mod color;
+pub mod config;
+pub mod config_file;
+pub mod detect;
+pub mod error;
+pub mod forge;
+#[cfg(feature = "git")]
+pub mod git;
+pub mod hg;
+pub mod hooks;
+#[cfg(feature = "jj")]
+pub mod jj;
+pub mod json;
+pub mod netfs;
+pub mod notify;
+pub mod output;
+pub mod pijul;
+pub mod sapling;
+pub mod scale;
+#[cfg(feature = "jj")]
+mod snapshot;
+pub mod starship;
+pub mod text;
+pub mod timings;
+
+pub use config::Config;
+pub use detect::{DetectResult, RepoType, detect};
+pub use error::{Error, Result};
+#[cfg(feature = "git")]
+pub use git::GitInfo;
+pub use hg::HgInfo;
+#[cfg(feature = "jj")]
+pub use jj::JjInfo;
+pub use output::RepoInfo;
+pub use pijul::PijulInfo;
+pub use sapling::SaplingInfo;
+
+#[cfg(all(feature = "jj", feature = "git"))]
+use config::{Backend, Colocated};
+#[cfg(feature = "jj")]
+use snapshot::SnapshotKey;
+use std::path::Path;
+use timings::Timings;
+
+/// Collect info for the detected repo, logging and swallowing errors
+///
+/// None outside a repo, inside its `.jj`/`.git` dir, for repo types whose
+/// backend is compiled out, or when collection failed (the error is logged
+/// at warn).
+pub fn collect(result: &DetectResult, config: &Config, timings: &mut Timings) -> Option<RepoInfo> {
+    try_collect(result, config, timings)
+        .inspect_err(|e| tracing::warn!("{e}"))
+        .ok()
+        .flatten()
+}
+
+/// Collect info for the detected repo, returning the collection error
+///
+/// Ok(None) where [`collect`] returns None without a failure.
+///
+/// # Errors
+///
+/// Returns the backend's error if collection fails.
+#[allow(unreachable_patterns)]
+pub fn try_collect(
+    result: &DetectResult,
+    config: &Config,
+    timings: &mut Timings,
+) -> Result<Option<RepoInfo>> {
+    let Some(repo_root) = result.repo_root.as_deref() else {
+        return Ok(None);
+    };
+    // Nothing in the working copy to describe from inside `.git` or `.jj`
+    if result.dot_dir.is_some() {
+        return Ok(None);
+    }
+    let info = match result.repo_type {
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoType::JjColocated => collect_colocated(repo_root, config, timings),
+        #[cfg(feature = "jj")]
+        RepoType::Jj | RepoType::JjColocated => {
+            collect_jj(repo_root, config, timings).map(RepoInfo::Jj)
+        }
+        #[cfg(feature = "git")]
+        RepoType::Git => collect_git(repo_root, config, None, timings).map(RepoInfo::Git),
+        RepoType::Hg => timings
+            .time("hg collect", || hg::collect(repo_root, config.id_length))
+            .map(RepoInfo::Hg),
+        RepoType::Pijul => timings
+            .time("pijul collect", || pijul::collect(repo_root))
+            .map(RepoInfo::Pijul),
+        RepoType::Sapling => timings
+            .time("sapling collect", || {
+                sapling::collect(repo_root, config.id_length)
+            })
+            .map(RepoInfo::Sapling),
+        RepoType::None => return Ok(None),
+        // Catch disabled variants
+        _ => return Ok(None),
+    };
+    info.map(Some)
+}
+
+/// Collect a colocated repo with the backends chosen in `config.colocated`,
+/// both of them when view and status differ
+///
+/// When the JJ store can't be loaded (corruption, an interrupted operation,
+/// a newer store format), the Git refs are rendered instead.
+#[cfg(all(feature = "jj", feature = "git"))]
+fn collect_colocated(repo_root: &Path, config: &Config, timings: &mut Timings) -> Result<RepoInfo> {
+    let Colocated { view, status } = config.colocated;
+    // Git's status shows the files jj would refuse to snapshot
+    let new_file_limit = if status == Backend::Git {
+        jj::max_new_file_size(repo_root)
+    } else {
+        None
+    };
+    if (view, status) == (Backend::Git, Backend::Git) {
+        return collect_git(repo_root, config, new_file_limit, timings).map(RepoInfo::Git);
+    }
+    if (view, status) == (Backend::Jj, Backend::Jj) {
+        return match collect_jj(repo_root, config, timings) {
+            Ok(jj) => Ok(RepoInfo::Jj(jj)),
+            Err(e) => {
+                tracing::warn!("{e}; falling back to Git");
+                collect_git(repo_root, config, new_file_limit, timings).map(RepoInfo::Git)
+            }
+        };
+    }
+    // Both backends: the Git status walk runs on a scoped thread alongside
+    // the JJ load, and its phases are appended after the join. Git opens
+    // `.git` a second time: jj-lib's backend holds a gix handle libgit2
+    // can't use, and a snapshot hit never loads the JJ store at all
+    let (jj, git) = std::thread::scope(|s| {
+        let git = s.spawn(|| {
+            let mut git_timings = Timings::default();
+            let git = collect_git(repo_root, config, new_file_limit, &mut git_timings);
+            (git, git_timings)
+        });
+        let jj = collect_jj(repo_root, config, timings);
+        let (git, git_timings) = git.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+        timings.extend(git_timings);
+        (jj, git)
+    });
+    match jj {
+        Ok(jj) => git.map(|git| RepoInfo::Colocated(jj, git)),
+        Err(e) => {
+            tracing::warn!("{e}; falling back to Git");
+            git.map(RepoInfo::Git)
+        }
+    }
+}
+
+/// Collect JJ info, rendering from the on-disk snapshot while the op head
+/// and collection options are unchanged
+///
+/// # Errors
+///
+/// Returns an error if the workspace can't be loaded.
+#[cfg(feature = "jj")]
+pub fn collect_jj(repo_root: &Path, config: &Config, timings: &mut Timings) -> Result<JjInfo> {
+    let mode = config.collect_mode(repo_root);
+    let key = config
+        .cache_dir
+        .as_deref()
+        .filter(|_| config.use_cache)
+        .and_then(|dir| {
+            let options = (
+                config.id_length,
+                config.ancestor_bookmark_depth,
+                config.jj_display,
+                config.read_only,
+                mode,
+                &config.conflict_count,
+                jj::config_stamp(repo_root),
+            );
+            SnapshotKey::new(
+                repo_root,
+                dir,
+                config.cache_max_age,
+                snapshot::fingerprint(&options),
+            )
+        });
+    let busy = jj::operation_in_progress(repo_root, config.read_only);
+    if let Some(info) = key
+        .as_ref()
+        .and_then(|key| timings.time("jj snapshot read", || key.load()))
+    {
+        return Ok(JjInfo {
+            op_in_progress: busy,
+            ..info
+        });
+    }
+    // The op head moved: render what it was and leave collecting to a
+    // background refresh
+    if config.stale_while_revalidate
+        && let Some(info) = key
+            .as_ref()
+            .and_then(|key| timings.time("jj snapshot read", || key.load_stale()))
+    {
+        return Ok(JjInfo {
+            op_in_progress: busy,
+            stale: true,
+            ..info
+        });
+    }
+    // Serialize collection across shells, waiting briefly; whoever waited
+    // re-reads the result, and a holder that outlasts the wait is ignored.
+    // Mid-operation, don't queue behind a prompt that may be waiting on jj.
+    let lock = key
+        .as_ref()
+        .filter(|_| !busy)
+        .and_then(|key| timings.time("jj snapshot lock", || key.lock()));
+    if lock.is_some()
+        && let Some(info) = key.as_ref().and_then(SnapshotKey::load)
+    {
+        return Ok(info);
+    }
+
+    // Read-only collection takes no jj locks, so a running command can't
+    // block it; the partial result isn't cached
+    let info = jj::collect(
+        repo_root,
+        config.id_length,
+        config.ancestor_bookmark_depth,
+        config.jj_display,
+        mode,
+        config.budgets.divergence,
+        config.conflict_count.as_deref(),
+        config.read_only || busy,
+        timings,
+    )?;
+    if busy {
+        return Ok(JjInfo {
+            op_in_progress: true,
+            ..info
+        });
+    }
+    if let Some(key) = &key {
+        timings.time("jj snapshot write", || key.store(&info));
+    }
+    Ok(info)
+}
+
+/// Collect Git info with the configured id length, scaling and budgets,
+/// counting untracked files over `new_file_limit` as oversized
+///
+/// # Errors
+///
+/// Returns an error if the repo can't be opened or HEAD can't be read.
+#[cfg(feature = "git")]
+pub fn collect_git(
+    repo_root: &Path,
+    config: &Config,
+    new_file_limit: Option<u64>,
+    timings: &mut Timings,
+) -> Result<GitInfo> {
+    git::collect(
+        repo_root,
+        config.boundaries.separate_git_dir(repo_root),
+        config.id_length,
+        config.collect_mode(repo_root),
+        config.budgets.status,
+        new_file_limit,
+        timings,
+    )
+}
+
+#[cfg(all(test, feature = "jj", feature = "git"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_jj_store_falls_back_to_git() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(tmp.path()).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "init", &tree, &[])
+            .unwrap();
+        // A `.jj` without a loadable store
+        std::fs::create_dir_all(tmp.path().join(".jj/repo")).unwrap();
+
+        let result = detect(tmp.path(), &detect::Boundaries::default());
+        assert_eq!(result.repo_type, RepoType::JjColocated);
+        let config = Config {
+            use_cache: false,
+            ..Config::default()
+        };
+        let info = try_collect(&result, &config, &mut Timings::default()).unwrap();
+        assert!(matches!(info, Some(RepoInfo::Git(_))));
+    }
+}
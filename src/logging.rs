@@ -0,0 +1,41 @@
+//! Opt-in diagnostics via `tracing`
+//!
+//! Prompt failures are silent by design, so logs are how a missing segment
+//! gets explained. Nothing is installed unless a level or log file is set.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Level used when only `JJ_STARSHIP_LOG_FILE` is set
+const DEFAULT_FILE_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// Install the global subscriber
+///
+/// Level: CLI > `JJ_STARSHIP_LOG_LEVEL` > warn if a log file is set > off.
+/// Logs append to `JJ_STARSHIP_LOG_FILE` if set, stderr otherwise. Span close
+/// events carry per-phase durations.
+pub fn init(level: Option<LevelFilter>) {
+    let file = env::var_os("JJ_STARSHIP_LOG_FILE");
+    let level = level
+        .or_else(|| env::var("JJ_STARSHIP_LOG_LEVEL").ok()?.parse().ok())
+        .or_else(|| file.as_ref().map(|_| DEFAULT_FILE_LEVEL));
+    let Some(level) = level.filter(|level| *level != LevelFilter::OFF) else {
+        return;
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false);
+    // A log file that can't be opened falls back to stderr rather than
+    // silently dropping the logs that were asked for
+    let file = file.and_then(|path| OpenOptions::new().create(true).append(true).open(path).ok());
+    let _ = match file {
+        Some(file) => builder.with_writer(Mutex::new(file)).try_init(),
+        None => builder.with_writer(io::stderr).try_init(),
+    };
+}
@@ -1,22 +1,47 @@
 //! jj-starship - Unified Git/JJ Starship prompt module
 
-mod color;
-mod config;
-mod detect;
-mod error;
-#[cfg(feature = "git")]
-mod git;
-mod jj;
-mod output;
+mod bench;
+mod dump;
+mod explain;
+mod logging;
+mod migrate;
+#[cfg(feature = "watch")]
+mod watch;
 
-#[cfg(feature = "git")]
+#[cfg(any(feature = "jj", feature = "git"))]
 use clap::Args;
-use clap::{Parser, Subcommand};
-use config::{Config, DisplayFlags};
-use detect::RepoType;
+use clap::{CommandFactory, Parser, Subcommand};
+use jj_starship::color::{Escapes, State, StyleOverride, TermColor, Theme};
+use jj_starship::config::{
+    Backend, BoundaryFlags, BudgetFlags, ColocatedFlags, Config, DisplayFlags, ElementToggle,
+    Flags, LayoutFlags, NameFlags, OutputFlags, PowerlineFlags, PrefixFlags, ScanFlags, ThemeFlags,
+    VisibilityFlags,
+};
+use jj_starship::detect::{Nesting, ResolveSymlinks};
+use jj_starship::error::Error;
+use jj_starship::hooks;
+use jj_starship::json::Query;
+use jj_starship::netfs::NetworkFs;
+use jj_starship::notify;
+use jj_starship::output::{
+    self, Affix, CountThreshold, DisplayRule, Field, OutputFormat, PrefixWord, Segment,
+    SymbolOverride, SymbolSet,
+};
+use jj_starship::timings::Timings;
+use jj_starship::{RepoInfo, cache, config_file, detect, json, starship, try_collect};
+use std::borrow::Cow;
 use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::mem;
 use std::path::{Path, PathBuf};
-use std::process::ExitCode;
+use std::process::{self, ExitCode, Stdio};
+use std::thread;
+#[cfg(feature = "watch")]
+use std::time::Duration;
+use std::time::Instant;
+use tracing::level_filters::LevelFilter;
 
 /// CLI args - bool fields are inherent to clap's flag-based interface
 #[derive(Parser)]
@@ -52,19 +77,262 @@ struct Cli {
     #[arg(long, global = true)]
     strip_bookmark_prefix: Option<String>,
 
-    /// Symbol prefix for JJ repos (default: "󱗆")
+    /// Count the commits with conflicts in REVSET (default: `all()`, e.g. `--conflict-count=mutable()`)
+    #[arg(
+        long,
+        global = true,
+        value_name = "REVSET",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all()"
+    )]
+    conflict_count: Option<String>,
+
+    /// Tracked files above which the git dirty scan is skipped (0 = unlimited, default: 100000)
     #[arg(long, global = true)]
-    jj_symbol: Option<String>,
+    large_repo_files: Option<usize>,
+
+    /// Packed objects above which ahead/behind and divergence are skipped (0 = unlimited, default: 2000000)
+    #[arg(long, global = true)]
+    large_repo_objects: Option<usize>,
+
+    /// Network filesystem fast path: auto, on, off (default: auto)
+    #[arg(long, global = true)]
+    network_fs: Option<NetworkFs>,
+
+    /// Prompt output format: prompt, json, nuon, omp, p10k, tmux, zellij, powerline (default: prompt)
+    #[arg(long, global = true)]
+    output: Option<OutputFormat>,
+
+    /// Wrap ANSI escapes for raw shell prompts: none, zsh, bash (default: none)
+    #[arg(long, global = true)]
+    escapes: Option<Escapes>,
+
+    /// Print only part of the segment: all, name, status (default: all)
+    #[arg(long, global = true)]
+    segment: Option<Segment>,
+
+    /// String between name, id and status (default: a space)
+    #[arg(long, global = true)]
+    separator: Option<String>,
+
+    /// String between bookmark names (default: ", ")
+    #[arg(long, global = true)]
+    bookmark_separator: Option<String>,
+
+    /// Word before the repo symbol (default: "on"; empty for none), for
+    /// every repo type or one with `git:WORD` (repeatable)
+    #[arg(long, global = true, value_name = "[REPO:]WORD")]
+    prefix_word: Vec<PrefixWord>,
+
+    /// Text around the name, id or status in place of its brackets, with
+    /// `{}` for the element, e.g. `--affix "id=⟨{}⟩"` or `--affix
+    /// "git:status=| {}"` (repeatable)
+    #[arg(long, global = true, value_name = "[REPO:]ELEMENT=PREFIX{}SUFFIX")]
+    affix: Vec<Affix>,
+
+    /// Glyph set for prefixes and status: nerd, emoji, ascii, text (default: nerd)
+    #[arg(long, global = true, value_name = "SET")]
+    symbol_set: Option<SymbolSet>,
+
+    /// Color preset: default, colorblind, high-contrast (default: default)
+    #[arg(long, global = true)]
+    theme: Option<Theme>,
+
+    /// Override one status glyph, e.g. `--symbol modified=*` (repeatable)
+    #[arg(long, global = true, value_name = "NAME=GLYPH")]
+    symbol: Vec<SymbolOverride>,
+
+    /// Style one part of the segment with a starship style string, e.g.
+    /// `--style "name=bold fg:#ff8800"` (symbol, name, id, status, id-prefix
+    /// or id-rest; repeatable)
+    #[arg(long, global = true, value_name = "PART=STYLE")]
+    style: Vec<StyleOverride>,
+
+    /// Style the repo symbol and status by repo state, e.g.
+    /// `--state-style conflict=red` (conflict, divergent, unsynced, dirty or
+    /// clean; repeatable)
+    #[arg(long, global = true, value_name = "STATE=STYLE")]
+    state_style: Vec<StyleOverride<State>>,
+
+    /// Powerline segment background: 0-255, #rrggbb or a color name (default: 236)
+    #[arg(long, global = true, value_name = "COLOR")]
+    powerline_bg: Option<TermColor>,
+
+    /// Background of the powerline segment to the left (draws the entry arrow)
+    #[arg(long, global = true, value_name = "COLOR")]
+    powerline_prev_bg: Option<TermColor>,
+
+    /// Background of the powerline segment to the right (default: terminal)
+    #[arg(long, global = true, value_name = "COLOR")]
+    powerline_next_bg: Option<TermColor>,
+
+    /// Backend shown for colocated JJ+Git repos: jj, git (default: jj)
+    #[arg(long, global = true, value_name = "BACKEND")]
+    colocated_view: Option<Backend>,
+
+    /// Backend whose status flags are shown in colocated repos (default: the view's);
+    /// `git` also marks untracked files over jj's `snapshot.max-new-file-size`
+    #[arg(long, global = true, value_name = "BACKEND")]
+    colocated_status: Option<Backend>,
+
+    /// Don't search for a repo above $HOME
+    #[arg(long, global = true)]
+    stop_at_home: bool,
+
+    /// Don't search for a repo across filesystem/mount boundaries
+    #[arg(long, global = true)]
+    one_filesystem: bool,
+
+    /// Max parent directories to search for a repo (0 = unlimited)
+    #[arg(long, global = true)]
+    max_search_depth: Option<usize>,
+
+    /// Path globs where no repo is searched for (comma-separated, e.g. "/mnt/nas/**,~/archive")
+    #[arg(long, global = true, value_name = "GLOBS")]
+    ignore_paths: Option<String>,
+
+    /// Git dirs kept apart from their work tree, e.g. a bare dotfile repo (comma-separated, e.g. "~/.cfg")
+    #[arg(long, global = true, value_name = "DIRS")]
+    git_dirs: Option<String>,
+
+    /// Repo to report when repos are nested: innermost, outermost or prefer-jj (default: innermost)
+    #[arg(long, global = true, value_name = "POLICY")]
+    nesting: Option<Nesting>,
+
+    /// Resolve symlinks in the start directory: auto (as given), on (canonicalize) or off ($PWD) (default: auto)
+    #[arg(long, global = true, value_name = "MODE")]
+    resolve_symlinks: Option<ResolveSymlinks>,
+
+    /// Time budget for the JJ divergence check in ms (0 = unbounded)
+    #[arg(long, global = true)]
+    divergence_timeout_ms: Option<u64>,
+
+    /// Time budget for the Git status scan in ms (0 = unbounded)
+    #[arg(long, global = true)]
+    status_timeout_ms: Option<u64>,
+
+    /// Shown in place of a status element whose check ran out of time (default: "⧗")
+    #[arg(long, global = true)]
+    timeout_placeholder: Option<String>,
+
+    /// Never write or lock repo state (disables JJ prefix coloring and divergence)
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Always collect JJ repos instead of rendering the cached snapshot
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// After a JJ operation, render the previous snapshot and refresh it in
+    /// the background
+    #[arg(long, global = true)]
+    stale_while_revalidate: bool,
+
+    /// Compact rendering for right prompts: symbol, shortest id, one status glyph
+    #[arg(long, global = true)]
+    compact: bool,
+
+    /// Words instead of glyphs, for screen readers and logs:
+    /// "jj repo, on bookmark main, change yzxv1234, conflicts"
+    #[arg(long, global = true)]
+    verbose_words: bool,
+
+    /// Link the branch, bookmarks and commit id to their pages on the
+    /// remote's forge (OSC 8 hyperlinks)
+    #[arg(long, global = true)]
+    hyperlinks: bool,
+
+    /// Desktop notification when the repo enters a conflict or divergent
+    /// state, once per transition
+    #[arg(long, global = true)]
+    notify: bool,
+
+    /// Take colors from starship.toml: the active palette and the `[custom.jj]` style
+    #[arg(long, global = true)]
+    starship_theme: bool,
+
+    /// Symbol prefix for Mercurial repos (default: "")
+    #[arg(long, global = true)]
+    hg_symbol: Option<String>,
+
+    /// Symbol prefix for Sapling repos (default: "")
+    #[arg(long, global = true)]
+    sl_symbol: Option<String>,
+
+    /// Symbol prefix for Pijul repos (default: "")
+    #[arg(long, global = true)]
+    pijul_symbol: Option<String>,
+
+    /// Elements to hide: prefix, name, id, status, color, prefix-color, each
+    /// optionally scoped to jj, git, hg, sl or pijul (e.g. "jj:id,git:status")
+    #[arg(long, global = true, value_delimiter = ',', value_name = "ELEMENTS")]
+    hide: Vec<ElementToggle>,
+
+    /// Elements to show even when hidden by `--hide`, a `--no-*` flag, the
+    /// environment or the config file (same syntax as `--hide`)
+    #[arg(long, global = true, value_delimiter = ',', value_name = "ELEMENTS")]
+    show: Vec<ElementToggle>,
+
+    /// Show elements only in some states: `ELEMENT=CONDITION` with the
+    /// `--hide` element syntax and dirty, clean, conflict, unsynced, bookmark
+    /// or no-bookmark (e.g. "id=no-bookmark,status=conflict")
+    #[arg(long, global = true, value_delimiter = ',', value_name = "RULES")]
+    show_if: Vec<DisplayRule>,
+
+    /// Print nothing while the repo is clean, synced and on its default
+    /// branch (main, master, trunk or default)
+    #[arg(long, global = true)]
+    only_when_dirty: bool,
+
+    /// Show a Git indicator only when its count is above N, e.g.
+    /// `--count-threshold behind=2` (conflicted, staged, modified, untracked,
+    /// deleted, ahead or behind)
+    #[arg(long, global = true, value_delimiter = ',', value_name = "NAME=N")]
+    count_threshold: Vec<CountThreshold>,
 
     /// Disable symbol prefix entirely
     #[arg(long, global = true)]
     no_symbol: bool,
 
-    /// Disable output styling
-    #[arg(long, global = true)]
+    /// Disable output styling (same layout, no escape codes)
+    #[arg(long, global = true, visible_alias = "no-ansi")]
     no_color: bool,
 
-    // JJ display flags
+    /// Print per-phase timings to stderr
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Report collection errors on stderr with an error code and exit 2
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Write a starship style string for the repo state (e.g. `bold red`
+    /// when conflicted) to this file descriptor (unix)
+    #[arg(long, global = true, value_name = "FD")]
+    style_fd: Option<u32>,
+
+    /// Log level: off, error, warn, info, debug, trace (default: off)
+    #[arg(long, global = true)]
+    log_level: Option<LevelFilter>,
+
+    #[cfg(feature = "jj")]
+    #[command(flatten)]
+    jj: JjArgs,
+
+    #[cfg(feature = "git")]
+    #[command(flatten)]
+    git: GitArgs,
+}
+
+/// JJ-specific CLI flags - bools map directly to clap's --no-* pattern
+#[cfg(feature = "jj")]
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+struct JjArgs {
+    /// Symbol prefix for JJ repos (default: "󱗆")
+    #[arg(long, global = true)]
+    jj_symbol: Option<String>,
     /// Hide "on {symbol}" prefix for JJ repos
     #[arg(long, global = true)]
     no_jj_prefix: bool,
@@ -80,10 +348,6 @@ struct Cli {
     /// Disable unique prefix coloring for `change_id`
     #[arg(long, global = true)]
     no_prefix_color: bool,
-
-    #[cfg(feature = "git")]
-    #[command(flatten)]
-    git: GitArgs,
 }
 
 /// Git-specific CLI flags - bools map directly to clap's --no-* pattern
@@ -108,6 +372,136 @@ struct GitArgs {
     no_git_status: bool,
 }
 
+#[cfg(feature = "jj")]
+impl JjArgs {
+    /// Split into the symbol override and display flags for `Config::new`
+    fn take_parts(&mut self, no_color: bool) -> (Option<String>, DisplayFlags) {
+        let flags = DisplayFlags {
+            no_prefix: self.no_jj_prefix,
+            no_name: self.no_jj_name,
+            no_id: self.no_jj_id,
+            no_status: self.no_jj_status,
+            no_color,
+            no_prefix_color: self.no_prefix_color,
+        };
+        (self.jj_symbol.take(), flags)
+    }
+}
+
+#[cfg(feature = "git")]
+impl GitArgs {
+    /// Split into the symbol override and display flags for `Config::new`
+    fn take_parts(&mut self, no_color: bool) -> (Option<String>, DisplayFlags) {
+        let flags = DisplayFlags {
+            no_prefix: self.no_git_prefix,
+            no_name: self.no_git_name,
+            no_id: self.no_git_id,
+            no_status: self.no_git_status,
+            no_color,
+            no_prefix_color: false, // N/A for git
+        };
+        (self.git_symbol.take(), flags)
+    }
+}
+
+impl Cli {
+    /// Config from the options, layered over env vars and the config file
+    fn config(&mut self) -> Config {
+        #[cfg(feature = "jj")]
+        let (jj_symbol, jj_flags) = self.jj.take_parts(self.no_color);
+        #[cfg(not(feature = "jj"))]
+        let (jj_symbol, jj_flags): (Option<String>, DisplayFlags) = (None, DisplayFlags::default());
+
+        #[cfg(feature = "git")]
+        let (git_symbol, git_flags) = self.git.take_parts(self.no_color);
+        #[cfg(not(feature = "git"))]
+        let (git_symbol, git_flags): (Option<String>, DisplayFlags) =
+            (None, DisplayFlags::default());
+
+        Config::new(Flags {
+            names: NameFlags {
+                truncate_name: self.truncate_name,
+                id_length: self.id_length,
+                ancestor_bookmark_depth: self.ancestor_bookmark_depth,
+                bookmarks_display_limit: self.bookmarks_display_limit,
+                strip_bookmark_prefix: self.strip_bookmark_prefix.take(),
+                conflict_count: self.conflict_count.take(),
+            },
+            scan: ScanFlags {
+                large_repo_files: self.large_repo_files,
+                large_repo_objects: self.large_repo_objects,
+                network_fs: self.network_fs,
+                read_only: self.read_only,
+                no_cache: self.no_cache,
+                stale_while_revalidate: self.stale_while_revalidate,
+            },
+            output: OutputFlags {
+                output: self.output,
+                escapes: self.escapes,
+                segment: self.segment,
+                compact: self.compact,
+                verbose_words: self.verbose_words,
+                hyperlinks: self.hyperlinks,
+                notify: self.notify,
+            },
+            theme: ThemeFlags {
+                symbol_set: self.symbol_set,
+                theme: self.theme,
+                starship_theme: self.starship_theme,
+                symbols: mem::take(&mut self.symbol),
+                styles: mem::take(&mut self.style),
+                state_styles: mem::take(&mut self.state_style),
+            },
+            prefixes: PrefixFlags {
+                jj_symbol,
+                git_symbol,
+                hg_symbol: self.hg_symbol.take(),
+                sl_symbol: self.sl_symbol.take(),
+                pijul_symbol: self.pijul_symbol.take(),
+                no_symbol: self.no_symbol,
+            },
+            boundaries: BoundaryFlags {
+                stop_at_home: self.stop_at_home,
+                one_filesystem: self.one_filesystem,
+                max_search_depth: self.max_search_depth,
+                ignore_paths: self.ignore_paths.take(),
+                git_dirs: self.git_dirs.take(),
+                nesting: self.nesting,
+                resolve_symlinks: self.resolve_symlinks,
+            },
+            budgets: BudgetFlags {
+                divergence_timeout_ms: self.divergence_timeout_ms,
+                status_timeout_ms: self.status_timeout_ms,
+                timeout_placeholder: self.timeout_placeholder.take(),
+            },
+            layout: LayoutFlags {
+                separator: self.separator.take(),
+                bookmark_separator: self.bookmark_separator.take(),
+                prefix_word: mem::take(&mut self.prefix_word),
+                affixes: mem::take(&mut self.affix),
+            },
+            powerline: PowerlineFlags {
+                bg: self.powerline_bg,
+                prev_bg: self.powerline_prev_bg,
+                next_bg: self.powerline_next_bg,
+            },
+            colocated: ColocatedFlags {
+                view: self.colocated_view,
+                status: self.colocated_status,
+            },
+            visibility: VisibilityFlags {
+                hide: mem::take(&mut self.hide),
+                show: mem::take(&mut self.show),
+                show_if: mem::take(&mut self.show_if),
+                only_when_dirty: self.only_when_dirty,
+                count_thresholds: mem::take(&mut self.count_threshold),
+            },
+            jj: jj_flags,
+            git: git_flags,
+        })
+    }
+}
+
 #[derive(Subcommand)]
 enum Command {
     /// Output prompt string (default)
@@ -116,119 +510,622 @@ enum Command {
     Detect,
     /// Print version and build info
     Version,
+    /// Print one value, for composing separately colored starship modules
+    /// (prints nothing and exits 1 when absent)
+    Field {
+        /// bookmark, change-id, branch, status or repo-type
+        name: Field,
+    },
+    /// Print one value of the `--output json` model by its jq-style path
+    /// (`.jj.bookmarks[0].name`); strings are printed raw (prints nothing
+    /// and exits 1 when null or absent)
+    Query {
+        /// Path into the model, `.` for all of it
+        path: Query,
+    },
+    /// Print the prompt, then what each of its symbols and values means
+    Explain,
+    /// Print the JSON Schema for `--output json`
+    Schema,
+    /// Collect repo info in a detached background process and exit immediately
+    /// (for shell chpwd/precmd hooks)
+    Warm,
+    /// Stay resident and print a new line whenever the rendered output
+    /// changes (for tmux panes and status bar widgets)
+    #[cfg(feature = "watch")]
+    Watch {
+        /// Quiet period after a change before re-rendering, in ms
+        #[arg(long, default_value_t = 100)]
+        debounce_ms: u64,
+    },
+    /// Diagnostics for bug reports
+    #[command(subcommand)]
+    Debug(DebugCommand),
+    /// Run detection and collection repeatedly and report latency per backend
+    Bench {
+        /// Number of runs per backend
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+    },
+    /// Print a config file and a simplified `[custom.jj]` stanza equivalent
+    /// to the starship.toml module
+    MigrateConfig {
+        /// starship.toml to read (default: `$STARSHIP_CONFIG` or `~/.config/starship.toml`)
+        starship_config: Option<PathBuf>,
+    },
+    /// Config file tools
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Snapshot cache tools
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Write man pages for jj-starship and each subcommand to a directory
+    Man {
+        /// Output directory, created if missing
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum DebugCommand {
+    /// Print version, platform, environment, config, detection, the raw
+    /// collected info, any error and per-phase timings as one report to
+    /// paste into an issue (the home directory is shown as `~`)
+    Dump,
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Print the cache directory
+    Path,
+    /// Print the number of snapshots, files and bytes in the cache and the
+    /// age of the oldest snapshot
+    Stats,
+    /// Remove every snapshot and `--notify` state file
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Validate the config file and print each problem as
+    /// `path:line:column: error|warning: message` (exits 1 on errors)
+    Check {
+        /// File to check (default: `$JJ_STARSHIP_CONFIG` or
+        /// `~/.config/jj-starship/config.toml`)
+        path: Option<PathBuf>,
+    },
 }
 
 fn main() -> ExitCode {
-    let cli = Cli::parse();
-    let Some(cwd) = cli.cwd.or_else(|| env::current_dir().ok()) else {
+    let start = Instant::now();
+    let mut cli = Cli::parse();
+    logging::init(cli.log_level);
+    // Older Windows consoles print escapes literally until asked not to;
+    // piped output (starship) is left alone
+    #[cfg(windows)]
+    let _ = anstyle_query::windows::enable_ansi_colors();
+    let config = cli.config();
+
+    let Some(cwd) = config.boundaries.resolve_symlinks.start_dir(cli.cwd.take()) else {
         return ExitCode::FAILURE;
     };
-    let jj_symbol = cli.jj_symbol;
-    let jj_flags = DisplayFlags {
-        no_prefix: cli.no_jj_prefix,
-        no_name: cli.no_jj_name,
-        no_id: cli.no_jj_id,
-        no_status: cli.no_jj_status,
-        no_color: cli.no_color,
-        no_prefix_color: cli.no_prefix_color,
+
+    // A background refresh re-runs its parent's command line (see `spawn_warm`)
+    let command = if env::var_os(WARM_CHILD).is_some() {
+        Command::Warm
+    } else {
+        cli.command.unwrap_or(Command::Prompt)
     };
+    match command {
+        Command::Prompt => prompt(&cwd, &config, cli.style_fd, cli.timings, cli.strict, start),
+        Command::Detect if detect::in_repo(&cwd, &config.boundaries) => ExitCode::SUCCESS,
+        Command::Detect => ExitCode::FAILURE,
+        Command::Version => {
+            println!("{}", version_text());
+            ExitCode::SUCCESS
+        }
+        Command::Field { name } => field(&cwd, &config, name, cli.strict),
+        Command::Query { path } => query(&cwd, &config, &path, cli.strict),
+        Command::Explain => explain(&cwd, &config),
+        Command::Debug(DebugCommand::Dump) => debug_dump(&cwd, &config),
+        Command::Schema => {
+            print!("{}", json::SCHEMA);
+            ExitCode::SUCCESS
+        }
+        Command::Warm => warm(&cwd, &config),
+        #[cfg(feature = "watch")]
+        Command::Watch { debounce_ms } => watch(&cwd, &config, debounce_ms),
+        Command::Bench { iterations } => bench(&cwd, &config, iterations),
+        Command::MigrateConfig { starship_config } => migrate_config(starship_config),
+        Command::Config(ConfigCommand::Check { path }) => config_check(path),
+        Command::Cache(command) => cache(&command, &config),
+        Command::Man { dir } => man(&dir),
+    }
+}
 
-    #[cfg(feature = "git")]
-    let (git_symbol, git_flags) = (
-        cli.git.git_symbol,
-        DisplayFlags {
-            no_prefix: cli.git.no_git_prefix,
-            no_name: cli.git.no_git_name,
-            no_id: cli.git.no_git_id,
-            no_status: cli.git.no_git_status,
-            no_color: cli.no_color,
-            no_prefix_color: false, // N/A for git
-        },
-    );
-    #[cfg(not(feature = "git"))]
-    let (git_symbol, git_flags): (Option<String>, DisplayFlags) = (None, DisplayFlags::default());
-
-    let config = Config::new(
-        cli.truncate_name,
-        cli.id_length,
-        cli.ancestor_bookmark_depth,
-        cli.bookmarks_display_limit,
-        cli.strip_bookmark_prefix,
-        jj_symbol,
-        git_symbol,
-        cli.no_symbol,
-        jj_flags,
-        git_flags,
-    );
-
-    match cli.command.unwrap_or(Command::Prompt) {
-        Command::Prompt => {
-            if let Some(output) = run_prompt(&cwd, &config) {
-                print!("{output}");
-                ExitCode::SUCCESS
-            } else {
-                ExitCode::FAILURE
-            }
+/// `prompt` subcommand: print the rendered output, then the optional style
+/// line and timings report
+fn prompt(
+    cwd: &Path,
+    config: &Config,
+    style_fd: Option<u32>,
+    report_timings: bool,
+    strict: bool,
+    start: Instant,
+) -> ExitCode {
+    let mut timings = Timings::default();
+    let Rendered {
+        output,
+        style,
+        error,
+    } = run_prompt(cwd, config, &mut timings);
+    if let Some(output) = &output {
+        print!("{output}");
+    }
+    if let (Some(fd), Some(style)) = (style_fd, style) {
+        write_style(fd, &style);
+    }
+    if report_timings || env::var("JJ_STARSHIP_TIMINGS").is_ok() {
+        timings.record("total", start.elapsed());
+        let _ = timings.report(&mut io::stderr());
+    }
+    if let Some(e) = error.filter(|_| is_strict(strict)) {
+        return strict_failure(&e);
+    }
+    if output.is_some() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// `field` subcommand: print one plain value
+///
+/// `repo-type` only needs detection; everything else collects as `prompt`
+/// does (through the JJ snapshot cache).
+fn field(cwd: &Path, config: &Config, name: Field, strict: bool) -> ExitCode {
+    let result = detect::detect(cwd, &config.boundaries);
+    let (info, error) = match name {
+        Field::RepoType | Field::RepoRoot | Field::StorePath => (None, None),
+        _ => collect_checked(&result, config, &mut Timings::default()),
+    };
+    if let Some(e) = error.filter(|_| is_strict(strict)) {
+        return strict_failure(&e);
+    }
+    match output::field(&result, info.as_ref(), name, config) {
+        Some(value) => {
+            print!("{value}");
+            ExitCode::SUCCESS
         }
-        Command::Detect => {
-            if detect::in_repo(&cwd) {
-                ExitCode::SUCCESS
-            } else {
-                ExitCode::FAILURE
-            }
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// `query` subcommand: print one value of the JSON model
+fn query(cwd: &Path, config: &Config, path: &Query, strict: bool) -> ExitCode {
+    let result = detect::detect(cwd, &config.boundaries);
+    let (info, error) = if path.needs_info() {
+        collect_checked(&result, config, &mut Timings::default())
+    } else {
+        (None, None)
+    };
+    if let Some(e) = error.filter(|_| is_strict(strict)) {
+        return strict_failure(&e);
+    }
+    let model = json::render(&result, info.as_ref(), json::Syntax::Json);
+    match path.select(&model) {
+        Some(value) => {
+            print!("{value}");
+            ExitCode::SUCCESS
         }
-        Command::Version => {
-            print_version();
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// `explain` subcommand: the prompt and a legend for its elements
+fn explain(cwd: &Path, config: &Config) -> ExitCode {
+    let result = detect::detect(cwd, &config.boundaries);
+    let info = match try_collect(&result, config, &mut Timings::default()) {
+        Ok(Some(info)) => info,
+        Ok(None) => {
+            eprintln!("jj-starship: not in a repo's working copy");
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("jj-starship: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let prompt = output::render(&result, Some(&info), config).unwrap_or_default();
+    let elements = explain::elements(&result, &info, config);
+    match explain::report(&prompt, &elements, &mut io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// `bench` subcommand: latency table for the current repo
+fn bench(cwd: &Path, config: &Config, iterations: usize) -> ExitCode {
+    match bench::run(cwd, config, iterations) {
+        Ok(stats) if bench::report(&stats, iterations, &mut io::stdout()).is_ok() => {
             ExitCode::SUCCESS
         }
+        Ok(_) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("jj-starship: {e}");
+            ExitCode::FAILURE
+        }
     }
 }
 
-/// Run prompt generation, returning None on error (silent fail for prompts)
-#[allow(unreachable_patterns)]
-fn run_prompt(cwd: &Path, config: &Config) -> Option<String> {
-    let result = detect::detect(cwd);
+/// `debug dump` subcommand: the redacted bug-report blob
+fn debug_dump(cwd: &Path, config: &Config) -> ExitCode {
+    match dump::run(cwd, config, &version_text(), &mut io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
 
-    match result.repo_type {
-        RepoType::Jj | RepoType::JjColocated => {
-            let repo_root = result.repo_root?;
-            let info =
-                jj::collect(&repo_root, config.id_length, config.ancestor_bookmark_depth).ok()?;
-            Some(output::format_jj(&info, config))
+/// `migrate-config` subcommand: both files' new contents, to copy by hand
+fn migrate_config(starship_config: Option<PathBuf>) -> ExitCode {
+    let Some(path) = starship_config.or_else(starship::path) else {
+        eprintln!("jj-starship: no starship.toml found; pass its path");
+        return ExitCode::FAILURE;
+    };
+    let migration = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| migrate::migrate(&text, &Cli::command()));
+    match migration {
+        Ok(migration) => {
+            let target = config_file::path().unwrap_or_else(|| "config.toml".into());
+            println!("# {}\n{}", target.display(), migration.config);
+            println!("# {}\n{}", path.display(), migration.stanza);
+            ExitCode::SUCCESS
         }
-        #[cfg(feature = "git")]
-        RepoType::Git => {
-            let repo_root = result.repo_root?;
-            let info = git::collect(&repo_root, config.id_length).ok()?;
-            Some(output::format_git(&info, config))
+        Err(e) => {
+            eprintln!("jj-starship: {}: {e}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `cache` subcommands: where the cache is, what it holds, and emptying it
+fn cache(command: &CacheCommand, config: &Config) -> ExitCode {
+    let Some(dir) = &config.cache_dir else {
+        eprintln!(
+            "jj-starship: no cache directory (JJ_STARSHIP_CACHE_DIR is empty, or HOME is unset)"
+        );
+        return ExitCode::FAILURE;
+    };
+    let done = match command {
+        CacheCommand::Path => {
+            println!("{}", dir.display());
+            Ok(())
+        }
+        CacheCommand::Stats => cache::stats(dir).map(|stats| {
+            println!("{}", dir.display());
+            println!(
+                "{} snapshots, {} files, {} bytes",
+                stats.snapshots, stats.files, stats.bytes
+            );
+            if let Some(oldest) = stats.oldest {
+                println!("oldest snapshot: {}d old", oldest.as_secs() / 86_400);
+            }
+        }),
+        CacheCommand::Clear => cache::clear(dir).map(|removed| {
+            println!("removed {removed} files from {}", dir.display());
+        }),
+    };
+    match done {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("jj-starship: {}: {e}", dir.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `config check` subcommand: diagnostics in compiler format, so editors
+/// can jump to them
+fn config_check(path: Option<PathBuf>) -> ExitCode {
+    let Some(path) = path.or_else(config_file::path) else {
+        eprintln!("jj-starship: no config file path; pass one");
+        return ExitCode::FAILURE;
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("jj-starship: {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let diagnostics = config_file::check(&text);
+    for diagnostic in &diagnostics {
+        println!("{}:{diagnostic}", path.display());
+    }
+    if diagnostics.is_empty() {
+        println!("{}: ok", path.display());
+    }
+    if diagnostics
+        .iter()
+        .any(|d| d.severity == config_file::Severity::Error)
+    {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// `man` subcommand: one page per command, named `jj-starship-<sub>.1`
+fn man(dir: &Path) -> ExitCode {
+    match fs::create_dir_all(dir).and_then(|()| clap_mangen::generate_to(Cli::command(), dir)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("jj-starship: {}: {e}", dir.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Set in the environment of the process [`spawn_warm`] spawns
+const WARM_CHILD: &str = "JJ_STARSHIP_WARM_CHILD";
+
+/// `warm` subcommand: collect the repo in a detached low-priority process
+///
+/// In that process (marked by [`WARM_CHILD`]), collect and store the JJ
+/// snapshot without rendering, running hooks or notifying, and without
+/// settling for a stale snapshot.
+fn warm(cwd: &Path, config: &Config) -> ExitCode {
+    let result = detect::detect(cwd, &config.boundaries);
+    if result.repo_root.is_none() {
+        return ExitCode::FAILURE;
+    }
+    if env::var_os(WARM_CHILD).is_some() {
+        let config = Config {
+            stale_while_revalidate: false,
+            ..config.clone()
+        };
+        return match collect_checked(&result, &config, &mut Timings::default()) {
+            (_, None) => ExitCode::SUCCESS,
+            (_, Some(_)) => ExitCode::FAILURE,
+        };
+    }
+    match spawn_warm() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            tracing::warn!("spawn warm: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `watch` subcommand: stream renders until the reader goes away
+#[cfg(feature = "watch")]
+fn watch(cwd: &Path, config: &Config, debounce_ms: u64) -> ExitCode {
+    let result = detect::detect(cwd, &config.boundaries);
+    if result.repo_root.is_none() {
+        return ExitCode::FAILURE;
+    }
+    let render = || {
+        run_prompt(cwd, config, &mut Timings::default())
+            .output
+            .unwrap_or_default()
+    };
+    let debounce = Duration::from_millis(debounce_ms);
+    match watch::run(
+        &result,
+        config.colocated,
+        debounce,
+        render,
+        &mut io::stdout().lock(),
+    ) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("jj-starship: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Re-run `warm` with the same arguments in a detached child, so the OS
+/// page cache holds the repo's store/index files and the JJ snapshot is
+/// stored by the time the shell renders the prompt. The child inherits the
+/// working directory and env vars, so it resolves the same config and
+/// snapshot fingerprint as the parent.
+fn spawn_warm() -> io::Result<()> {
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    spawn_low_priority(&env::current_exe()?, &args, &[(WARM_CHILD, "1")])
+}
+
+/// Wrappers that lower the child's CPU and IO priority, most thorough first
+#[cfg(target_os = "linux")]
+const LOW_PRIORITY: &[&[&str]] = &[
+    &["ionice", "-c", "3", "nice", "-n", "19"],
+    &["nice", "-n", "19"],
+];
+#[cfg(target_os = "macos")]
+const LOW_PRIORITY: &[&[&str]] = &[&["taskpolicy", "-b"], &["nice", "-n", "19"]];
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+const LOW_PRIORITY: &[&[&str]] = &[&["nice", "-n", "19"]];
+#[cfg(not(unix))]
+const LOW_PRIORITY: &[&[&str]] = &[];
+
+/// Spawn a detached background child that never competes with foreground work
+///
+/// Tries each available priority wrapper, falling back to a plain spawn when
+/// none is installed.
+fn spawn_low_priority(program: &Path, args: &[OsString], envs: &[(&str, &str)]) -> io::Result<()> {
+    for wrapper in LOW_PRIORITY {
+        let mut command = process::Command::new(wrapper[0]);
+        command
+            .args(&wrapper[1..])
+            .arg(program)
+            .args(args)
+            .envs(envs.iter().copied());
+        match detach(&mut command).spawn() {
+            Ok(child) => {
+                reap(child);
+                return Ok(());
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
         }
-        RepoType::None => None,
-        // Catch disabled variants
-        _ => None,
     }
+    let mut command = process::Command::new(program);
+    command.args(args).envs(envs.iter().copied());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+        command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+    }
+    detach(&mut command).spawn().map(reap)
 }
 
-fn print_version() {
+/// Wait for `child` on a thread of its own, so a long-lived process (`watch`)
+/// doesn't collect zombies; a prompt exits first and leaves it to init
+fn reap(mut child: process::Child) {
+    thread::spawn(move || child.wait());
+}
+
+/// Cut `command` off from the terminal: no stdio, and on Unix a process
+/// group of its own, so the shell's hangup or Ctrl-C doesn't reach it
+fn detach(command: &mut process::Command) -> &mut process::Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+}
+
+/// Output of one prompt generation
+struct Rendered {
+    /// Rendered segment, None when there's nothing to show
+    output: Option<String>,
+    /// Starship style for the repo state
+    style: Option<Cow<'static, str>>,
+    /// Collection error (already logged), reported under `--strict`
+    error: Option<Error>,
+}
+
+/// Run prompt generation; errors are kept for `--strict` but otherwise
+/// silent (a failed collection renders degraded or not at all)
+fn run_prompt(cwd: &Path, config: &Config, timings: &mut Timings) -> Rendered {
+    let result = timings.time("detect", || detect::detect(cwd, &config.boundaries));
+    tracing::debug!(repo_type = ?result.repo_type, repo_root = ?result.repo_root, "detected");
+    // Hooks run alongside collection, in the repo root
+    let hooks = result
+        .repo_root
+        .as_deref()
+        .filter(|_| !config.hooks.is_empty())
+        .map(|root| hooks::start(&config.hooks, root));
+    let (info, error) = collect_checked(&result, config, timings);
+    if stale(info.as_ref())
+        && let Err(e) = spawn_warm()
+    {
+        tracing::warn!("spawn refresh: {e}");
+    }
+    if config.notify
+        && let (Some(dir), Some(root), Some(info)) = (&config.cache_dir, &result.repo_root, &info)
+    {
+        timings.time("notify", || {
+            notify::check(dir, config.cache_max_age, root, info);
+        });
+    }
+    let mut output = timings.time("format", || match &error {
+        Some(e) => output::render_error(&result, e, config),
+        None => output::render(&result, info.as_ref(), config),
+    });
+    if let Some(hooks) = hooks {
+        let outputs = timings.time("hooks", || hooks.finish());
+        output = output.map(|output| hooks::splice(output, &outputs, config));
+    }
+    let style = info
+        .as_ref()
+        .map(|info| output::starship_style(info, config));
+    Rendered {
+        output,
+        style,
+        error,
+    }
+}
+
+/// A JJ result that `--stale-while-revalidate` served from an earlier op
+/// head's snapshot, to refresh in the background
+///
+/// Not while a `jj` command is running: the refresh would collect read-only
+/// and store nothing.
+fn stale(info: Option<&RepoInfo>) -> bool {
+    match info {
+        #[cfg(feature = "jj")]
+        Some(RepoInfo::Jj(jj)) => jj.stale && !jj.op_in_progress,
+        #[cfg(all(feature = "jj", feature = "git"))]
+        Some(RepoInfo::Colocated(jj, _)) => jj.stale && !jj.op_in_progress,
+        _ => false,
+    }
+}
+
+/// Collect like `jj_starship::collect`, also returning the error it swallows
+fn collect_checked(
+    result: &detect::DetectResult,
+    config: &Config,
+    timings: &mut Timings,
+) -> (Option<RepoInfo>, Option<Error>) {
+    match try_collect(result, config, timings) {
+        Ok(info) => (info, None),
+        Err(e) => {
+            tracing::warn!("{e}");
+            (None, Some(e))
+        }
+    }
+}
+
+/// `--strict` or `JJ_STARSHIP_STRICT`
+fn is_strict(flag: bool) -> bool {
+    flag || env::var("JJ_STARSHIP_STRICT").is_ok()
+}
+
+/// Report a collection error as `error[CODE]: message` and exit 2
+fn strict_failure(e: &Error) -> ExitCode {
+    eprintln!("jj-starship: error[{}]: {e}", e.code());
+    ExitCode::from(2)
+}
+
+/// Best-effort write of the style line to an inherited descriptor
+///
+/// Goes through `/dev/fd` so no unsafe fd adoption is needed.
+fn write_style(fd: u32, style: &str) {
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .open(format!("/dev/fd/{fd}"))
+        .and_then(|mut file| writeln!(file, "{style}"));
+    if let Err(e) = written {
+        tracing::debug!("style fd {fd}: {e}");
+    }
+}
+
+/// Version, build and feature lines, as `version` prints them
+fn version_text() -> String {
     let version = env!("CARGO_PKG_VERSION");
     let change_id = env!("JJ_CHANGE_ID");
     let commit = env!("GIT_COMMIT");
     let date = env!("BUILD_DATE");
 
-    println!("jj-starship {version}");
-    println!("change: {change_id}");
-    println!("commit: {commit}");
-    println!("built:  {date}");
-
-    #[allow(unused_mut)] // mut needed when features are enabled
-    let mut features: Vec<&str> = Vec::new();
-    #[cfg(feature = "git")]
-    features.push("git");
-
-    if features.is_empty() {
-        println!("features: none");
-    } else {
-        println!("features: {}", features.join(", "));
-    }
+    let features: &[&str] = &[
+        #[cfg(feature = "jj")]
+        "jj",
+        #[cfg(feature = "git")]
+        "git",
+    ];
+    format!(
+        "jj-starship {version}\nchange: {change_id}\ncommit: {commit}\nbuilt:  {date}\nfeatures: {}",
+        features.join(", ")
+    )
 }
 
 #[cfg(test)]
@@ -260,6 +1157,13 @@ mod tests {
         assert!(matches!(cli.command, Some(Command::Version)));
     }
 
+    #[test]
+    fn warm_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "--cwd", "/repo", "warm"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Warm)));
+        assert_eq!(cli.cwd, Some(PathBuf::from("/repo")));
+    }
+
     #[test]
     fn cwd_arg() {
         let cli = Cli::try_parse_from(["jj-starship", "--cwd", "/some/path"]).unwrap();
@@ -298,93 +1202,493 @@ mod tests {
     }
 
     #[test]
-    fn jj_symbol_arg() {
-        let cli = Cli::try_parse_from(["jj-starship", "--jj-symbol", "JJ:"]).unwrap();
-        assert_eq!(cli.jj_symbol, Some("JJ:".to_string()));
+    fn conflict_count_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--conflict-count"]).unwrap();
+        assert_eq!(cli.conflict_count.as_deref(), Some("all()"));
+        let cli = Cli::try_parse_from(["jj-starship", "--conflict-count=mutable()"]).unwrap();
+        assert_eq!(cli.conflict_count.as_deref(), Some("mutable()"));
+        let cli = Cli::try_parse_from(["jj-starship", "--conflict-count", "prompt"]).unwrap();
+        assert_eq!(cli.conflict_count.as_deref(), Some("all()"));
+    }
+
+    #[test]
+    fn large_repo_threshold_args() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--large-repo-files",
+            "50000",
+            "--large-repo-objects",
+            "0",
+        ])
+        .unwrap();
+        assert_eq!(cli.large_repo_files, Some(50000));
+        assert_eq!(cli.large_repo_objects, Some(0));
+    }
+
+    #[test]
+    fn network_fs_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--network-fs", "on"]).unwrap();
+        assert_eq!(cli.network_fs, Some(NetworkFs::On));
+        assert!(Cli::try_parse_from(["jj-starship", "--network-fs", "maybe"]).is_err());
+    }
+
+    #[test]
+    fn detection_boundary_args() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--stop-at-home",
+            "--one-filesystem",
+            "--max-search-depth",
+            "4",
+            "--ignore-paths",
+            "/mnt/nas/**,~/archive",
+        ])
+        .unwrap();
+        assert!(cli.stop_at_home);
+        assert!(cli.one_filesystem);
+        assert_eq!(cli.max_search_depth, Some(4));
+        assert_eq!(cli.ignore_paths.as_deref(), Some("/mnt/nas/**,~/archive"));
+
+        let cli = Cli::try_parse_from(["jj-starship", "--git-dirs", "~/.cfg"]).unwrap();
+        assert_eq!(cli.git_dirs.as_deref(), Some("~/.cfg"));
+
+        let cli = Cli::try_parse_from(["jj-starship", "--nesting", "prefer-jj"]).unwrap();
+        assert_eq!(cli.nesting, Some(Nesting::PreferJj));
+        assert!(Cli::try_parse_from(["jj-starship", "--nesting", "first"]).is_err());
+
+        let cli = Cli::try_parse_from(["jj-starship", "--resolve-symlinks", "on"]).unwrap();
+        assert_eq!(cli.resolve_symlinks, Some(ResolveSymlinks::On));
+    }
+
+    #[test]
+    fn no_color_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "--no-color"]).unwrap();
+        assert!(cli.no_color);
+    }
+
+    #[test]
+    fn no_ansi_alias() {
+        let cli = Cli::try_parse_from(["jj-starship", "--no-ansi"]).unwrap();
+        assert!(cli.no_color);
+    }
+
+    #[test]
+    fn style_fd_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--style-fd", "3"]).unwrap();
+        assert_eq!(cli.style_fd, Some(3));
+        assert!(Cli::try_parse_from(["jj-starship", "--style-fd", "-1"]).is_err());
+    }
+
+    #[test]
+    fn escapes_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--escapes", "zsh"]).unwrap();
+        assert_eq!(cli.escapes, Some(Escapes::Zsh));
+        assert!(Cli::try_parse_from(["jj-starship", "--escapes", "fish"]).is_err());
+    }
+
+    #[test]
+    fn segment_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--segment", "status"]).unwrap();
+        assert_eq!(cli.segment, Some(Segment::Status));
+        assert!(Cli::try_parse_from(["jj-starship", "--segment", "id"]).is_err());
+    }
+
+    #[test]
+    fn separator_args() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--separator",
+            " | ",
+            "--bookmark-separator",
+            " ",
+        ])
+        .unwrap();
+        assert_eq!(cli.separator.as_deref(), Some(" | "));
+        assert_eq!(cli.bookmark_separator.as_deref(), Some(" "));
+
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--prefix-word",
+            "",
+            "--prefix-word",
+            "git:in",
+        ])
+        .unwrap();
+        assert_eq!(cli.prefix_word.len(), 2);
+        assert_eq!(cli.prefix_word[1].repo, Some("git"));
+
+        let cli = Cli::try_parse_from(["jj-starship", "--affix", "id=⟨{}⟩"]).unwrap();
+        assert_eq!(cli.affix[0].prefix, "⟨");
+        assert!(Cli::try_parse_from(["jj-starship", "--affix", "id=<>"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--affix", "color=<{}>"]).is_err());
+    }
+
+    #[test]
+    fn field_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "field", "change-id"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Field {
+                name: Field::ChangeId
+            })
+        ));
+        assert!(Cli::try_parse_from(["jj-starship", "field", "author"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "field"]).is_err());
+    }
+
+    #[test]
+    fn query_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "query", ".jj.bookmarks[0].name"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Query { path }) if path == ".jj.bookmarks[0].name".parse().unwrap()
+        ));
+        assert!(Cli::try_parse_from(["jj-starship", "query", "jj.bookmark"]).is_err());
+    }
+
+    #[test]
+    fn symbol_args() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--symbol-set",
+            "ascii",
+            "--symbol",
+            "modified=*",
+            "--symbol",
+            "ahead=>",
+        ])
+        .unwrap();
+        assert_eq!(cli.symbol_set, Some(SymbolSet::Ascii));
+        assert_eq!(cli.symbol.len(), 2);
+        assert!(Cli::try_parse_from(["jj-starship", "--symbol-set", "fancy"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--symbol", "dirty=*"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--symbol", "modified"]).is_err());
+    }
+
+    #[test]
+    fn powerline_args() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--output",
+            "powerline",
+            "--powerline-bg",
+            "#303030",
+            "--powerline-prev-bg",
+            "4",
+        ])
+        .unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::Powerline));
+        assert_eq!(cli.powerline_bg, Some(TermColor::Rgb(0x30, 0x30, 0x30)));
+        assert_eq!(cli.powerline_prev_bg, Some(TermColor::Indexed(4)));
+        assert_eq!(cli.powerline_next_bg, None);
+        assert!(Cli::try_parse_from(["jj-starship", "--powerline-bg", "#30"]).is_err());
+    }
+
+    #[test]
+    fn colocated_args() {
+        let cli = Cli::try_parse_from(["jj-starship", "--colocated-view", "git"]).unwrap();
+        assert_eq!(cli.colocated_view, Some(Backend::Git));
+        assert_eq!(cli.colocated_status, None);
+        assert!(Cli::try_parse_from(["jj-starship", "--colocated-status", "hg"]).is_err());
     }
 
     #[test]
-    fn no_symbol_takes_precedence_over_jj_symbol() {
+    fn output_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--output", "json"]).unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::Json));
+        let cli = Cli::try_parse_from(["jj-starship", "--output", "nuon"]).unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::Nuon));
+        let cli = Cli::try_parse_from(["jj-starship", "--output", "omp"]).unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::Omp));
+        let cli = Cli::try_parse_from(["jj-starship", "--output", "p10k"]).unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::P10k));
+        let cli = Cli::try_parse_from(["jj-starship", "--output", "tmux"]).unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::Tmux));
+        let cli = Cli::try_parse_from(["jj-starship", "--output", "zellij"]).unwrap();
+        assert_eq!(cli.output, Some(OutputFormat::Zellij));
+        assert!(Cli::try_parse_from(["jj-starship", "--output", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn schema_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "schema"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Schema)));
+    }
+
+    #[test]
+    fn bench_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "bench"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Bench { iterations: 100 })
+        ));
+
+        let cli = Cli::try_parse_from(["jj-starship", "bench", "--iterations", "5"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Bench { iterations: 5 })
+        ));
+    }
+
+    #[test]
+    fn debug_dump_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "debug", "dump"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Debug(DebugCommand::Dump))
+        ));
+        assert!(Cli::try_parse_from(["jj-starship", "debug"]).is_err());
+    }
+
+    #[test]
+    fn cache_subcommands() {
+        let cli = Cli::try_parse_from(["jj-starship", "cache", "clear"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Cache(CacheCommand::Clear))
+        ));
+        assert!(Cli::try_parse_from(["jj-starship", "cache", "stats"]).is_ok());
+        assert!(Cli::try_parse_from(["jj-starship", "cache", "path"]).is_ok());
+        assert!(Cli::try_parse_from(["jj-starship", "cache"]).is_err());
+    }
+
+    #[test]
+    fn config_check_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "config", "check"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Config(ConfigCommand::Check { path: None }))
+        ));
+        let cli = Cli::try_parse_from(["jj-starship", "config", "check", "c.toml"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Config(ConfigCommand::Check { path: Some(_) }))
+        ));
+    }
+
+    #[test]
+    fn hide_and_show_lists() {
+        let mut cli = Cli::try_parse_from([
+            "jj-starship",
+            "--hide",
+            "jj:id,git:status",
+            "--hide",
+            "prefix",
+            "--show",
+            "hg:prefix",
+        ])
+        .unwrap();
+        assert_eq!(cli.hide.len(), 3);
+        let config = cli.config();
+        assert!(!config.jj_display.show_id);
+        assert!(config.git_display.show_id);
+        assert!(!config.git_display.show_status);
+        assert!(!config.jj_display.show_prefix);
+        assert!(config.hg_display.show_prefix);
+
+        assert!(Cli::try_parse_from(["jj-starship", "--hide", "svn:id"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--hide", "jj:branch"]).is_err());
+    }
+
+    #[test]
+    fn only_when_dirty_flag() {
+        let mut cli = Cli::try_parse_from(["jj-starship", "--only-when-dirty"]).unwrap();
+        assert!(cli.config().only_when_dirty);
+    }
+
+    #[test]
+    fn count_thresholds() {
+        let mut cli = Cli::try_parse_from([
+            "jj-starship",
+            "--count-threshold",
+            "behind=2,ahead=1",
+            "--count-threshold",
+            "behind=4",
+        ])
+        .unwrap();
+        let thresholds = cli.config().count_thresholds;
+        assert_eq!((thresholds.ahead, thresholds.behind), (1, 4));
+        assert!(Cli::try_parse_from(["jj-starship", "--count-threshold", "stash=1"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--count-threshold", "ahead=-1"]).is_err());
+    }
+
+    #[test]
+    fn style_overrides() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--style",
+            "name=bold fg:#ff8800 bg:color_aqua",
+            "--style",
+            "id-prefix=underline",
+        ])
+        .unwrap();
+        assert_eq!(cli.style.len(), 2);
+        assert!(Cli::try_parse_from(["jj-starship", "--style", "branch=bold"]).is_err());
         let cli =
-            Cli::try_parse_from(["jj-starship", "--jj-symbol", "custom", "--no-symbol"]).unwrap();
-        // no_symbol should be true, and when Config is built, symbols become empty
-        assert!(cli.no_symbol);
-        assert_eq!(cli.jj_symbol, Some("custom".to_string()));
-
-        // Verify Config respects no_symbol precedence
-        let config = Config::new(
-            None,
-            None,
-            None,
-            None,
-            None,
-            cli.jj_symbol,
-            None,
-            cli.no_symbol,
-            DisplayFlags::default(),
-            DisplayFlags::default(),
-        );
-        assert_eq!(config.jj_symbol.as_ref(), "");
-        assert_eq!(config.git_symbol.as_ref(), "");
+            Cli::try_parse_from(["jj-starship", "--state-style", "dirty=bold yellow"]).unwrap();
+        assert_eq!(cli.state_style.len(), 1);
+        assert!(Cli::try_parse_from(["jj-starship", "--state-style", "behind=red"]).is_err());
+    }
+
+    #[test]
+    fn show_if_rules() {
+        let mut cli = Cli::try_parse_from([
+            "jj-starship",
+            "--show-if",
+            "id=no-bookmark,jj:status=conflict",
+        ])
+        .unwrap();
+        assert_eq!(cli.config().show_if.len(), 2);
+        assert!(Cli::try_parse_from(["jj-starship", "--show-if", "id"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--show-if", "id=detached"]).is_err());
     }
 
     #[test]
-    fn no_jj_prefix_flag() {
-        let cli = Cli::try_parse_from(["jj-starship", "--no-jj-prefix"]).unwrap();
-        assert!(cli.no_jj_prefix);
+    fn hyperlinks_flag() {
+        let mut cli = Cli::try_parse_from(["jj-starship", "--hyperlinks"]).unwrap();
+        assert!(cli.config().hyperlinks);
+        let mut cli = Cli::try_parse_from(["jj-starship"]).unwrap();
+        assert!(!cli.config().hyperlinks);
     }
 
     #[test]
-    fn no_jj_name_flag() {
-        let cli = Cli::try_parse_from(["jj-starship", "--no-jj-name"]).unwrap();
-        assert!(cli.no_jj_name);
+    fn notify_flag() {
+        let mut cli = Cli::try_parse_from(["jj-starship", "--notify"]).unwrap();
+        assert!(cli.config().notify);
     }
 
     #[test]
-    fn no_jj_id_flag() {
-        let cli = Cli::try_parse_from(["jj-starship", "--no-jj-id"]).unwrap();
-        assert!(cli.no_jj_id);
+    fn colorblind_theme() {
+        // The set is explicit so TERM=dumb doesn't swap in ASCII glyphs
+        let mut cli = Cli::try_parse_from([
+            "jj-starship",
+            "--theme",
+            "colorblind",
+            "--symbol-set",
+            "nerd",
+        ])
+        .unwrap();
+        let config = cli.config();
+        assert_eq!(config.symbols.conflict, "‼");
+        assert_eq!(config.palette.red, Some(TermColor::Rgb(0xe6, 0x9f, 0x00)));
+        assert!(Cli::try_parse_from(["jj-starship", "--theme", "solarized"]).is_err());
     }
 
     #[test]
-    fn no_jj_status_flag() {
-        let cli = Cli::try_parse_from(["jj-starship", "--no-jj-status"]).unwrap();
-        assert!(cli.no_jj_status);
+    fn verbose_words_flag() {
+        let mut cli = Cli::try_parse_from(["jj-starship", "--verbose-words"]).unwrap();
+        assert!(cli.config().verbose_words);
     }
 
     #[test]
-    fn no_color_flag() {
-        let cli = Cli::try_parse_from(["jj-starship", "--no-color"]).unwrap();
-        assert!(cli.no_color);
+    fn starship_theme_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "--starship-theme"]).unwrap();
+        assert!(cli.starship_theme);
     }
 
     #[test]
-    fn no_prefix_color_flag() {
-        let cli = Cli::try_parse_from(["jj-starship", "--no-prefix-color"]).unwrap();
-        assert!(cli.no_prefix_color);
+    fn man_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "man", "out"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Man { dir }) if dir == Path::new("out")));
+        assert!(Cli::try_parse_from(["jj-starship", "man"]).is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("man1");
+        assert_eq!(man(&dir), ExitCode::SUCCESS);
+        let page = fs::read_to_string(dir.join("jj-starship.1")).unwrap();
+        assert!(page.contains("\\-\\-id\\-length"));
+        assert!(dir.join("jj-starship-debug-dump.1").exists());
     }
 
     #[test]
-    fn multiple_global_args() {
+    fn explain_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "--symbol-set", "ascii", "explain"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Explain)));
+        assert_eq!(cli.symbol_set, Some(SymbolSet::Ascii));
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn watch_subcommand() {
+        let cli = Cli::try_parse_from(["jj-starship", "watch"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Watch { debounce_ms: 100 })
+        ));
         let cli = Cli::try_parse_from([
             "jj-starship",
-            "--cwd",
-            "/test",
-            "--truncate-name",
-            "15",
-            "--id-length",
-            "6",
-            "--no-jj-prefix",
-            "--no-jj-status",
+            "--output",
+            "json",
+            "watch",
+            "--debounce-ms",
+            "20",
         ])
         .unwrap();
-        assert_eq!(cli.cwd, Some(PathBuf::from("/test")));
-        assert_eq!(cli.truncate_name, Some(15));
-        assert_eq!(cli.id_length, Some(6));
-        assert!(cli.no_jj_prefix);
-        assert!(cli.no_jj_status);
-        assert!(!cli.no_jj_name);
-        assert!(!cli.no_jj_id);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Watch { debounce_ms: 20 })
+        ));
+    }
+
+    #[test]
+    fn budget_args() {
+        let cli = Cli::try_parse_from([
+            "jj-starship",
+            "--divergence-timeout-ms",
+            "50",
+            "--status-timeout-ms",
+            "200",
+            "--timeout-placeholder",
+            "…",
+        ])
+        .unwrap();
+        assert_eq!(cli.divergence_timeout_ms, Some(50));
+        assert_eq!(cli.status_timeout_ms, Some(200));
+        assert_eq!(cli.timeout_placeholder, Some("…".to_string()));
+    }
+
+    #[test]
+    fn read_only_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "--read-only"]).unwrap();
+        assert!(cli.read_only);
+    }
+
+    #[test]
+    fn compact_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "--compact"]).unwrap();
+        assert!(cli.compact);
+    }
+
+    #[test]
+    fn no_cache_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "--no-cache"]).unwrap();
+        assert!(cli.no_cache);
+    }
+
+    #[test]
+    fn stale_while_revalidate_flag() {
+        let mut cli = Cli::try_parse_from(["jj-starship", "--stale-while-revalidate"]).unwrap();
+        assert!(cli.stale_while_revalidate);
+        assert!(cli.config().stale_while_revalidate);
+    }
+
+    #[test]
+    fn log_level_arg() {
+        let cli = Cli::try_parse_from(["jj-starship", "--log-level", "debug"]).unwrap();
+        assert_eq!(cli.log_level, Some(LevelFilter::DEBUG));
+        assert!(Cli::try_parse_from(["jj-starship", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn strict_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "field", "branch", "--strict"]).unwrap();
+        assert!(cli.strict);
+    }
+
+    #[test]
+    fn timings_flag() {
+        let cli = Cli::try_parse_from(["jj-starship", "prompt", "--timings"]).unwrap();
+        assert!(cli.timings);
     }
 
     #[test]
@@ -394,6 +1698,94 @@ mod tests {
         assert!(matches!(cli.command, Some(Command::Detect)));
     }
 
+    #[cfg(feature = "jj")]
+    mod jj_args {
+        use super::*;
+
+        #[test]
+        fn jj_symbol_arg() {
+            let cli = Cli::try_parse_from(["jj-starship", "--jj-symbol", "JJ:"]).unwrap();
+            assert_eq!(cli.jj.jj_symbol, Some("JJ:".to_string()));
+        }
+
+        #[test]
+        fn no_symbol_takes_precedence_over_jj_symbol() {
+            let cli = Cli::try_parse_from(["jj-starship", "--jj-symbol", "custom", "--no-symbol"])
+                .unwrap();
+            // no_symbol should be true, and when Config is built, symbols become empty
+            assert!(cli.no_symbol);
+            assert_eq!(cli.jj.jj_symbol, Some("custom".to_string()));
+
+            // Verify Config respects no_symbol precedence
+            let config = Config::new(Flags {
+                prefixes: PrefixFlags {
+                    jj_symbol: cli.jj.jj_symbol,
+                    no_symbol: cli.no_symbol,
+                    ..PrefixFlags::default()
+                },
+                ..Flags::default()
+            });
+            assert_eq!(config.jj_symbol.as_ref(), "");
+            assert_eq!(config.git_symbol.as_ref(), "");
+            assert_eq!(config.hg_symbol.as_ref(), "");
+            assert_eq!(config.sl_symbol.as_ref(), "");
+            assert_eq!(config.pijul_symbol.as_ref(), "");
+        }
+
+        #[test]
+        fn no_jj_prefix_flag() {
+            let cli = Cli::try_parse_from(["jj-starship", "--no-jj-prefix"]).unwrap();
+            assert!(cli.jj.no_jj_prefix);
+        }
+
+        #[test]
+        fn no_jj_name_flag() {
+            let cli = Cli::try_parse_from(["jj-starship", "--no-jj-name"]).unwrap();
+            assert!(cli.jj.no_jj_name);
+        }
+
+        #[test]
+        fn no_jj_id_flag() {
+            let cli = Cli::try_parse_from(["jj-starship", "--no-jj-id"]).unwrap();
+            assert!(cli.jj.no_jj_id);
+        }
+
+        #[test]
+        fn no_jj_status_flag() {
+            let cli = Cli::try_parse_from(["jj-starship", "--no-jj-status"]).unwrap();
+            assert!(cli.jj.no_jj_status);
+        }
+
+        #[test]
+        fn no_prefix_color_flag() {
+            let cli = Cli::try_parse_from(["jj-starship", "--no-prefix-color"]).unwrap();
+            assert!(cli.jj.no_prefix_color);
+        }
+
+        #[test]
+        fn multiple_global_args() {
+            let cli = Cli::try_parse_from([
+                "jj-starship",
+                "--cwd",
+                "/test",
+                "--truncate-name",
+                "15",
+                "--id-length",
+                "6",
+                "--no-jj-prefix",
+                "--no-jj-status",
+            ])
+            .unwrap();
+            assert_eq!(cli.cwd, Some(PathBuf::from("/test")));
+            assert_eq!(cli.truncate_name, Some(15));
+            assert_eq!(cli.id_length, Some(6));
+            assert!(cli.jj.no_jj_prefix);
+            assert!(cli.jj.no_jj_status);
+            assert!(!cli.jj.no_jj_name);
+            assert!(!cli.jj.no_jj_id);
+        }
+    }
+
     #[cfg(feature = "git")]
     mod git_args {
         use super::*;
@@ -0,0 +1,267 @@
+//! `migrate-config` subcommand - move a starship `[custom.jj]` setup into
+//! the jj-starship config file
+//!
+//! Options in the module's command line become config file keys; whatever
+//! the file can't hold (subcommands, `--strict`, ...) stays on the command
+//! line of the rewritten stanza. `format`, `style` and every other module
+//! key are carried over unchanged.
+
+use clap::ArgAction;
+use jj_starship::config_file::KEYS;
+use std::fmt::Write;
+use toml::{Table, Value};
+
+/// Generated config file and starship stanza
+#[derive(Debug)]
+pub struct Migration {
+    /// Contents for the jj-starship config file
+    pub config: String,
+    /// Replacement `[custom.jj]` block for starship.toml
+    pub stanza: String,
+}
+
+/// Migrate the `[custom.jj]` module of a starship.toml
+///
+/// `cli` is the jj-starship command definition, used to tell flags from
+/// options that take a value.
+///
+/// # Errors
+///
+/// Returns a message when the TOML doesn't parse or has no `[custom.jj]`
+/// module.
+pub fn migrate(starship_toml: &str, cli: &clap::Command) -> Result<Migration, String> {
+    let starship: Table = starship_toml.parse().map_err(|e| format!("{e}"))?;
+    let mut module = starship
+        .get("custom")
+        .and_then(|custom| custom.get("jj"))
+        .and_then(Value::as_table)
+        .cloned()
+        .ok_or("no [custom.jj] module in starship.toml")?;
+
+    let args = command_line(&module);
+    let mut config = Table::new();
+    let mut kept = Vec::new();
+    if let Some(args) = &args {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                kept.push(arg.clone());
+                continue;
+            };
+            let (name, inline) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (option, None),
+            };
+            let had_inline = inline.is_some();
+            let takes_value = cli
+                .get_arguments()
+                .find(|a| a.get_long() == Some(name) || has_alias(a, name))
+                .is_some_and(|a| !matches!(a.get_action(), ArgAction::SetTrue));
+            let value = if takes_value {
+                inline.or_else(|| args.next().cloned())
+            } else {
+                None
+            };
+            if !store(&mut config, name, value.as_deref()) {
+                kept.push(arg.clone());
+                if !had_inline && let Some(value) = value {
+                    kept.push(value);
+                }
+            }
+        }
+    }
+
+    let mut note = String::new();
+    if args.is_none()
+        && let Some(command) = module.get("command").and_then(Value::as_str)
+    {
+        let _ = writeln!(note, "# replaces: command = {:?}", command.trim());
+    }
+    module.remove("command");
+    let mut shell = vec![Value::from("jj-starship")];
+    shell.extend(kept.into_iter().map(Value::from));
+    module.insert("shell".into(), Value::Array(shell));
+    if !module
+        .get("when")
+        .and_then(Value::as_str)
+        .is_some_and(|when| when.contains("jj-starship"))
+    {
+        module.insert("when".into(), "jj-starship detect".into());
+    }
+    if !module.contains_key("format") {
+        module.insert("format".into(), "$output ".into());
+    }
+
+    let mut custom = Table::new();
+    custom.insert("jj".into(), Value::Table(module));
+    let mut stanza = Table::new();
+    stanza.insert("custom".into(), Value::Table(custom));
+    Ok(Migration {
+        config: toml::to_string(&config).map_err(|e| format!("{e}"))?,
+        stanza: note + &toml::to_string(&stanza).map_err(|e| format!("{e}"))?,
+    })
+}
+
+/// Arguments after `jj-starship` in the module's `shell` or `command`,
+/// None when it doesn't run jj-starship
+fn command_line(module: &Table) -> Option<Vec<String>> {
+    let shell: Vec<String> = match module.get("shell") {
+        Some(Value::String(shell)) => vec![shell.clone()],
+        Some(Value::Array(shell)) => shell
+            .iter()
+            .filter_map(|arg| arg.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let command = module.get("command").and_then(Value::as_str).unwrap_or("");
+    // `shell = ["jj-starship", ...]` runs jj-starship itself, with `command`
+    // as its stdin; otherwise `command` is the jj-starship command line
+    let words = if shell.first().is_some_and(|program| is_jj_starship(program)) {
+        shell
+    } else {
+        split_words(command)
+    };
+    let (program, args) = words.split_first()?;
+    is_jj_starship(program).then(|| args.to_vec())
+}
+
+fn is_jj_starship(program: &str) -> bool {
+    program.rsplit(['/', '\\']).next() == Some("jj-starship")
+}
+
+fn has_alias(arg: &clap::Arg, name: &str) -> bool {
+    arg.get_visible_aliases()
+        .is_some_and(|aliases| aliases.contains(&name))
+}
+
+/// Put one option in the config table; false when the file has no key
+/// for it
+fn store(config: &mut Table, name: &str, value: Option<&str>) -> bool {
+    let key = match name {
+        "no-color" | "no-ansi" => "no_ansi".to_string(),
+        _ => name.replace('-', "_"),
+    };
+    // `--symbol NAME=GLYPH`, `--style PART=STYLE` and `--state-style
+    // STATE=STYLE` collect into tables
+    if let Some(table) = match key.as_str() {
+        "symbol" => Some("symbols"),
+        "style" => Some("styles"),
+        "state_style" => Some("state_styles"),
+        _ => None,
+    } {
+        let Some((name, entry)) = value.and_then(|v| v.split_once('=')) else {
+            return false;
+        };
+        if let Value::Table(entries) = config
+            .entry(table)
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            entries.insert(name.into(), entry.into());
+        }
+        return true;
+    }
+    if !KEYS.contains(&key.as_str()) {
+        return false;
+    }
+    let value = match value {
+        None => Value::Boolean(true),
+        Some(v) => v.parse::<i64>().map_or_else(|_| v.into(), Value::from),
+    };
+    config.insert(key, value);
+    true
+}
+
+/// Split a command line on whitespace, honoring single and double quotes
+fn split_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli() -> clap::Command {
+        clap::Command::new("jj-starship")
+            .arg(clap::Arg::new("id-length").long("id-length"))
+            .arg(clap::Arg::new("symbol").long("symbol"))
+            .arg(clap::Arg::new("separator").long("separator"))
+            .arg(
+                clap::Arg::new("no-git-id")
+                    .long("no-git-id")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("strict")
+                    .long("strict")
+                    .action(ArgAction::SetTrue),
+            )
+    }
+
+    #[test]
+    fn options_move_to_the_config_file() {
+        let migration = migrate(
+            r#"
+[custom.jj]
+command = "jj-starship --id-length 12 --no-git-id --separator=' | ' --symbol modified=* --strict"
+when = "jj-starship detect"
+style = "bold purple"
+format = "[$output]($style) "
+"#,
+            &cli(),
+        )
+        .unwrap();
+        assert_eq!(
+            migration.config,
+            "id_length = 12\nno_git_id = true\nseparator = \" | \"\n\n[symbols]\nmodified = \"*\"\n"
+        );
+        assert_eq!(
+            migration.stanza,
+            "[custom.jj]\nformat = \"[$output]($style) \"\nshell = [\"jj-starship\", \"--strict\"]\nstyle = \"bold purple\"\nwhen = \"jj-starship detect\"\n"
+        );
+    }
+
+    #[test]
+    fn shell_invocation_and_foreign_commands() {
+        let migration = migrate(
+            "[custom.jj]\nshell = [\"jj-starship\", \"--id-length\", \"4\", \"field\", \"bookmark\"]\n",
+            &cli(),
+        )
+        .unwrap();
+        assert_eq!(migration.config, "id_length = 4\n");
+        assert!(
+            migration
+                .stanza
+                .contains("shell = [\"jj-starship\", \"field\", \"bookmark\"]")
+        );
+
+        let migration = migrate(
+            "[custom.jj]\ncommand = \"jj log -r@ --no-graph\"\nwhen = \"jj root\"\n",
+            &cli(),
+        )
+        .unwrap();
+        assert_eq!(migration.config, "");
+        assert!(
+            migration
+                .stanza
+                .starts_with("# replaces: command = \"jj log -r@ --no-graph\"\n")
+        );
+        assert!(migration.stanza.contains("when = \"jj-starship detect\""));
+
+        assert!(migrate("[custom.git]\n", &cli()).is_err());
+    }
+}
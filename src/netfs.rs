@@ -0,0 +1,187 @@
+//! Network filesystem detection - repos on NFS/SMB/sshfs use the fast path
+//!
+//! Detection reads `/proc/self/mountinfo` on Linux. Other platforms rely on
+//! the explicit override since there is no cheap, safe fs-type query.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Filesystem types whose worktree scans routinely take seconds
+const NETWORK_FSTYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "davfs",
+    "sshfs",
+    "rclone",
+    "s3fs",
+    "gcsfuse",
+];
+
+/// Network filesystem handling mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NetworkFs {
+    /// Detect from the mount table
+    #[default]
+    Auto,
+    /// Always use the fast path
+    On,
+    /// Never use the fast path
+    Off,
+}
+
+impl NetworkFs {
+    /// Whether `path` should be collected via the fast path
+    #[must_use = "returns detection result, does not modify state"]
+    pub fn applies(self, path: &Path) -> bool {
+        match self {
+            Self::On => true,
+            Self::Off => false,
+            Self::Auto => is_network_fs(path),
+        }
+    }
+}
+
+impl FromStr for NetworkFs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "on" => Ok(Self::On),
+            "off" => Ok(Self::Off),
+            _ => Err(format!("expected auto, on or off, got {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for NetworkFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::On => "on",
+            Self::Off => "off",
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_network_fs(path: &Path) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") else {
+        return false;
+    };
+    mount_fstype(&mountinfo, &path).is_some_and(is_network_fstype)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_fs(_path: &Path) -> bool {
+    false
+}
+
+/// Filesystem type of the mount containing `path` (longest mount point wins;
+/// among equal mount points the last, i.e. topmost, entry wins)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mount_fstype<'a>(mountinfo: &'a str, path: &Path) -> Option<&'a str> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // "<id> <parent> <dev> <root> <mount point> <opts...> - <fstype> <source> <opts>"
+            let (fields, rest) = line.split_once(" - ")?;
+            let mount_point = unescape_octal(fields.split(' ').nth(4)?);
+            let fstype = rest.split(' ').next()?;
+            Some((mount_point, fstype))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fstype)| fstype)
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn is_network_fstype(fstype: &str) -> bool {
+    let base = fstype.strip_prefix("fuse.").unwrap_or(fstype);
+    NETWORK_FSTYPES.contains(&base)
+}
+
+/// Decode mountinfo's `\NNN` octal escapes (space, tab, newline, backslash)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn unescape_octal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find('\\') {
+        out.push_str(&rest[..idx]);
+        let escaped = rest.get(idx + 1..idx + 4);
+        if let Some(byte) = escaped.and_then(|oct| u8::from_str_radix(oct, 8).ok()) {
+            out.push(char::from(byte));
+            rest = &rest[idx + 4..];
+        } else {
+            out.push('\\');
+            rest = &rest[idx + 1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+40 22 0:35 / /mnt/nas rw,relatime shared:20 - nfs4 nas:/export rw
+41 40 0:36 / /mnt/nas/local rw,relatime shared:21 - tmpfs tmpfs rw
+42 22 0:37 / /home/me/remote\\040box rw,nosuid - fuse.sshfs me@box: rw
+";
+
+    #[test]
+    fn longest_mount_point_wins() {
+        assert_eq!(
+            mount_fstype(MOUNTINFO, Path::new("/mnt/nas/repo")),
+            Some("nfs4")
+        );
+        assert_eq!(
+            mount_fstype(MOUNTINFO, Path::new("/mnt/nas/local/repo")),
+            Some("tmpfs")
+        );
+        assert_eq!(
+            mount_fstype(MOUNTINFO, Path::new("/srv/repo")),
+            Some("ext4")
+        );
+    }
+
+    #[test]
+    fn mount_point_escapes_decoded() {
+        assert_eq!(
+            mount_fstype(MOUNTINFO, Path::new("/home/me/remote box/repo")),
+            Some("fuse.sshfs")
+        );
+    }
+
+    #[test]
+    fn network_fstypes() {
+        assert!(is_network_fstype("nfs4"));
+        assert!(is_network_fstype("cifs"));
+        assert!(is_network_fstype("fuse.sshfs"));
+        assert!(!is_network_fstype("ext4"));
+        assert!(!is_network_fstype("fuse.portal"));
+    }
+
+    #[test]
+    fn parse_mode() {
+        assert_eq!("auto".parse(), Ok(NetworkFs::Auto));
+        assert_eq!("on".parse(), Ok(NetworkFs::On));
+        assert_eq!("off".parse(), Ok(NetworkFs::Off));
+        assert!("yes".parse::<NetworkFs>().is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Desktop notifications (`--notify`) when a repo enters a conflict or
+//! divergent state
+//!
+//! The states a prompt last saw are kept per workspace in a small file in the
+//! cache directory, so a state raised by a rebase in another pane notifies
+//! once, at the first prompt after it, rather than at every prompt. The first
+//! prompt in a workspace only records its states.
+
+use crate::cache;
+use crate::output::RepoInfo;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::{Command, Stdio};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::thread;
+use std::time::Duration;
+
+/// States worth interrupting for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Alerts {
+    pub conflict: bool,
+    pub divergent: bool,
+}
+
+impl Alerts {
+    /// States of the collected info (a colocated repo's from either side)
+    #[must_use]
+    pub fn of(info: &RepoInfo) -> Self {
+        match info {
+            #[cfg(feature = "jj")]
+            RepoInfo::Jj(info) => Self {
+                conflict: info.conflict,
+                divergent: info.divergent,
+            },
+            #[cfg(feature = "git")]
+            RepoInfo::Git(info) => Self {
+                conflict: info.conflicted > 0,
+                divergent: false,
+            },
+            #[cfg(all(feature = "jj", feature = "git"))]
+            RepoInfo::Colocated(jj, git) => Self {
+                conflict: jj.conflict || git.conflicted > 0,
+                divergent: jj.divergent,
+            },
+            RepoInfo::Hg(_) | RepoInfo::Sapling(_) | RepoInfo::Pijul(_) => Self::default(),
+        }
+    }
+
+    /// Messages for the states set here but not in `previous`
+    fn entered(self, previous: Self) -> Vec<&'static str> {
+        let mut messages = Vec::new();
+        if self.conflict && !previous.conflict {
+            messages.push("has conflicts");
+        }
+        if self.divergent && !previous.divergent {
+            messages.push("has a divergent change");
+        }
+        messages
+    }
+
+    fn encode(self) -> String {
+        format!("{} {}\n", u8::from(self.conflict), u8::from(self.divergent))
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let mut flags = text.split_whitespace().map(|flag| flag == "1");
+        Some(Self {
+            conflict: flags.next()?,
+            divergent: flags.next()?,
+        })
+    }
+}
+
+/// Record the workspace's states in the cache directory `dir` and notify
+/// for each one it just entered
+///
+/// A workspace's first record prunes cache files older than `max_age`, so
+/// records of deleted workspaces don't pile up.
+pub fn check(dir: &Path, max_age: Option<Duration>, repo_root: &Path, info: &RepoInfo) {
+    let mut hasher = cache::StableHasher::default();
+    repo_root.hash(&mut hasher);
+    let file = dir.join(format!("{:016x}.alerts", hasher.finish()));
+    if let Some(max_age) = max_age
+        && !file.exists()
+    {
+        cache::prune(dir, max_age);
+    }
+    let name = repo_root.file_name().map_or_else(
+        || repo_root.to_string_lossy(),
+        |name| name.to_string_lossy(),
+    );
+    for message in transition(&file, Alerts::of(info)) {
+        send(&format!("{name} {message}"));
+    }
+}
+
+/// Messages for the states entered since the states stored in `file`,
+/// storing `current` when it changed
+fn transition(file: &Path, current: Alerts) -> Vec<&'static str> {
+    let previous = fs::read_to_string(file)
+        .ok()
+        .and_then(|text| Alerts::decode(&text));
+    if previous == Some(current) {
+        return Vec::new();
+    }
+    let written = file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(file, current.encode()));
+    if let Err(e) = written {
+        // Without the record every prompt would notify again
+        tracing::debug!("notify state: {e}");
+        return Vec::new();
+    }
+    previous.map_or_else(Vec::new, |previous| current.entered(previous))
+}
+
+/// Best-effort desktop notification; the notifier isn't waited for
+#[cfg(target_os = "linux")]
+fn send(body: &str) {
+    spawn(Command::new("notify-send").args(["jj-starship", body]));
+}
+
+/// Best-effort desktop notification; the notifier isn't waited for
+#[cfg(target_os = "macos")]
+fn send(body: &str) {
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!(
+        "display notification \"{}\" with title \"jj-starship\"",
+        quote(body)
+    );
+    spawn(Command::new("osascript").args(["-e", &script]));
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn send(body: &str) {
+    tracing::debug!("no notifier on this platform: {body}");
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn spawn(command: &mut Command) {
+    let spawned = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        // Reaped on a thread of its own, so `watch` doesn't collect zombies
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => tracing::debug!("notify: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLEAN: Alerts = Alerts {
+        conflict: false,
+        divergent: false,
+    };
+    const CONFLICT: Alerts = Alerts {
+        conflict: true,
+        divergent: false,
+    };
+    const BOTH: Alerts = Alerts {
+        conflict: true,
+        divergent: true,
+    };
+
+    #[test]
+    fn notifies_once_per_transition() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cache/repo.alerts");
+        assert!(
+            transition(&file, CONFLICT).is_empty(),
+            "first sight records"
+        );
+        assert!(transition(&file, CONFLICT).is_empty());
+        assert!(transition(&file, CLEAN).is_empty());
+        assert_eq!(transition(&file, CONFLICT), ["has conflicts"]);
+        assert!(transition(&file, CONFLICT).is_empty());
+        assert_eq!(transition(&file, BOTH), ["has a divergent change"]);
+        assert_eq!(Alerts::decode(&BOTH.encode()), Some(BOTH));
+    }
+}
@@ -1,256 +1,3271 @@
 //! Output formatting for prompt strings
 
-#[cfg(feature = "git")]
 use std::borrow::Cow;
-#[cfg(feature = "git")]
+use std::fmt;
 use std::fmt::Write;
+use std::str::FromStr;
 
-use crate::color::{BLUE, BRIGHT_BLACK, BRIGHT_MAGENTA, GREEN, PURPLE, RED, RESET};
-use crate::config::Config;
+use crate::budget::DEFAULT_TIMEOUT_PLACEHOLDER;
+use crate::color::{Color, Escapes, Markup, Painter, Part, RESET, State, TermColor};
+#[cfg(feature = "jj")]
+use crate::config::Backend;
+use crate::config::{
+    Config, DEFAULT_GIT_SYMBOL, DEFAULT_HG_SYMBOL, DEFAULT_JJ_SYMBOL, DEFAULT_PIJUL_SYMBOL,
+    DEFAULT_SL_SYMBOL, DisplayConfig, Element, ElementToggle, TOGGLE_REPOS,
+};
+use crate::detect::{DetectResult, RepoType};
+use crate::error::Error;
+use crate::forge::{self, Forge};
 #[cfg(feature = "git")]
 use crate::git::GitInfo;
+use crate::hg::HgInfo;
+#[cfg(feature = "jj")]
 use crate::jj::JjInfo;
+use crate::json;
+use crate::pijul::PijulInfo;
+use crate::sapling::SaplingInfo;
+#[cfg(feature = "jj")]
+use crate::text;
 
-fn format_segment(text: &str, color: &str, show_color: bool) -> String {
-    if show_color {
-        format!("{color}{text}{RESET}")
-    } else {
-        text.to_string()
+/// What `prompt` prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Styled prompt segment
+    #[default]
+    Prompt,
+    /// Versioned JSON model of the repo (see `json.rs`)
+    Json,
+    /// The JSON model as a nushell record
+    Nuon,
+    /// Prompt segment with oh-my-posh color tags, for its `command` segment
+    Omp,
+    /// Foreground, icon and zsh-escaped text lines for `p10k segment`
+    P10k,
+    /// Prompt segment with tmux style markup, for `status-right`
+    Tmux,
+    /// Prompt segment with zjstatus style markup, for zellij status bars
+    Zellij,
+    /// Self-contained powerline segment: background plus transition glyphs
+    Powerline,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prompt" => Ok(Self::Prompt),
+            "json" => Ok(Self::Json),
+            "nuon" => Ok(Self::Nuon),
+            "omp" => Ok(Self::Omp),
+            "p10k" => Ok(Self::P10k),
+            "tmux" => Ok(Self::Tmux),
+            "zellij" => Ok(Self::Zellij),
+            "powerline" => Ok(Self::Powerline),
+            _ => Err(format!(
+                "expected prompt, json, nuon, omp, p10k, tmux, zellij or powerline, got {s:?}"
+            )),
+        }
     }
 }
 
-/// Format `change_id` with unique prefix highlighting (matching jj log style)
-/// Prefix is bright magenta, rest is gray
-fn format_change_id(change_id: &str, prefix_len: usize, show_prefix_color: bool) -> String {
-    if !show_prefix_color {
-        return change_id.to_string();
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Prompt => "prompt",
+            Self::Json => "json",
+            Self::Nuon => "nuon",
+            Self::Omp => "omp",
+            Self::P10k => "p10k",
+            Self::Tmux => "tmux",
+            Self::Zellij => "zellij",
+            Self::Powerline => "powerline",
+        })
     }
-    let prefix_len = prefix_len.min(change_id.len());
-    let prefix = &change_id[..prefix_len];
-    let rest = &change_id[prefix_len..];
-    if rest.is_empty() {
-        format!("{BRIGHT_MAGENTA}{prefix}{RESET}")
-    } else {
-        format!("{BRIGHT_MAGENTA}{prefix}{RESET}{BRIGHT_BLACK}{rest}{RESET}")
+}
+
+impl OutputFormat {
+    /// Color markup for prompt-segment formats
+    #[must_use]
+    pub const fn markup(self, escapes: Escapes) -> Markup {
+        match self {
+            Self::Omp => Markup::OhMyPosh,
+            Self::P10k => Markup::Zsh,
+            Self::Tmux => Markup::Tmux,
+            Self::Zellij => Markup::Zellij,
+            Self::Powerline => Markup::Powerline(escapes),
+            Self::Prompt | Self::Json | Self::Nuon => Markup::Ansi(escapes),
+        }
+    }
+
+    /// Whether the segment starts with "on {symbol}" (p10k draws its own icon)
+    #[must_use]
+    pub const fn inline_prefix(self) -> bool {
+        !matches!(self, Self::P10k)
     }
 }
 
-/// Format JJ info as prompt string
-/// Pattern: `on {symbol}{change_id} ({bookmarks}) [{status}]`
-#[must_use = "returns formatted string, does not print"]
-pub fn format_jj(info: &JjInfo, config: &Config) -> String {
-    let mut out = String::with_capacity(128);
-    let display = &config.jj_display;
+/// Which part of the segment to print, so two-line or powerline prompts can
+/// place the name/id and the status in separate starship modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Segment {
+    /// Everything
+    #[default]
+    All,
+    /// Prefix, name and id
+    Name,
+    /// Status glyphs only
+    Status,
+}
 
-    // "on {symbol}" prefix
-    if display.show_prefix {
-        out.push_str("on ");
-        out.push_str(&format_segment(&config.jj_symbol, BLUE, display.show_color));
+impl Segment {
+    /// Hide the display elements outside this segment
+    #[must_use]
+    pub const fn apply(self, display: DisplayConfig) -> DisplayConfig {
+        match self {
+            Self::All => display,
+            Self::Name => DisplayConfig {
+                show_status: false,
+                ..display
+            },
+            Self::Status => DisplayConfig {
+                show_prefix: false,
+                show_name: false,
+                show_id: false,
+                ..display
+            },
+        }
+    }
+}
+
+impl FromStr for Segment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            "name" => Ok(Self::Name),
+            "status" => Ok(Self::Status),
+            _ => Err(format!("expected all, name or status, got {s:?}")),
+        }
     }
+}
 
-    // change_id with prefix coloring (controlled by show_id)
-    if display.show_id {
-        let use_prefix_color = display.show_color && display.show_prefix_color;
-        if use_prefix_color {
-            out.push_str(&format_change_id(
-                &info.change_id,
-                info.change_id_prefix_len,
-                true,
-            ));
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::All => "all",
+            Self::Name => "name",
+            Self::Status => "status",
+        })
+    }
+}
+
+/// Repo state that `--show-if` conditions test
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RepoState {
+    /// Any status indicator applies
+    pub dirty: bool,
+    pub conflict: bool,
+    /// JJ change id with several visible commits
+    pub divergent: bool,
+    /// Ahead of or behind the remote
+    pub unsynced: bool,
+    /// On a bookmark (or, for Git, a branch)
+    pub bookmark: bool,
+}
+
+impl RepoState {
+    /// The most urgent state, for `--state-style`
+    #[must_use]
+    pub const fn state(self) -> State {
+        if self.conflict {
+            State::Conflict
+        } else if self.divergent {
+            State::Divergent
+        } else if self.unsynced {
+            State::Unsynced
+        } else if self.dirty {
+            State::Dirty
         } else {
-            out.push_str(&format_segment(&info.change_id, PURPLE, display.show_color));
+            State::Clean
         }
     }
+}
 
-    // Bookmarks in parentheses (controlled by show_name - they're names/labels)
-    if display.show_name && !info.bookmarks.is_empty() {
-        if !out.is_empty() {
-            out.push(' ');
+/// When a `--show-if` rule shows its element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Dirty,
+    Clean,
+    Conflict,
+    Unsynced,
+    Bookmark,
+    NoBookmark,
+}
+
+impl Condition {
+    #[must_use]
+    pub const fn holds(self, state: RepoState) -> bool {
+        match self {
+            Self::Dirty => state.dirty,
+            Self::Clean => !state.dirty,
+            Self::Conflict => state.conflict,
+            Self::Unsynced => state.unsynced,
+            Self::Bookmark => state.bookmark,
+            Self::NoBookmark => !state.bookmark,
         }
+    }
+}
 
-        let total = info.bookmarks.len();
-        let limit = config.bookmarks_display_limit;
-        let show_count = if limit == 0 { total } else { limit.min(total) };
-        let hidden = total.saturating_sub(show_count);
+impl FromStr for Condition {
+    type Err = String;
 
-        let mut bookmark_strs: Vec<String> = info
-            .bookmarks
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dirty" => Ok(Self::Dirty),
+            "clean" => Ok(Self::Clean),
+            "conflict" => Ok(Self::Conflict),
+            "unsynced" => Ok(Self::Unsynced),
+            "bookmark" => Ok(Self::Bookmark),
+            "no-bookmark" => Ok(Self::NoBookmark),
+            _ => Err(format!(
+                "expected dirty, clean, conflict, unsynced, bookmark or no-bookmark, got {s:?}"
+            )),
+        }
+    }
+}
+
+/// One `--show-if` rule: `id=no-bookmark` shows the id only off a bookmark,
+/// `jj:status=conflict` limits JJ's status to conflicts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayRule {
+    pub toggle: ElementToggle,
+    pub condition: Condition,
+}
+
+impl FromStr for DisplayRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (toggle, condition) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected [repo:]element=condition, got {s:?}"))?;
+        Ok(Self {
+            toggle: toggle.parse()?,
+            condition: condition.parse()?,
+        })
+    }
+}
+
+/// Display options for `repo` after the segment filter and the `--show-if`
+/// rules: an element with rules is hidden unless one of their conditions
+/// holds
+fn displayed(
+    config: &Config,
+    repo: &str,
+    display: DisplayConfig,
+    state: RepoState,
+) -> DisplayConfig {
+    let mut display = config.segment.apply(display);
+    let hidden = |element| {
+        let mut rules = config
+            .show_if
             .iter()
-            .take(show_count)
-            .map(|(name, dist)| {
-                let stripped = config.strip_prefix(name);
-                let truncated = config.truncate(&stripped);
-                if *dist > 0 {
-                    format!("{truncated}~{dist}")
-                } else {
-                    truncated.into_owned()
-                }
-            })
-            .collect();
+            .filter(|r| r.toggle.matches(repo, element));
+        rules.clone().next().is_some() && !rules.any(|r| r.condition.holds(state))
+    };
+    for (element, shown) in [
+        (Element::Prefix, &mut display.show_prefix),
+        (Element::Name, &mut display.show_name),
+        (Element::Id, &mut display.show_id),
+        (Element::Status, &mut display.show_status),
+        (Element::Color, &mut display.show_color),
+        (Element::PrefixColor, &mut display.show_prefix_color),
+    ] {
+        *shown &= !hidden(element);
+    }
+    display
+}
 
-        if hidden > 0 {
-            bookmark_strs.push(format!("…+{hidden}"));
+/// Painter with the `--state-style` for `state` on the symbol and status
+fn state_painter(config: &Config, state: RepoState) -> Painter {
+    let mut painter = config.painter();
+    if let Some(style) = config.state_styles.get(state.state()) {
+        painter.styles.set(Part::Symbol, style);
+        painter.styles.set(Part::Status, style);
+    }
+    painter
+}
+
+/// Strings placed between rendered elements
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Between name, id and status (default: a space)
+    pub separator: Cow<'static, str>,
+    /// Between bookmark names (default: `, `)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub bookmark_separator: Cow<'static, str>,
+    /// `--prefix-word` entries; the last match wins
+    pub prefix_words: Vec<PrefixWord>,
+    /// `--affix` entries; the last match wins
+    pub affixes: Vec<Affix>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            separator: Cow::Borrowed(DEFAULT_SEPARATOR),
+            bookmark_separator: Cow::Borrowed(DEFAULT_BOOKMARK_SEPARATOR),
+            prefix_words: Vec::new(),
+            affixes: Vec::new(),
         }
+    }
+}
+
+impl Layout {
+    /// Text before the symbol of a `repo` segment: its word and a space,
+    /// or nothing for an empty word
+    #[must_use]
+    pub fn prefix_word(&self, repo: &str) -> Cow<'_, str> {
+        let scoped = self.prefix_words.iter().rev();
+        let word = scoped
+            .clone()
+            .find(|w| w.repo == Some(repo))
+            .or_else(|| scoped.clone().find(|w| w.repo.is_none()))
+            .map_or(DEFAULT_PREFIX_WORD, |w| &w.word);
+        if word.is_empty() {
+            Cow::Borrowed("")
+        } else {
+            Cow::Owned(format!("{word} "))
+        }
+    }
 
-        let bookmarks_text = format!("({})", bookmark_strs.join(", "));
-        out.push_str(&format_segment(&bookmarks_text, GREEN, display.show_color));
+    /// `text` between the `--affix` for `element` of `repo`, else between
+    /// the element's own `open` and `close` brackets
+    #[must_use]
+    pub fn wrap(
+        &self,
+        repo: &str,
+        element: Element,
+        text: &str,
+        [open, close]: [&str; 2],
+    ) -> String {
+        let entries = self.affixes.iter().rev().filter(|a| a.element == element);
+        let (prefix, suffix) = entries
+            .clone()
+            .find(|a| a.repo == Some(repo))
+            .or_else(|| entries.clone().find(|a| a.repo.is_none()))
+            .map_or((open, close), |a| (&a.prefix, &a.suffix));
+        format!("{prefix}{text}{suffix}")
     }
+}
 
-    // Status indicators in red (priority: ! > ⇔ > ? > ⇡)
-    if display.show_status {
-        let mut status = String::with_capacity(8);
-        if info.conflict {
-            status.push('!');
+pub const DEFAULT_SEPARATOR: &str = " ";
+pub const DEFAULT_BOOKMARK_SEPARATOR: &str = ", ";
+pub const DEFAULT_PREFIX_WORD: &str = "on";
+
+/// One `--affix` entry: text around the name, id or status of every repo
+/// type (`id=⟨{}⟩`), or of one (`git:status=| {}`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Affix {
+    /// None for every repo type
+    pub repo: Option<&'static str>,
+    pub element: Element,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl FromStr for Affix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, text) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected [REPO:]ELEMENT=PREFIX{{}}SUFFIX, got {s:?}"))?;
+        let ElementToggle { repo, element } = target.parse()?;
+        if !matches!(element, Element::Name | Element::Id | Element::Status) {
+            return Err(format!("expected name, id or status, got {target:?}"));
+        }
+        let (prefix, suffix) = text
+            .split_once("{}")
+            .ok_or_else(|| format!("expected {{}} where the text goes, got {text:?}"))?;
+        Ok(Self {
+            repo,
+            element,
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+        })
+    }
+}
+
+/// One `--prefix-word` entry: `WORD` for every repo type, `git:WORD` for one
+///
+/// A `:` not preceded by a repo type name is part of the word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixWord {
+    /// None for every repo type
+    pub repo: Option<&'static str>,
+    pub word: String,
+}
+
+impl FromStr for PrefixWord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scoped = s.split_once(':').and_then(|(repo, word)| {
+            let repo = TOGGLE_REPOS.into_iter().find(|&r| r == repo)?;
+            Some((repo, word))
+        });
+        Ok(match scoped {
+            Some((repo, word)) => Self {
+                repo: Some(repo),
+                word: word.to_string(),
+            },
+            None => Self {
+                repo: None,
+                word: s.to_string(),
+            },
+        })
+    }
+}
+
+/// Built-in glyph sets for the prefix symbols and status indicators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymbolSet {
+    /// Nerd Font repo icons, unicode status arrows
+    #[default]
+    Nerd,
+    /// Emoji only, for terminals without a patched font
+    Emoji,
+    /// Plain ASCII
+    Ascii,
+    /// Words, for screen readers and logs
+    Text,
+}
+
+impl SymbolSet {
+    /// JJ repo prefix
+    #[must_use]
+    pub const fn jj(self) -> &'static str {
+        match self {
+            Self::Nerd => DEFAULT_JJ_SYMBOL,
+            Self::Emoji => "🌀 ",
+            Self::Ascii | Self::Text => "jj ",
+        }
+    }
+
+    /// Git repo prefix
+    #[must_use]
+    pub const fn git(self) -> &'static str {
+        match self {
+            Self::Nerd => DEFAULT_GIT_SYMBOL,
+            Self::Emoji => "🌱 ",
+            Self::Ascii | Self::Text => "git ",
         }
-        if info.divergent {
-            status.push('⇔');
+    }
+
+    /// Mercurial repo prefix
+    #[must_use]
+    pub const fn hg(self) -> &'static str {
+        match self {
+            Self::Nerd => DEFAULT_HG_SYMBOL,
+            Self::Emoji => "☿ ",
+            Self::Ascii | Self::Text => "hg ",
         }
-        if info.empty_desc {
-            status.push('?');
+    }
+
+    /// Sapling repo prefix
+    #[must_use]
+    pub const fn sapling(self) -> &'static str {
+        match self {
+            Self::Nerd => DEFAULT_SL_SYMBOL,
+            Self::Emoji => "🌿 ",
+            Self::Ascii | Self::Text => "sl ",
         }
-        if info.has_remote && !info.is_synced {
-            status.push('⇡');
+    }
+
+    /// Pijul repo prefix
+    #[must_use]
+    pub const fn pijul(self) -> &'static str {
+        match self {
+            Self::Nerd => DEFAULT_PIJUL_SYMBOL,
+            Self::Emoji => "🐦 ",
+            Self::Ascii | Self::Text => "pijul ",
         }
+    }
 
-        if !status.is_empty() {
-            if !out.is_empty() {
-                out.push(' ');
-            }
-            let status_text = format!("[{}]", &status);
-            out.push_str(&format_segment(&status_text, RED, display.show_color));
+    /// Placeholder for a status element whose check ran out of time
+    #[must_use]
+    pub const fn timeout(self) -> &'static str {
+        match self {
+            Self::Nerd => DEFAULT_TIMEOUT_PLACEHOLDER,
+            Self::Emoji => "⏳",
+            Self::Ascii => "~",
+            Self::Text => "timeout",
         }
     }
 
-    out
+    /// Conflict glyph shaped unlike the other status glyphs, for themes
+    /// that don't color conflicts red; None when the set's is already
+    #[must_use]
+    pub const fn conflict_shape(self) -> Option<&'static str> {
+        match self {
+            Self::Nerd => Some("‼"),
+            Self::Ascii => Some("!!"),
+            Self::Emoji | Self::Text => None,
+        }
+    }
+
+    /// Status indicators
+    #[must_use]
+    pub const fn status(self) -> Symbols {
+        let [
+            conflict,
+            divergent,
+            undescribed,
+            unsynced,
+            conflicts,
+            conflicted,
+            staged,
+            modified,
+            untracked,
+            oversized,
+            deleted,
+            ahead,
+            behind,
+            lfs,
+            lfs_missing,
+            separator,
+            error,
+            busy,
+        ] = match self {
+            Self::Nerd => [
+                "!", "⇔", "?", "⇡", "⚔", "=", "+", "!", "?", "◼", "✘", "⇡", "⇣", "⛁", "⛁✘", "",
+                "⚠", "⟳",
+            ],
+            Self::Emoji => [
+                "💥", "🔀", "💬", "🔼", "⚔️", "💥", "➕", "📝", "❓", "🐘", "❌", "🔼", "🔽", "📦",
+                "📦❌", "", "⚠️", "🔄",
+            ],
+            Self::Ascii => [
+                "!", "<>", "?", "^", "X", "=", "+", "!", "?", "#", "x", "^", "v", "L", "L!", "",
+                "??", "*",
+            ],
+            Self::Text => [
+                "conflict",
+                "divergent",
+                "undescribed",
+                "unsynced",
+                "conflicts:",
+                "conflicted",
+                "staged",
+                "modified",
+                "untracked",
+                "oversized",
+                "deleted",
+                "ahead:",
+                "behind:",
+                "+lfs",
+                "lfs-missing",
+                " ",
+                "error",
+                "busy",
+            ],
+        };
+        Symbols {
+            conflict: Cow::Borrowed(conflict),
+            divergent: Cow::Borrowed(divergent),
+            undescribed: Cow::Borrowed(undescribed),
+            unsynced: Cow::Borrowed(unsynced),
+            conflicts: Cow::Borrowed(conflicts),
+            conflicted: Cow::Borrowed(conflicted),
+            staged: Cow::Borrowed(staged),
+            modified: Cow::Borrowed(modified),
+            untracked: Cow::Borrowed(untracked),
+            oversized: Cow::Borrowed(oversized),
+            deleted: Cow::Borrowed(deleted),
+            ahead: Cow::Borrowed(ahead),
+            behind: Cow::Borrowed(behind),
+            lfs: Cow::Borrowed(lfs),
+            lfs_missing: Cow::Borrowed(lfs_missing),
+            separator: Cow::Borrowed(separator),
+            error: Cow::Borrowed(error),
+            busy: Cow::Borrowed(busy),
+        }
+    }
 }
 
-/// Format Git info as prompt string
-/// Pattern: `on {symbol}{name} ({id}) [{status}]`
-#[cfg(feature = "git")]
-#[must_use = "returns formatted string, does not print"]
-pub fn format_git(info: &GitInfo, config: &Config) -> String {
-    let mut out = String::with_capacity(128);
-    let display = &config.git_display;
+impl FromStr for SymbolSet {
+    type Err = String;
 
-    // "on {symbol}" prefix
-    if display.show_prefix {
-        out.push_str("on ");
-        out.push_str(&format_segment(
-            &config.git_symbol,
-            BLUE,
-            display.show_color,
-        ));
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nerd" => Ok(Self::Nerd),
+            "emoji" => Ok(Self::Emoji),
+            "ascii" => Ok(Self::Ascii),
+            "text" => Ok(Self::Text),
+            _ => Err(format!("expected nerd, emoji, ascii or text, got {s:?}")),
+        }
     }
+}
 
-    // Name in purple (branch or HEAD)
-    if display.show_name {
-        let name: Cow<str> = info
-            .branch
-            .as_ref()
-            .map_or(Cow::Borrowed("HEAD"), |b| config.truncate(b));
-        out.push_str(&format_segment(&name, PURPLE, display.show_color));
+impl fmt::Display for SymbolSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Nerd => "nerd",
+            Self::Emoji => "emoji",
+            Self::Ascii => "ascii",
+            Self::Text => "text",
+        })
+    }
+}
+
+/// Status indicator glyphs
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(all(feature = "jj", feature = "git")), allow(dead_code))]
+pub struct Symbols {
+    /// JJ working copy has conflicts
+    pub conflict: Cow<'static, str>,
+    /// JJ change id has multiple visible commits
+    pub divergent: Cow<'static, str>,
+    /// JJ working copy has no description
+    pub undescribed: Cow<'static, str>,
+    /// JJ bookmark differs from its remote
+    pub unsynced: Cow<'static, str>,
+    /// JJ commits with conflicts in the `--conflict-count` revset (followed
+    /// by the count)
+    pub conflicts: Cow<'static, str>,
+    /// Git conflicted files
+    pub conflicted: Cow<'static, str>,
+    /// Git staged changes
+    pub staged: Cow<'static, str>,
+    /// Git unstaged modifications
+    pub modified: Cow<'static, str>,
+    /// Git untracked files
+    pub untracked: Cow<'static, str>,
+    /// Untracked files too large for jj to snapshot (colocated repos with
+    /// `--colocated-status git`)
+    pub oversized: Cow<'static, str>,
+    /// Git deleted files
+    pub deleted: Cow<'static, str>,
+    /// Git commits ahead of upstream (followed by the count)
+    pub ahead: Cow<'static, str>,
+    /// Git commits behind upstream (followed by the count)
+    pub behind: Cow<'static, str>,
+    /// After the Git branch name when the repo uses LFS
+    pub lfs: Cow<'static, str>,
+    /// Git repo uses LFS but the `lfs` filter isn't installed
+    pub lfs_missing: Cow<'static, str>,
+    /// Between indicators inside the brackets
+    pub separator: Cow<'static, str>,
+    /// Shown after the repo symbol when collection fails (empty = hide the
+    /// segment)
+    pub error: Cow<'static, str>,
+    /// A jj/git command held the repo's lock during collection
+    pub busy: Cow<'static, str>,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        SymbolSet::default().status()
     }
+}
 
-    // ID in green
-    if display.show_id {
-        if !out.is_empty() {
-            out.push(' ');
+/// One `--symbol NAME=GLYPH` override
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolOverride {
+    name: String,
+    glyph: String,
+}
+
+impl SymbolOverride {
+    /// Names accepted by `--symbol`
+    pub const NAMES: [&str; 18] = [
+        "conflict",
+        "divergent",
+        "undescribed",
+        "unsynced",
+        "conflicts",
+        "conflicted",
+        "staged",
+        "modified",
+        "untracked",
+        "oversized",
+        "deleted",
+        "ahead",
+        "behind",
+        "lfs",
+        "lfs_missing",
+        "separator",
+        "error",
+        "busy",
+    ];
+
+    /// Replace the named glyph in `symbols`
+    pub fn apply(self, symbols: &mut Symbols) {
+        let slot = match self.name.as_str() {
+            "conflict" => &mut symbols.conflict,
+            "divergent" => &mut symbols.divergent,
+            "undescribed" => &mut symbols.undescribed,
+            "unsynced" => &mut symbols.unsynced,
+            "conflicts" => &mut symbols.conflicts,
+            "conflicted" => &mut symbols.conflicted,
+            "staged" => &mut symbols.staged,
+            "modified" => &mut symbols.modified,
+            "untracked" => &mut symbols.untracked,
+            "oversized" => &mut symbols.oversized,
+            "deleted" => &mut symbols.deleted,
+            "ahead" => &mut symbols.ahead,
+            "behind" => &mut symbols.behind,
+            "lfs" => &mut symbols.lfs,
+            "lfs_missing" => &mut symbols.lfs_missing,
+            "error" => &mut symbols.error,
+            "busy" => &mut symbols.busy,
+            // "separator"; names are validated when parsed
+            _ => &mut symbols.separator,
+        };
+        *slot = Cow::Owned(self.glyph);
+    }
+}
+
+impl FromStr for SymbolOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, glyph) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=GLYPH, got {s:?}"))?;
+        if !Self::NAMES.contains(&name) {
+            return Err(format!(
+                "unknown symbol {name:?}, expected one of {}",
+                Self::NAMES.join(", ")
+            ));
         }
-        let id_text = format!("({})", &info.head_short);
-        out.push_str(&format_segment(&id_text, GREEN, display.show_color));
+        Ok(Self {
+            name: name.to_string(),
+            glyph: glyph.to_string(),
+        })
     }
+}
 
-    // Status indicators in red
-    if display.show_status {
-        let mut status = String::with_capacity(16);
+/// Counts a Git indicator must exceed to be shown (0 = any)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(not(feature = "git"), allow(dead_code))]
+pub struct CountThresholds {
+    pub conflicted: usize,
+    pub staged: usize,
+    pub modified: usize,
+    pub untracked: usize,
+    pub deleted: usize,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// One `--count-threshold NAME=N` entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountThreshold {
+    name: &'static str,
+    above: usize,
+}
+
+impl CountThreshold {
+    /// Names accepted by `--count-threshold`
+    pub const NAMES: [&str; 7] = [
+        "conflicted",
+        "staged",
+        "modified",
+        "untracked",
+        "deleted",
+        "ahead",
+        "behind",
+    ];
+
+    /// Set the named threshold in `thresholds`
+    pub fn apply(self, thresholds: &mut CountThresholds) {
+        let slot = match self.name {
+            "conflicted" => &mut thresholds.conflicted,
+            "staged" => &mut thresholds.staged,
+            "modified" => &mut thresholds.modified,
+            "untracked" => &mut thresholds.untracked,
+            "deleted" => &mut thresholds.deleted,
+            "ahead" => &mut thresholds.ahead,
+            // "behind"; names are validated when parsed
+            _ => &mut thresholds.behind,
+        };
+        *slot = self.above;
+    }
+}
+
+impl FromStr for CountThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, above) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=N, got {s:?}"))?;
+        let name = Self::NAMES
+            .into_iter()
+            .find(|&n| n == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown count {name:?}, expected one of {}",
+                    Self::NAMES.join(", ")
+                )
+            })?;
+        let above = above
+            .parse()
+            .map_err(|_| format!("expected a non-negative number, got {above:?}"))?;
+        Ok(Self { name, above })
+    }
+}
+
+/// One value for the `field` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Nearest JJ bookmark, as displayed (`main`, `feat~2`)
+    Bookmark,
+    /// JJ change id
+    ChangeId,
+    /// Git branch (absent when detached)
+    Branch,
+    /// Status indicators without brackets (absent when clean)
+    Status,
+    /// `jj`, `jj-colocated` or `git`
+    RepoType,
+    /// Workspace root (the directory holding `.jj`, `.git`, ...)
+    RepoRoot,
+    /// Underlying repo storage shared by all workspaces
+    StorePath,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bookmark" => Ok(Self::Bookmark),
+            "change-id" => Ok(Self::ChangeId),
+            "branch" => Ok(Self::Branch),
+            "status" => Ok(Self::Status),
+            "repo-type" => Ok(Self::RepoType),
+            "repo-root" => Ok(Self::RepoRoot),
+            "store-path" => Ok(Self::StorePath),
+            _ => Err(format!(
+                "expected bookmark, change-id, branch, status, repo-type, repo-root or store-path, got {s:?}"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bookmark => "bookmark",
+            Self::ChangeId => "change-id",
+            Self::Branch => "branch",
+            Self::Status => "status",
+            Self::RepoType => "repo-type",
+            Self::RepoRoot => "repo-root",
+            Self::StorePath => "store-path",
+        })
+    }
+}
 
-        // File status (order: = > + > ! > ? > ✘)
-        if info.conflicted > 0 {
-            status.push('=');
+/// Plain (uncolored) value of one field, None when it doesn't apply
+///
+/// Only `repo-type` is answered from detection alone; `info` may be None
+/// for it.
+#[must_use = "returns field value, does not print"]
+#[allow(unreachable_patterns)]
+pub fn field(
+    result: &DetectResult,
+    info: Option<&RepoInfo>,
+    field: Field,
+    config: &Config,
+) -> Option<String> {
+    let value = match (field, info) {
+        (Field::RepoType, _) => result.repo_type.name()?.to_string(),
+        (Field::RepoRoot, _) => result.repo_root.as_ref()?.to_string_lossy().into_owned(),
+        (Field::StorePath, _) => result.store_path.as_ref()?.to_string_lossy().into_owned(),
+        #[cfg(feature = "jj")]
+        (Field::Bookmark, Some(RepoInfo::Jj(info))) => {
+            let (name, distance) = info.bookmarks.first()?;
+            bookmark_label(name, *distance, config)
         }
-        if info.staged > 0 {
-            status.push('+');
+        #[cfg(feature = "jj")]
+        (Field::ChangeId, Some(RepoInfo::Jj(info))) => info.change_id.clone(),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        (Field::Bookmark, Some(RepoInfo::Colocated(info, _))) => {
+            let (name, distance) = info.bookmarks.first()?;
+            bookmark_label(name, *distance, config)
         }
-        if info.modified > 0 {
-            status.push('!');
+        #[cfg(all(feature = "jj", feature = "git"))]
+        (Field::ChangeId, Some(RepoInfo::Colocated(info, _))) => info.change_id.clone(),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        (Field::Branch, Some(RepoInfo::Colocated(_, info))) => {
+            config.name(info.branch.as_ref()?).into_owned()
         }
-        if info.untracked > 0 {
-            status.push('?');
+        (Field::Bookmark, Some(RepoInfo::Hg(info))) => {
+            config.name(info.bookmark.as_ref()?).into_owned()
         }
-        if info.deleted > 0 {
-            status.push('✘');
+        (Field::Branch, Some(RepoInfo::Hg(info))) => config.name(&info.branch).into_owned(),
+        (Field::ChangeId, Some(RepoInfo::Hg(info))) => info.node.clone(),
+        (Field::Bookmark, Some(RepoInfo::Sapling(info))) => {
+            config.name(info.bookmark.as_ref()?).into_owned()
         }
-
-        // Ahead/behind
-        if info.ahead > 0 {
-            let _ = write!(status, "⇡{}", info.ahead);
+        (Field::ChangeId, Some(RepoInfo::Sapling(info))) => info.node.clone(),
+        (Field::Branch, Some(RepoInfo::Pijul(info))) => config.name(&info.channel).into_owned(),
+        #[cfg(feature = "git")]
+        (Field::Branch, Some(RepoInfo::Git(info))) => {
+            config.name(info.branch.as_ref()?).into_owned()
         }
-        if info.behind > 0 {
-            let _ = write!(status, "⇣{}", info.behind);
+        (Field::Status, Some(info)) => {
+            status_text(&status_of(info, config), &config.symbols.separator)
         }
+        _ => return None,
+    };
+    (!value.is_empty()).then_some(value)
+}
 
-        if !status.is_empty() {
-            if !out.is_empty() {
-                out.push(' ');
+/// Collected info for the detected repo
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum RepoInfo {
+    #[cfg(feature = "jj")]
+    Jj(JjInfo),
+    #[cfg(feature = "git")]
+    Git(GitInfo),
+    Hg(HgInfo),
+    Sapling(SaplingInfo),
+    Pijul(PijulInfo),
+    /// Colocated repo collected with both backends (see `Config::colocated`)
+    #[cfg(all(feature = "jj", feature = "git"))]
+    Colocated(JjInfo, GitInfo),
+}
+
+/// Render collected info in the configured output format
+///
+/// Prompt output is None outside a repo or when collection failed; the data
+/// models are always printed so scripts can tell those cases apart.
+#[must_use = "returns formatted string, does not print"]
+pub fn render(result: &DetectResult, info: Option<&RepoInfo>, config: &Config) -> Option<String> {
+    let resolved = resolve(result, config);
+    let config = resolved.as_ref().unwrap_or(config);
+    match config.output {
+        OutputFormat::Prompt | OutputFormat::Omp | OutputFormat::Tmux | OutputFormat::Zellij => {
+            match (info, &result.dot_dir) {
+                (Some(info), _) if config.only_when_dirty && at_rest(info, config) => {
+                    Some(String::new())
+                }
+                (Some(info), _) => Some(format_info(info, config)),
+                (None, Some(dot_dir)) => Some(format_dot_dir(dot_dir, config)),
+                (None, None) => format_error(result.repo_type, &config.symbols.error, config),
             }
-            let status_text = format!("[{}]", &status);
-            out.push_str(&format_segment(&status_text, RED, display.show_color));
         }
+        OutputFormat::Powerline | OutputFormat::P10k
+            if config.only_when_dirty && info.is_some_and(|info| at_rest(info, config)) =>
+        {
+            Some(String::new())
+        }
+        OutputFormat::Powerline => info.map(|info| format_powerline(info, config)),
+        OutputFormat::P10k => info.map(|info| format_p10k(info, config)),
+        OutputFormat::Json => Some(json::render(result, info, json::Syntax::Json)),
+        OutputFormat::Nuon => Some(json::render(result, info, json::Syntax::Nuon)),
+    }
+}
+
+/// `config` with the settings that depend on the repo filled in: the forge
+/// for `--hyperlinks` and the change id colors from jj's config; None when
+/// neither applies
+fn resolve(result: &DetectResult, config: &Config) -> Option<Config> {
+    let mut resolved = None;
+    if config.hyperlinks && config.forge.is_none() {
+        resolved.get_or_insert_with(|| config.clone()).forge = Forge::for_repo(result);
+    }
+    // `--style` and a theme or starship palette color win over jj's colors
+    #[cfg(feature = "jj")]
+    if config.jj_colors
+        && paints_change_id(result.repo_type, config)
+        && let Some(root) = &result.repo_root
+    {
+        let [prefix, rest] = crate::jj::change_id_styles(root);
+        let resolved = resolved.get_or_insert_with(|| config.clone());
+        resolved.jj_colors = false;
+        let styles = &mut resolved.styles;
+        if config.palette.bright_magenta.is_none() {
+            styles.id_prefix = styles.id_prefix.or(prefix);
+        }
+        if config.palette.bright_black.is_none() {
+            styles.id_rest = styles.id_rest.or(rest);
+        }
+    }
+    resolved
+}
+
+/// Whether the output paints a JJ change id's prefix and rest, which only
+/// jj's colors settings can still change: a prompt-style output of the JJ
+/// view with prefix colors on and a part that neither `--style` nor the
+/// palette colors
+#[cfg(feature = "jj")]
+fn paints_change_id(repo_type: RepoType, config: &Config) -> bool {
+    let jj_view = match repo_type {
+        RepoType::Jj => true,
+        RepoType::JjColocated => config.colocated.view == Backend::Jj,
+        _ => false,
+    };
+    let prefix_open = config.styles.id_prefix.is_none() && config.palette.bright_magenta.is_none();
+    let rest_open = config.styles.id_rest.is_none() && config.palette.bright_black.is_none();
+    jj_view
+        && !config.verbose_words
+        && !matches!(config.output, OutputFormat::Json | OutputFormat::Nuon)
+        && config.jj_display.uses_prefix_color()
+        && (prefix_open || rest_open)
+}
+
+/// Branch names `--only-when-dirty` treats as the default branch
+pub const DEFAULT_BRANCHES: [&str; 4] = ["main", "master", "trunk", "default"];
+
+/// Whether the repo needs no attention: no status indicator and on a
+/// default branch (for JJ, a default bookmark on the working copy or an
+/// ancestor)
+fn at_rest(info: &RepoInfo, config: &Config) -> bool {
+    let default = |name: &str| DEFAULT_BRANCHES.contains(&name);
+    let on_default = match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => info.bookmarks.iter().any(|(name, _)| default(name)),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => info.branch.as_deref().is_some_and(default),
+        RepoInfo::Hg(info) => default(info.bookmark.as_ref().unwrap_or(&info.branch)),
+        RepoInfo::Sapling(info) => info.bookmark.as_deref().is_some_and(default),
+        RepoInfo::Pijul(info) => default(&info.channel),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => match config.colocated.view {
+            Backend::Jj => jj.bookmarks.iter().any(|(name, _)| default(name)),
+            Backend::Git => git.branch.as_deref().is_some_and(default),
+        },
+    };
+    on_default && status_of(info, config).is_empty()
+}
+
+/// Marker shown inside a repo's metadata dir (`GIT DIR`, `JJ DIR`, ...),
+/// like git's own prompt
+fn format_dot_dir(dot_dir: &str, config: &Config) -> String {
+    let display = match dot_dir {
+        ".jj" => config.jj_display,
+        ".git" => config.git_display,
+        ".hg" => config.hg_display,
+        ".sl" => config.sl_display,
+        _ => config.pijul_display,
+    };
+    let label = format!("{} DIR", dot_dir.trim_start_matches('.').to_uppercase());
+    format_segment(
+        &label,
+        Part::Status,
+        Color::Red,
+        display.show_color,
+        config.painter(),
+    )
+}
+
+/// Text shown in place of the error glyph for a repo written by a newer `jj`
+pub const UPDATE_HINT: &str = "(update jj-starship)";
+
+/// [`render`] for a failed collection: a repo written by a newer `jj` gets
+/// [`UPDATE_HINT`] in place of the error glyph
+#[must_use]
+#[cfg_attr(not(feature = "jj"), allow(unused_variables))]
+pub fn render_error(result: &DetectResult, error: &Error, config: &Config) -> Option<String> {
+    #[cfg(feature = "jj")]
+    if matches!(error, Error::JjFormat(_))
+        && result.dot_dir.is_none()
+        && matches!(
+            config.output,
+            OutputFormat::Prompt | OutputFormat::Omp | OutputFormat::Tmux | OutputFormat::Zellij
+        )
+    {
+        return format_error(result.repo_type, UPDATE_HINT, config);
+    }
+    render(result, None, config)
+}
+
+/// Degraded segment for a detected repo whose collection failed: the repo
+/// symbol and `text` (the error glyph), so a failure doesn't read as "not in
+/// a repo"
+fn format_error(repo_type: RepoType, text: &str, config: &Config) -> Option<String> {
+    let (repo, symbol, display) = match repo_type {
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoType::JjColocated if config.colocated.view == Backend::Git => {
+            ("git", &config.git_symbol, config.git_display)
+        }
+        RepoType::Jj | RepoType::JjColocated => ("jj", &config.jj_symbol, config.jj_display),
+        RepoType::Git => ("git", &config.git_symbol, config.git_display),
+        RepoType::Hg => ("hg", &config.hg_symbol, config.hg_display),
+        RepoType::Sapling => ("sl", &config.sl_symbol, config.sl_display),
+        RepoType::Pijul => ("pijul", &config.pijul_symbol, config.pijul_display),
+        _ => return None,
+    };
+    if text.is_empty() {
+        return None;
+    }
+    let markup = config.painter();
+    let mut out = String::with_capacity(32);
+    if display.show_prefix && config.output.inline_prefix() {
+        out.push_str(&config.layout.prefix_word(repo));
+        out.push_str(&format_segment(
+            symbol,
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+    out.push_str(&format_segment(
+        text,
+        Part::Status,
+        Color::Red,
+        display.show_color,
+        markup,
+    ));
+    Some(out)
+}
+
+/// Segment text in the configured layout (full or compact)
+fn format_info(info: &RepoInfo, config: &Config) -> String {
+    if config.verbose_words {
+        return format_words(info, config);
+    }
+    if config.compact {
+        return format_compact(info, config);
+    }
+    match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => format_jj(info, config),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => format_git(info, config),
+        RepoInfo::Hg(info) => format_hg(info, config),
+        RepoInfo::Sapling(info) => format_sapling(info, config),
+        RepoInfo::Pijul(info) => format_pijul(info, config),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => format_colocated(jj, git, config),
+    }
+}
+
+/// `--verbose-words` rendering: the segment as a plain sentence, for
+/// screen readers and logs
+/// Pattern: `{repo} repo, on {name}, {id}, {status}, ...`
+fn format_words(info: &RepoInfo, config: &Config) -> String {
+    let status = status_of(info, config);
+    let (repo, state) = state_of(info, &status, config);
+    let (display, name, id) = match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => jj_words(info, config),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => git_words(info, config),
+        RepoInfo::Hg(info) => (
+            config.hg_display,
+            Some(match &info.bookmark {
+                Some(bookmark) => format!("on bookmark {}", config.name(bookmark)),
+                None => format!("on branch {}", config.name(&info.branch)),
+            }),
+            Some(format!("commit {}", info.node)),
+        ),
+        RepoInfo::Sapling(info) => (
+            config.sl_display,
+            info.bookmark
+                .as_ref()
+                .map(|bookmark| format!("on bookmark {}", config.name(bookmark))),
+            Some(format!("commit {}", info.node)),
+        ),
+        RepoInfo::Pijul(info) => (
+            config.pijul_display,
+            Some(format!("on channel {}", config.name(&info.channel))),
+            None,
+        ),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => match config.colocated.view {
+            Backend::Jj => jj_words(jj, config),
+            Backend::Git => git_words(git, config),
+        },
+    };
+    let display = displayed(config, repo, display, state);
+
+    let mut words = Vec::with_capacity(8);
+    if display.show_prefix {
+        words.push(format!("{} repo", repo_word(repo)));
+    }
+    if display.show_name {
+        words.extend(name);
+    }
+    if display.show_id {
+        words.extend(id);
+    }
+    if display.show_status {
+        words.extend(status_words(info, config));
+    }
+    words.join(", ")
+}
+
+/// Spoken name of a repo type
+const fn repo_word(repo: &str) -> &str {
+    match repo.as_bytes() {
+        b"hg" => "mercurial",
+        b"sl" => "sapling",
+        _ => repo,
+    }
+}
+
+/// `count word`, with an `s` unless the count is one
+#[cfg(feature = "jj")]
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{count} {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+/// JJ display options, bookmarks and change id in words
+#[cfg(feature = "jj")]
+fn jj_words(info: &JjInfo, config: &Config) -> (DisplayConfig, Option<String>, Option<String>) {
+    let limit = match config.bookmarks_display_limit {
+        0 => info.bookmarks.len(),
+        limit => limit.min(info.bookmarks.len()),
+    };
+    let mut names: Vec<String> = info.bookmarks[..limit]
+        .iter()
+        .map(|(name, distance)| {
+            let name = config.name(&config.strip_prefix(name)).into_owned();
+            match distance {
+                0 => name,
+                n => format!("{name} {} back", plural(*n, "commit")),
+            }
+        })
+        .collect();
+    if info.bookmarks.len() > limit {
+        names.push(format!("{} more", info.bookmarks.len() - limit));
+    }
+    let name = match names.as_slice() {
+        [] => None,
+        [name] => Some(format!("on bookmark {name}")),
+        _ => Some(format!("on bookmarks {}", names.join(" and "))),
+    };
+    (
+        config.jj_display,
+        name,
+        Some(format!("change {}", info.change_id)),
+    )
+}
+
+/// Git display options, branch and commit in words
+#[cfg(feature = "git")]
+fn git_words(info: &GitInfo, config: &Config) -> (DisplayConfig, Option<String>, Option<String>) {
+    let mut name = match &info.branch {
+        Some(branch) => format!("on branch {}", config.name(branch)),
+        None => "detached HEAD".to_string(),
+    };
+    if info.lfs {
+        name.push_str(" with LFS");
+    }
+    (
+        config.git_display,
+        Some(name),
+        Some(format!("commit {}", info.head_short)),
+    )
+}
+
+/// Status in words, for the backend chosen like [`status_of`]
+#[cfg_attr(not(feature = "git"), allow(unused_variables))]
+fn status_words(info: &RepoInfo, config: &Config) -> Vec<String> {
+    match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => jj_status_words(info),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => git_status_words(info, config),
+        RepoInfo::Hg(_) | RepoInfo::Sapling(_) | RepoInfo::Pijul(_) => Vec::new(),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => match config.colocated.status {
+            Backend::Jj => jj_status_words(jj),
+            Backend::Git => git_status_words(git, config),
+        },
+    }
+}
+
+/// JJ status in words, in [`jj_status`] order
+#[cfg(feature = "jj")]
+fn jj_status_words(info: &JjInfo) -> Vec<String> {
+    let conflicted_commits = match info.conflicted_commits {
+        1 => "1 conflicted commit".to_string(),
+        n => format!("{n} conflicted commits"),
+    };
+    [
+        (info.op_in_progress, "operation in progress"),
+        (info.conflict, "conflicts"),
+        (info.conflicted_commits > 0, conflicted_commits.as_str()),
+        (info.divergent, "divergent"),
+        (
+            !info.divergent && info.divergence_timed_out,
+            "divergence check timed out",
+        ),
+        (info.empty_desc, "no description"),
+        (info.has_remote && !info.is_synced, "unsynced"),
+    ]
+    .into_iter()
+    .filter(|(applies, _)| *applies)
+    .map(|(_, words)| words.to_string())
+    .collect()
+}
+
+/// Git status in words with counts, in [`git_status`] order
+#[cfg(feature = "git")]
+fn git_status_words(info: &GitInfo, config: &Config) -> Vec<String> {
+    let above = &config.count_thresholds;
+    let mut words = Vec::with_capacity(8);
+    if info.op_in_progress {
+        words.push("operation in progress".to_string());
+    }
+    if info.lfs_missing {
+        words.push("LFS filter missing".to_string());
+    }
+    if info.status_timed_out {
+        words.push("status timed out".to_string());
+    }
+    for (count, threshold, word) in [
+        (info.conflicted, above.conflicted, "conflicted"),
+        (info.staged, above.staged, "staged"),
+        (info.modified, above.modified, "modified"),
+        (info.untracked, above.untracked, "untracked"),
+        (info.deleted, above.deleted, "deleted"),
+        (info.ahead, above.ahead, "ahead"),
+        (info.behind, above.behind, "behind"),
+    ] {
+        if count > threshold {
+            words.push(format!("{count} {word}"));
+        }
+    }
+    words
+}
+
+/// Colors for `--output powerline`
+#[derive(Debug, Clone, Copy)]
+pub struct Powerline {
+    /// Segment background
+    pub bg: TermColor,
+    /// Background of the segment to the left; draws the entry transition
+    pub prev: Option<TermColor>,
+    /// Background of the segment to the right (None = terminal default)
+    pub next: Option<TermColor>,
+}
+
+impl Default for Powerline {
+    fn default() -> Self {
+        Self {
+            bg: DEFAULT_POWERLINE_BG,
+            prev: None,
+            next: None,
+        }
+    }
+}
+
+pub const DEFAULT_POWERLINE_BG: TermColor = TermColor::Indexed(236);
+
+/// Powerline transition glyph (nerd font / powerline symbols)
+const POWERLINE_ARROW: &str = "\u{e0b0}";
+
+/// Full powerline segment: `{arrow in}{bg} {text} {arrow out}`
+///
+/// Element colors only reset the foreground, so the background spans the
+/// whole segment without depending on the surrounding prompt's bg handling.
+fn format_powerline(info: &RepoInfo, config: &Config) -> String {
+    let wrap = |escape: &str| config.escapes.wrap(escape);
+    let Powerline { bg, prev, next } = config.powerline;
+    let mut out = String::with_capacity(192);
+    if let Some(prev) = prev {
+        out.push_str(&wrap(&(prev.fg() + &bg.bg())));
+        out.push_str(POWERLINE_ARROW);
+    }
+    out.push_str(&wrap(&bg.bg()));
+    out.push(' ');
+    out.push_str(&format_info(info, config));
+    out.push(' ');
+    out.push_str(&wrap(RESET));
+    let exit = match next {
+        Some(next) => bg.fg() + &next.bg(),
+        None => bg.fg(),
+    };
+    out.push_str(&wrap(&exit));
+    out.push_str(POWERLINE_ARROW);
+    out.push_str(&wrap(RESET));
+    out
+}
+
+/// Compact hash length (git's minimum abbreviation)
+const MIN_ABBREV: usize = 4;
+
+/// Right-prompt variant: symbol, shortest id and the top-priority status glyph
+/// Pattern: `{symbol}{id}{glyph}`
+#[must_use = "returns formatted string, does not print"]
+pub fn format_compact(info: &RepoInfo, config: &Config) -> String {
+    let (display, symbol, id, id_color) = match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => jj_compact(info, config),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => git_compact(info, config),
+        RepoInfo::Hg(info) => (
+            config.hg_display,
+            &config.hg_symbol,
+            info.node.chars().take(MIN_ABBREV).collect::<String>(),
+            Color::Green,
+        ),
+        RepoInfo::Sapling(info) => (
+            config.sl_display,
+            &config.sl_symbol,
+            info.node.chars().take(MIN_ABBREV).collect::<String>(),
+            Color::Purple,
+        ),
+        // No id; the channel stands in for it
+        RepoInfo::Pijul(info) => (
+            config.pijul_display,
+            &config.pijul_symbol,
+            config.name(&info.channel).into_owned(),
+            Color::Purple,
+        ),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => match config.colocated.view {
+            Backend::Jj => jj_compact(jj, config),
+            Backend::Git => git_compact(git, config),
+        },
+    };
+    let status = status_of(info, config);
+
+    let (repo, state) = state_of(info, &status, config);
+    let display = displayed(config, repo, display, state);
+    let markup = state_painter(config, state);
+    let mut out = String::with_capacity(64);
+    if display.show_prefix {
+        out.push_str(&format_segment(
+            symbol.trim_end(),
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+    if display.show_id {
+        out.push_str(&format_segment(
+            &id,
+            Part::Id,
+            id_color,
+            display.show_color,
+            markup,
+        ));
+    }
+    if display.show_status
+        && let Some((glyph, _)) = status.first()
+    {
+        out.push_str(&format_segment(
+            glyph,
+            Part::Status,
+            Color::Red,
+            display.show_color,
+            markup,
+        ));
+    }
+    out
+}
+
+/// Compact display, symbol, shortest unique change id and its color
+#[cfg(feature = "jj")]
+fn jj_compact<'a>(
+    info: &JjInfo,
+    config: &'a Config,
+) -> (DisplayConfig, &'a Cow<'static, str>, String, Color) {
+    let len = info.change_id_prefix_len.max(1);
+    let color = if config.jj_display.uses_prefix_color() {
+        Color::BrightMagenta
+    } else {
+        Color::Purple
+    };
+    (
+        config.jj_display,
+        &config.jj_symbol,
+        info.change_id.chars().take(len).collect::<String>(),
+        color,
+    )
+}
+
+/// Compact display, symbol, minimum-length hash and its color
+#[cfg(feature = "git")]
+fn git_compact<'a>(
+    info: &GitInfo,
+    config: &'a Config,
+) -> (DisplayConfig, &'a Cow<'static, str>, String, Color) {
+    (
+        config.git_display,
+        &config.git_symbol,
+        info.head_short.chars().take(MIN_ABBREV).collect::<String>(),
+        Color::Green,
+    )
+}
+
+/// Starship style string for the repo state, for a `[$output]($style)`
+/// wrapper: the `--state-style` for the state, else conflicts are bold red,
+/// anything needing attention (dirty, undescribed, unsynced, divergent)
+/// yellow, clean green, in the `--theme`'s colors
+#[must_use]
+#[cfg_attr(not(all(feature = "jj", feature = "git")), allow(unused_variables))]
+pub fn starship_style(info: &RepoInfo, config: &Config) -> Cow<'static, str> {
+    let (_, state) = state_of(info, &status_of(info, config), config);
+    if let Some(style) = config.state_styles.get(state.state()) {
+        return Cow::Owned(style.to_string());
+    }
+    let (conflict, attention) = match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => jj_state(info),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => git_state(info),
+        RepoInfo::Hg(_) | RepoInfo::Sapling(_) | RepoInfo::Pijul(_) => (false, false),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => match config.colocated.status {
+            Backend::Jj => jj_state(jj),
+            Backend::Git => git_state(git),
+        },
+    };
+    let [conflicted, needs_attention, clean] = config.theme.state_styles();
+    Cow::Borrowed(if conflict {
+        conflicted
+    } else if attention {
+        needs_attention
+    } else {
+        clean
+    })
+}
+
+/// Whether the working copy has conflicts, and whether it needs attention
+#[cfg(feature = "jj")]
+fn jj_state(info: &JjInfo) -> (bool, bool) {
+    (
+        info.conflict,
+        info.divergent || info.empty_desc || (info.has_remote && !info.is_synced),
+    )
+}
+
+/// Whether the worktree has conflicts, and whether it needs attention
+#[cfg(feature = "git")]
+fn git_state(info: &GitInfo) -> (bool, bool) {
+    (
+        info.conflicted > 0,
+        info.staged + info.modified + info.untracked + info.deleted + info.ahead + info.behind > 0,
+    )
+}
+
+/// Lines for a `prompt_jj` zsh function: foreground color index, icon, and
+/// the segment text with zsh color escapes
+///
+/// The foreground is red while any status glyph applies (even when the
+/// status element is hidden), blue otherwise.
+fn format_p10k(info: &RepoInfo, config: &Config) -> String {
+    let (symbol, text) = match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => (&config.jj_symbol, format_jj(info, config)),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => (&config.git_symbol, format_git(info, config)),
+        RepoInfo::Hg(info) => (&config.hg_symbol, format_hg(info, config)),
+        RepoInfo::Sapling(info) => (&config.sl_symbol, format_sapling(info, config)),
+        RepoInfo::Pijul(info) => (&config.pijul_symbol, format_pijul(info, config)),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => {
+            let symbol = match config.colocated.view {
+                Backend::Jj => &config.jj_symbol,
+                Backend::Git => &config.git_symbol,
+            };
+            (symbol, format_colocated(jj, git, config))
+        }
+    };
+    let foreground = if status_of(info, config).is_empty() {
+        Color::Blue
+    } else {
+        Color::Red
+    };
+    format!("{}\n{}\n{text}\n", foreground.index(), symbol.trim_end())
+}
+
+/// `text` as a hyperlink to its page on the forge, under `--hyperlinks`
+/// with color on
+#[cfg(any(feature = "jj", feature = "git"))]
+fn linked(
+    text: String,
+    show_color: bool,
+    config: &Config,
+    url: impl FnOnce(&Forge) -> String,
+) -> String {
+    match &config.forge {
+        Some(forge) if config.hyperlinks && show_color => {
+            forge::hyperlink(&text, &url(forge), config.output.markup(config.escapes))
+        }
+        _ => text,
+    }
+}
+
+fn format_segment(
+    text: &str,
+    part: Part,
+    color: Color,
+    show_color: bool,
+    markup: Painter,
+) -> String {
+    if show_color {
+        markup.paint_part(text, part, color)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Format `change_id` with unique prefix highlighting (matching jj log style)
+/// Prefix is bright magenta, rest is gray, unless jj's `colors` settings
+/// say otherwise
+#[cfg(feature = "jj")]
+fn format_change_id(
+    change_id: &str,
+    prefix_len: usize,
+    show_prefix_color: bool,
+    markup: Painter,
+) -> String {
+    if !show_prefix_color {
+        return change_id.to_string();
+    }
+    let (prefix, rest) = text::split_at(change_id, prefix_len);
+    let prefix = markup.paint_part(prefix, Part::IdPrefix, Color::BrightMagenta);
+    if rest.is_empty() {
+        prefix
+    } else {
+        prefix + &markup.paint_part(rest, Part::IdRest, Color::BrightBlack)
+    }
+}
+
+/// Format JJ info as prompt string
+/// Pattern: `on {symbol}{change_id} ({bookmarks}) [{status}]`
+#[cfg(feature = "jj")]
+#[must_use = "returns formatted string, does not print"]
+pub fn format_jj(info: &JjInfo, config: &Config) -> String {
+    jj_segment(info, &jj_status(info, config), config)
+}
+
+/// JJ segment with the given status indicators
+#[cfg(feature = "jj")]
+fn jj_segment(info: &JjInfo, status: &[Indicator<'_>], config: &Config) -> String {
+    let mut out = String::with_capacity(128);
+    let state = jj_state_of(info, status);
+    let display = &displayed(config, "jj", config.jj_display, state);
+    let markup = state_painter(config, state);
+
+    // "on {symbol}" prefix
+    if display.show_prefix && config.output.inline_prefix() {
+        out.push_str(&config.layout.prefix_word("jj"));
+        out.push_str(&format_segment(
+            &config.jj_symbol,
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    // change_id with prefix coloring (controlled by show_id)
+    if display.show_id {
+        if display.uses_prefix_color() {
+            // An affix takes the id's color around the two-tone id
+            let wrapped = config.layout.wrap("jj", Element::Id, "\0", ["", ""]);
+            let (open, close) = wrapped.split_once('\0').unwrap_or_default();
+            let affix = |text: &str| {
+                if text.is_empty() {
+                    String::new()
+                } else {
+                    markup.paint_part(text, Part::Id, Color::Purple)
+                }
+            };
+            out.push_str(&affix(open));
+            out.push_str(&format_change_id(
+                &info.change_id,
+                info.change_id_prefix_len,
+                true,
+                markup,
+            ));
+            out.push_str(&affix(close));
+        } else {
+            out.push_str(&format_segment(
+                &config
+                    .layout
+                    .wrap("jj", Element::Id, &info.change_id, ["", ""]),
+                Part::Id,
+                Color::Purple,
+                display.show_color,
+                markup,
+            ));
+        }
+    }
+
+    // Bookmarks in parentheses (controlled by show_name - they're names/labels)
+    if display.show_name && !info.bookmarks.is_empty() {
+        if !out.is_empty() {
+            out.push_str(&config.layout.separator);
+        }
+
+        let total = info.bookmarks.len();
+        let limit = config.bookmarks_display_limit;
+        let show_count = if limit == 0 { total } else { limit.min(total) };
+        let hidden = total.saturating_sub(show_count);
+
+        let mut bookmark_strs: Vec<String> = info
+            .bookmarks
+            .iter()
+            .take(show_count)
+            .map(|(name, dist)| {
+                let label = bookmark_label(name, *dist, config);
+                linked(label, display.show_color, config, |forge| {
+                    forge.branch_url(name)
+                })
+            })
+            .collect();
+
+        if hidden > 0 {
+            bookmark_strs.push(format!("…+{hidden}"));
+        }
+
+        let bookmarks_text = config.layout.wrap(
+            "jj",
+            Element::Name,
+            &bookmark_strs.join(&config.layout.bookmark_separator),
+            ["(", ")"],
+        );
+        out.push_str(&format_segment(
+            &bookmarks_text,
+            Part::Name,
+            Color::Green,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    // Status indicators in red
+    if display.show_status && !status.is_empty() {
+        if !out.is_empty() {
+            out.push_str(&config.layout.separator);
+        }
+        let status_text = config.layout.wrap(
+            "jj",
+            Element::Status,
+            &status_text(status, &config.symbols.separator),
+            ["[", "]"],
+        );
+        out.push_str(&format_segment(
+            &status_text,
+            Part::Status,
+            Color::Red,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    out
+}
+
+/// Bookmark name, stripped and truncated, with `~n` when n ancestors away
+#[cfg(feature = "jj")]
+#[must_use]
+pub fn bookmark_label(name: &str, distance: usize, config: &Config) -> String {
+    let stripped = config.strip_prefix(name);
+    let truncated = config.name(&stripped);
+    if distance > 0 {
+        format!("{truncated}~{distance}")
+    } else {
+        truncated.into_owned()
+    }
+}
+
+/// Format Git info as prompt string
+/// Pattern: `on {symbol}{name} ({id}) [{status}]`
+#[cfg(feature = "git")]
+#[must_use = "returns formatted string, does not print"]
+pub fn format_git(info: &GitInfo, config: &Config) -> String {
+    git_segment(info, &git_status(info, config), config)
+}
+
+/// Git segment with the given status indicators
+#[cfg(feature = "git")]
+fn git_segment(info: &GitInfo, status: &[Indicator<'_>], config: &Config) -> String {
+    let mut out = String::with_capacity(128);
+    let state = git_state_of(info, status);
+    let display = &displayed(config, "git", config.git_display, state);
+    let markup = state_painter(config, state);
+
+    // "on {symbol}" prefix
+    if display.show_prefix && config.output.inline_prefix() {
+        out.push_str(&config.layout.prefix_word("git"));
+        out.push_str(&format_segment(
+            &config.git_symbol,
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    // Name in purple (branch or HEAD), marked when the repo uses LFS
+    if display.show_name {
+        let mut name = match &info.branch {
+            Some(branch) => linked(
+                config.name(branch).into_owned(),
+                display.show_color,
+                config,
+                |forge| forge.branch_url(branch),
+            ),
+            None => "HEAD".to_string(),
+        };
+        if info.lfs {
+            name.push_str(&config.symbols.lfs);
+        }
+        out.push_str(&format_segment(
+            &config.layout.wrap("git", Element::Name, &name, ["", ""]),
+            Part::Name,
+            Color::Purple,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    // ID in green
+    if display.show_id {
+        if !out.is_empty() {
+            out.push_str(&config.layout.separator);
+        }
+        let id = linked(
+            info.head_short.clone(),
+            display.show_color,
+            config,
+            |forge| forge.commit_url(&info.head_short),
+        );
+        let id_text = config.layout.wrap("git", Element::Id, &id, ["(", ")"]);
+        out.push_str(&format_segment(
+            &id_text,
+            Part::Id,
+            Color::Green,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    // Status indicators in red
+    if display.show_status && !status.is_empty() {
+        if !out.is_empty() {
+            out.push_str(&config.layout.separator);
+        }
+        let status_text = config.layout.wrap(
+            "git",
+            Element::Status,
+            &status_text(status, &config.symbols.separator),
+            ["[", "]"],
+        );
+        out.push_str(&format_segment(
+            &status_text,
+            Part::Status,
+            Color::Red,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    out
+}
+
+/// Format Mercurial info as prompt string
+/// Pattern: `on {symbol}{bookmark or branch} ({node})`
+#[must_use = "returns formatted string, does not print"]
+pub fn format_hg(info: &HgInfo, config: &Config) -> String {
+    let mut out = String::with_capacity(96);
+    let state = bookmark_state(info.bookmark.as_ref());
+    let display = &displayed(config, "hg", config.hg_display, state);
+    let markup = state_painter(config, state);
+
+    if display.show_prefix && config.output.inline_prefix() {
+        out.push_str(&config.layout.prefix_word("hg"));
+        out.push_str(&format_segment(
+            &config.hg_symbol,
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    // The active bookmark is the more specific name
+    if display.show_name {
+        let name = info.bookmark.as_ref().unwrap_or(&info.branch);
+        out.push_str(&format_segment(
+            &config
+                .layout
+                .wrap("hg", Element::Name, &config.name(name), ["", ""]),
+            Part::Name,
+            Color::Purple,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    if display.show_id {
+        if !out.is_empty() {
+            out.push_str(&config.layout.separator);
+        }
+        let id_text = config
+            .layout
+            .wrap("hg", Element::Id, &info.node, ["(", ")"]);
+        out.push_str(&format_segment(
+            &id_text,
+            Part::Id,
+            Color::Green,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    out
+}
+
+/// Format Sapling info as prompt string
+/// Pattern: `on {symbol}{node} ({bookmark})`
+#[must_use = "returns formatted string, does not print"]
+pub fn format_sapling(info: &SaplingInfo, config: &Config) -> String {
+    let mut out = String::with_capacity(96);
+    let state = bookmark_state(info.bookmark.as_ref());
+    let display = &displayed(config, "sl", config.sl_display, state);
+    let markup = state_painter(config, state);
+
+    if display.show_prefix && config.output.inline_prefix() {
+        out.push_str(&config.layout.prefix_word("sl"));
+        out.push_str(&format_segment(
+            &config.sl_symbol,
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    if display.show_id {
+        out.push_str(&format_segment(
+            &config.layout.wrap("sl", Element::Id, &info.node, ["", ""]),
+            Part::Id,
+            Color::Purple,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    if display.show_name
+        && let Some(bookmark) = &info.bookmark
+    {
+        if !out.is_empty() {
+            out.push_str(&config.layout.separator);
+        }
+        let bookmark_text =
+            config
+                .layout
+                .wrap("sl", Element::Name, &config.name(bookmark), ["(", ")"]);
+        out.push_str(&format_segment(
+            &bookmark_text,
+            Part::Name,
+            Color::Green,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    out
+}
+
+/// Format Pijul info as prompt string
+/// Pattern: `on {symbol}{channel}`
+#[must_use = "returns formatted string, does not print"]
+pub fn format_pijul(info: &PijulInfo, config: &Config) -> String {
+    let mut out = String::with_capacity(64);
+    let state = RepoState::default();
+    let display = &displayed(config, "pijul", config.pijul_display, state);
+    let markup = state_painter(config, state);
+
+    if display.show_prefix && config.output.inline_prefix() {
+        out.push_str(&config.layout.prefix_word("pijul"));
+        out.push_str(&format_segment(
+            &config.pijul_symbol,
+            Part::Symbol,
+            Color::Blue,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    if display.show_name {
+        out.push_str(&format_segment(
+            &config.layout.wrap(
+                "pijul",
+                Element::Name,
+                &config.name(&info.channel),
+                ["", ""],
+            ),
+            Part::Name,
+            Color::Purple,
+            display.show_color,
+            markup,
+        ));
+    }
+
+    out
+}
+
+/// Colocated repo: the view backend's segment with the status backend's
+/// indicators
+#[cfg(all(feature = "jj", feature = "git"))]
+fn format_colocated(jj: &JjInfo, git: &GitInfo, config: &Config) -> String {
+    let status = colocated_status(jj, git, config);
+    match config.colocated.view {
+        Backend::Jj => jj_segment(jj, &status, config),
+        Backend::Git => git_segment(git, &status, config),
+    }
+}
+
+/// One status indicator: its glyph and, for ahead/behind, a count
+type Indicator<'a> = (&'a str, Option<usize>);
+
+/// Indicators joined by the symbol set's separator
+fn status_text(indicators: &[Indicator<'_>], separator: &str) -> String {
+    let mut out = String::with_capacity(16);
+    for (i, (glyph, count)) in indicators.iter().enumerate() {
+        if i > 0 {
+            out.push_str(separator);
+        }
+        out.push_str(glyph);
+        if let Some(count) = count {
+            let _ = write!(out, "{count}");
+        }
+    }
+    out
+}
+
+/// Status indicators for any repo type, empty when clean or not collected
+fn status_of<'a>(info: &RepoInfo, config: &'a Config) -> Vec<Indicator<'a>> {
+    match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => jj_status(info, config),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => git_status(info, config),
+        RepoInfo::Hg(_) | RepoInfo::Sapling(_) | RepoInfo::Pijul(_) => Vec::new(),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => colocated_status(jj, git, config),
+    }
+}
+
+/// Repo type name (as in `--show-if` rules) and state, for the backend
+/// rendering the segment
+#[cfg_attr(not(all(feature = "jj", feature = "git")), allow(unused_variables))]
+fn state_of(
+    info: &RepoInfo,
+    status: &[Indicator<'_>],
+    config: &Config,
+) -> (&'static str, RepoState) {
+    match info {
+        #[cfg(feature = "jj")]
+        RepoInfo::Jj(info) => ("jj", jj_state_of(info, status)),
+        #[cfg(feature = "git")]
+        RepoInfo::Git(info) => ("git", git_state_of(info, status)),
+        RepoInfo::Hg(info) => ("hg", bookmark_state(info.bookmark.as_ref())),
+        RepoInfo::Sapling(info) => ("sl", bookmark_state(info.bookmark.as_ref())),
+        RepoInfo::Pijul(_) => ("pijul", RepoState::default()),
+        #[cfg(all(feature = "jj", feature = "git"))]
+        RepoInfo::Colocated(jj, git) => match config.colocated.view {
+            Backend::Jj => ("jj", jj_state_of(jj, status)),
+            Backend::Git => ("git", git_state_of(git, status)),
+        },
+    }
+}
+
+/// State of a backend with no status: whether a bookmark is active
+fn bookmark_state(bookmark: Option<&String>) -> RepoState {
+    RepoState {
+        bookmark: bookmark.is_some(),
+        ..RepoState::default()
+    }
+}
+
+/// JJ state; only a bookmark on the working copy itself counts
+#[cfg(feature = "jj")]
+fn jj_state_of(info: &JjInfo, status: &[Indicator<'_>]) -> RepoState {
+    RepoState {
+        dirty: !status.is_empty(),
+        conflict: info.conflict,
+        divergent: info.divergent,
+        unsynced: info.has_remote && !info.is_synced,
+        bookmark: info.bookmarks.iter().any(|(_, distance)| *distance == 0),
+    }
+}
+
+/// Git state; a detached HEAD has no bookmark
+#[cfg(feature = "git")]
+fn git_state_of(info: &GitInfo, status: &[Indicator<'_>]) -> RepoState {
+    RepoState {
+        dirty: !status.is_empty(),
+        conflict: info.conflicted > 0,
+        divergent: false,
+        unsynced: info.ahead + info.behind > 0,
+        bookmark: info.branch.is_some(),
+    }
+}
+
+/// Indicators of the backend chosen by `config.colocated.status`
+#[cfg(all(feature = "jj", feature = "git"))]
+fn colocated_status<'a>(jj: &JjInfo, git: &GitInfo, config: &'a Config) -> Vec<Indicator<'a>> {
+    match config.colocated.status {
+        Backend::Jj => jj_status(jj, config),
+        Backend::Git => git_status(git, config),
+    }
+}
+
+/// JJ status indicators (priority: conflict > divergent > undescribed >
+/// unsynced), empty when clean
+#[cfg(feature = "jj")]
+fn jj_status<'a>(info: &JjInfo, config: &'a Config) -> Vec<Indicator<'a>> {
+    let symbols = &config.symbols;
+    let mut status = Vec::with_capacity(4);
+    if info.op_in_progress {
+        status.push((&*symbols.busy, None));
+    }
+    if info.conflict {
+        status.push((&*symbols.conflict, None));
+    }
+    if info.conflicted_commits > 0 {
+        status.push((&*symbols.conflicts, Some(info.conflicted_commits)));
+    }
+    if info.divergent {
+        status.push((&*symbols.divergent, None));
+    } else if info.divergence_timed_out {
+        status.push((&*config.budgets.placeholder, None));
+    }
+    if info.empty_desc {
+        status.push((&*symbols.undescribed, None));
+    }
+    if info.has_remote && !info.is_synced {
+        status.push((&*symbols.unsynced, None));
+    }
+    status
+}
+
+/// Git status indicators, empty when clean; counts at or below their
+/// `--count-threshold` are left out
+#[cfg(feature = "git")]
+fn git_status<'a>(info: &GitInfo, config: &'a Config) -> Vec<Indicator<'a>> {
+    let symbols = &config.symbols;
+    let above = &config.count_thresholds;
+    let mut status = Vec::with_capacity(8);
+    if info.op_in_progress {
+        status.push((&*symbols.busy, None));
+    }
+    // Always shown: checkouts leave LFS pointer files in place of content
+    if info.lfs_missing {
+        status.push((&*symbols.lfs_missing, None));
+    }
+
+    // File status (order: conflicted > staged > modified > untracked >
+    // deleted), or the placeholder if the scan ran over its budget
+    if info.status_timed_out {
+        status.push((&*config.budgets.placeholder, None));
+    }
+    for (count, threshold, glyph) in [
+        (info.conflicted, above.conflicted, &symbols.conflicted),
+        (info.staged, above.staged, &symbols.staged),
+        (info.modified, above.modified, &symbols.modified),
+        (info.untracked, above.untracked, &symbols.untracked),
+        (info.deleted, above.deleted, &symbols.deleted),
+    ] {
+        if count > threshold {
+            status.push((&**glyph, None));
+        }
+    }
+    // Always shown: jj's next snapshot fails on these
+    if info.oversized > 0 {
+        status.push((&*symbols.oversized, None));
+    }
+
+    // Ahead/behind
+    if info.ahead > above.ahead {
+        status.push((&*symbols.ahead, Some(info.ahead)));
+    }
+    if info.behind > above.behind {
+        status.push((&*symbols.behind, Some(info.behind)));
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[cfg(any(feature = "jj", feature = "git"))]
+    use crate::color::PURPLE;
+    #[cfg(any(feature = "jj", feature = "git"))]
+    use crate::color::Theme;
+    use crate::color::{BLUE, GREEN, RED, RESET};
+    #[cfg(feature = "jj")]
+    use crate::color::{BRIGHT_BLACK, BRIGHT_MAGENTA, StateStyles, Style};
+
+    #[cfg(feature = "jj")]
+    use crate::budget::Budgets;
+    #[cfg(feature = "git")]
+    use crate::config::DEFAULT_GIT_SYMBOL;
+    #[cfg(feature = "jj")]
+    use crate::config::DEFAULT_JJ_SYMBOL;
+
+    #[allow(dead_code)]
+    fn default_config() -> Config {
+        Config::default()
+    }
+
+    #[allow(dead_code)]
+    fn no_symbol_config() -> Config {
+        Config {
+            truncate_name: 0,
+            id_length: 8,
+            ancestor_bookmark_depth: 10,
+            bookmarks_display_limit: 0, // unlimited for tests
+            strip_bookmark_prefix: Vec::new(),
+            jj_symbol: Cow::Borrowed(""),
+            git_symbol: Cow::Borrowed(""),
+            hg_symbol: Cow::Borrowed(""),
+            sl_symbol: Cow::Borrowed(""),
+            pijul_symbol: Cow::Borrowed(""),
+            jj_display: DisplayConfig::all_visible(),
+            git_display: DisplayConfig::all_visible(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_dot_dir_marker() {
+        let result = DetectResult {
+            repo_type: RepoType::Hg,
+            repo_root: Some("/repo".into()),
+            store_path: Some("/repo/.hg".into()),
+            dot_dir: Some(".hg".into()),
+            nested_roots: vec!["/repo".into()],
+        };
+        assert_eq!(
+            render(&result, None, &default_config()),
+            Some(format!("{RED}HG DIR{RESET}"))
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn jj_colors_are_only_read_when_painted() {
+        let mut config = Config::default();
+        assert!(paints_change_id(RepoType::Jj, &config));
+        assert!(paints_change_id(RepoType::JjColocated, &config));
+        assert!(!paints_change_id(RepoType::Git, &config));
+
+        config.colocated.view = Backend::Git;
+        assert!(!paints_change_id(RepoType::JjColocated, &config));
+        config.colocated.view = Backend::Jj;
+
+        config.output = OutputFormat::Json;
+        assert!(!paints_change_id(RepoType::Jj, &config));
+        config.output = OutputFormat::Powerline;
+        assert!(paints_change_id(RepoType::Jj, &config));
+
+        config.jj_display.show_color = false;
+        assert!(!paints_change_id(RepoType::Jj, &config));
+        config.jj_display.show_color = true;
+
+        config.styles.id_prefix = Some(Style::default());
+        assert!(paints_change_id(RepoType::Jj, &config));
+        config.styles.id_rest = Some(Style::default());
+        assert!(!paints_change_id(RepoType::Jj, &config));
+    }
+
+    #[test]
+    fn test_error_format() {
+        let result = DetectResult {
+            repo_type: RepoType::Pijul,
+            repo_root: Some("/repo".into()),
+            store_path: Some("/repo/.pijul".into()),
+            dot_dir: None,
+            nested_roots: vec!["/repo".into()],
+        };
+        let mut config = no_symbol_config();
+        assert_eq!(
+            render(&result, None, &config),
+            Some(format!("on {BLUE}{RESET}{RED}⚠{RESET}"))
+        );
+        config.symbols.error = Cow::Borrowed("");
+        assert_eq!(render(&result, None, &config), None);
+        assert_eq!(render(&DetectResult::default(), None, &config), None);
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_newer_format_hint() {
+        let result = DetectResult {
+            repo_type: RepoType::Jj,
+            repo_root: Some("/repo".into()),
+            store_path: Some("/repo/.jj/repo".into()),
+            dot_dir: None,
+            nested_roots: vec!["/repo".into()],
+        };
+        let mut config = no_symbol_config();
+        let format = Error::JjFormat("Unsupported op_store backend type 'v2'".into());
+        assert_eq!(
+            render_error(&result, &format, &config),
+            Some(format!("on {BLUE}{RESET}{RED}(update jj-starship){RESET}"))
+        );
+        // Other failures keep the error glyph
+        let other = Error::Jj("load workspace: gone".into());
+        assert_eq!(
+            render_error(&result, &other, &config),
+            render(&result, None, &config)
+        );
+        config.output = OutputFormat::Json;
+        assert_eq!(
+            render_error(&result, &format, &config),
+            render(&result, None, &config)
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_clean() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: false,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        assert_eq!(
+            format_jj(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{BRIGHT_MAGENTA}yzxv{RESET}{BRIGHT_BLACK}1234{RESET} {GREEN}(main){RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_high_contrast() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: false,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            palette: Theme::HighContrast.palette(),
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            "on \x1b[38;5;12m\x1b[0m\x1b[38;5;13myzxv\x1b[0m\x1b[38;5;15m1234\x1b[0m \x1b[38;5;10m(main)\x1b[0m"
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_verbose_words() {
+        let info = RepoInfo::Jj(JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0), ("feat".into(), 1), ("old".into(), 3)],
+            empty_desc: true,
+            conflict: true,
+            conflicted_commits: 2,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: false,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        });
+        let config = Config {
+            verbose_words: true,
+            bookmarks_display_limit: 2,
+            ..Config::default()
+        };
+        assert_eq!(
+            format_info(&info, &config),
+            "jj repo, on bookmarks main and feat 1 commit back and 1 more, change yzxv1234, conflicts, 2 conflicted commits, no description, unsynced"
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_conflicted_commits() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![],
+            empty_desc: false,
+            conflict: true,
+            conflicted_commits: 3,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(format_jj(&info, &config), "on yzxv1234 [!⚔3]");
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_divergence_timed_out() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: true,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            budgets: Budgets {
+                placeholder: Cow::Borrowed("~"),
+                ..Budgets::default()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(format_jj(&info, &config), "on yzxv1234 [~?]");
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_fields() {
+        let result = DetectResult {
+            repo_type: RepoType::Jj,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let info = RepoInfo::Jj(JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("feat".into(), 2)],
+            empty_desc: false,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: false,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        });
+        let config = no_symbol_config();
+        let get = |name| field(&result, Some(&info), name, &config);
+        assert_eq!(get(Field::Bookmark).as_deref(), Some("feat~2"));
+        assert_eq!(get(Field::ChangeId).as_deref(), Some("yzxv1234"));
+        assert_eq!(get(Field::RepoType).as_deref(), Some("jj"));
+        // Clean: status is absent, as is the git-only branch
+        assert_eq!(get(Field::Status), None);
+        assert_eq!(get(Field::Branch), None);
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_symbol_sets() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 2,
+            untracked: 1,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 3,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        let mut config = Config {
+            git_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            symbols: SymbolSet::Text.status(),
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_git(&info, &config),
+            "on main (a3b4c5d) [modified untracked ahead:3]"
+        );
+
+        config.symbols = SymbolSet::Ascii.status();
+        "modified=*"
+            .parse::<SymbolOverride>()
+            .unwrap()
+            .apply(&mut config.symbols);
+        assert_eq!(format_git(&info, &config), "on main (a3b4c5d) [*?^3]");
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_omp() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            output: OutputFormat::Omp,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            "on <blue></><lightMagenta>yzxv</><darkGray>1234</> <green>(main)</> <red>[?]</>"
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_tmux() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 1,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        let config = Config {
+            output: OutputFormat::Tmux,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_git(&info, &config),
+            "on #[fg=colour4]#[fg=default]#[fg=colour5]main#[fg=default] \
+             #[fg=colour2](a3b4c5d)#[fg=default] #[fg=colour1][⇡1]#[fg=default]"
+        );
+    }
+
+    #[test]
+    fn test_names_are_sanitized() {
+        let info = HgInfo {
+            branch: "100%\x1b[2Jfix#1".into(),
+            bookmark: None,
+            node: "0123abcd".into(),
+        };
+        let mut config = Config {
+            hg_display: DisplayConfig {
+                show_color: false,
+                show_id: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(format_hg(&info, &config), "on 100%\u{fffd}[2Jfix#1");
+        config.escapes = Escapes::Zsh;
+        assert_eq!(format_hg(&info, &config), "on 100%%\u{fffd}[2Jfix#1");
+        config.output = OutputFormat::Tmux;
+        assert_eq!(format_hg(&info, &config), "on 100%\u{fffd}[2Jfix##1");
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_hyperlinks() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        let mut config = Config {
+            hyperlinks: true,
+            forge: Forge::from_remote("git@github.com:o/r.git"),
+            ..no_symbol_config()
+        };
+        let out = format_git(&info, &config);
+        assert!(out.contains("\x1b]8;;https://github.com/o/r/tree/main\x1b\\main\x1b]8;;\x1b\\"));
+        assert!(out.contains("\x1b]8;;https://github.com/o/r/commit/a3b4c5d\x1b\\a3b4c5d"));
+
+        config.git_display.show_color = false;
+        assert_eq!(format_git(&info, &config), "on main (a3b4c5d)");
+    }
+
+    #[test]
+    fn test_prefix_words() {
+        let info = HgInfo {
+            branch: "default".into(),
+            bookmark: None,
+            node: "0123abcd".into(),
+        };
+        let mut config = Config {
+            hg_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        config.layout.prefix_words = vec!["sur".parse().unwrap()];
+        assert_eq!(format_hg(&info, &config), "sur default (0123abcd)");
+        config.layout.prefix_words.push("hg:".parse().unwrap());
+        assert_eq!(format_hg(&info, &config), "default (0123abcd)");
+        assert_eq!(config.layout.prefix_word("git"), "sur ");
+        assert_eq!(
+            "at:".parse(),
+            Ok(PrefixWord {
+                repo: None,
+                word: "at:".into(),
+            }),
+            "only a repo type scopes the word"
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_affixes() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let mut config = no_symbol_config();
+        config.layout.affixes = vec![
+            "id=⟨{}⟩".parse().unwrap(),
+            "status=| {}".parse().unwrap(),
+            "git:status=<{}>".parse().unwrap(),
+        ];
+        assert_eq!(
+            format_jj(&info, &config),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}⟨{RESET}{BRIGHT_MAGENTA}yzxv{RESET}{BRIGHT_BLACK}1234{RESET}\
+                 {PURPLE}⟩{RESET} {GREEN}(main){RESET} {RED}| ?{RESET}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_hg_format() {
+        let info = HgInfo {
+            branch: "default".into(),
+            bookmark: None,
+            node: "0123abcd".into(),
+        };
+        let config = Config {
+            output: OutputFormat::Tmux,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_hg(&info, &config),
+            "on #[fg=colour4]#[fg=default]#[fg=colour5]default#[fg=default] \
+             #[fg=colour2](0123abcd)#[fg=default]"
+        );
+
+        // The active bookmark is shown over the branch, which stays a field
+        let info = HgInfo {
+            bookmark: Some("feat".into()),
+            ..info
+        };
+        let config = Config {
+            hg_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..Config::default()
+        };
+        assert_eq!(
+            format_hg(&info, &config),
+            format!("on {DEFAULT_HG_SYMBOL}feat (0123abcd)")
+        );
+        let result = DetectResult {
+            repo_type: RepoType::Hg,
+            repo_root: None,
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let info = RepoInfo::Hg(info);
+        assert_eq!(
+            field(&result, Some(&info), Field::Branch, &config).as_deref(),
+            Some("default")
+        );
+    }
+
+    #[test]
+    fn test_pijul_format() {
+        let info = PijulInfo {
+            channel: "feature".into(),
+        };
+        let config = Config {
+            output: OutputFormat::Tmux,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_pijul(&info, &config),
+            "on #[fg=colour4]#[fg=default]#[fg=colour5]feature#[fg=default]"
+        );
+        let config = Config {
+            segment: Segment::Status,
+            ..no_symbol_config()
+        };
+        assert_eq!(format_pijul(&info, &config), "");
+    }
+
+    #[test]
+    fn test_sapling_format() {
+        let info = SaplingInfo {
+            bookmark: Some("main".into()),
+            node: "0123abcd".into(),
+        };
+        let config = Config {
+            output: OutputFormat::Tmux,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_sapling(&info, &config),
+            "on #[fg=colour4]#[fg=default]#[fg=colour5]0123abcd#[fg=default] \
+             #[fg=colour2](main)#[fg=default]"
+        );
+
+        let info = RepoInfo::Sapling(SaplingInfo {
+            bookmark: None,
+            ..info
+        });
+        let config = Config {
+            compact: true,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_info(&info, &config),
+            "\x1b[34m\x1b[0m\x1b[35m0123\x1b[0m"
+        );
+        assert_eq!(starship_style(&info, &Config::default()), "green");
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_powerline() {
+        let info = RepoInfo::Git(GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        });
+        let config = Config {
+            output: OutputFormat::Powerline,
+            powerline: Powerline {
+                bg: TermColor::Indexed(236),
+                prev: Some(TermColor::Indexed(4)),
+                next: None,
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_powerline(&info, &config),
+            "\x1b[38;5;4m\x1b[48;5;236m\u{e0b0}\x1b[48;5;236m on \x1b[34m\x1b[39m\x1b[35mmain\x1b[39m \
+             \x1b[32m(a3b4c5d)\x1b[39m \x1b[0m\x1b[38;5;236m\u{e0b0}\x1b[0m"
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_zellij() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![],
+            empty_desc: false,
+            conflict: true,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            output: OutputFormat::Zellij,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            "on #[fg=4]#[fg=13]yzxv#[fg=8]1234 #[fg=1][!]"
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_starship_style() {
+        let info = |modified, conflicted, behind| {
+            RepoInfo::Git(GitInfo {
+                branch: Some("main".into()),
+                head_short: "a3b4c5d".into(),
+                staged: 0,
+                modified,
+                untracked: 0,
+                oversized: 0,
+                deleted: 0,
+                conflicted,
+                status_timed_out: false,
+                ahead: 0,
+                behind,
+                op_in_progress: false,
+                lfs: false,
+                lfs_missing: false,
+            })
+        };
+        assert_eq!(
+            starship_style(&info(1, 1, 0), &Config::default()),
+            "bold red"
+        );
+        assert_eq!(starship_style(&info(0, 0, 2), &Config::default()), "yellow");
+        assert_eq!(starship_style(&info(0, 0, 0), &Config::default()), "green");
+
+        let colorblind = Config {
+            theme: Theme::Colorblind,
+            ..Config::default()
+        };
+        assert_eq!(
+            starship_style(&info(1, 1, 0), &colorblind),
+            "bold fg:#d55e00"
+        );
+        assert_eq!(starship_style(&info(0, 0, 0), &colorblind), "fg:#0072b2");
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_compact() {
+        let info = RepoInfo::Jj(JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 2,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: true,
+            conflict: true,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        });
+        let config = Config {
+            compact: true,
+            jj_symbol: Cow::Borrowed("J "),
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_compact(&info, &config),
+            format!("{BLUE}J{RESET}{BRIGHT_MAGENTA}yz{RESET}{RED}!{RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_verbose_words() {
+        let info = RepoInfo::Git(GitInfo {
+            branch: None,
+            head_short: "a3b4c5d".into(),
+            staged: 1,
+            modified: 3,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 2,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        });
+        let mut config = Config {
+            verbose_words: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            format_info(&info, &config),
+            "git repo, detached HEAD, commit a3b4c5d, 1 staged, 3 modified, 2 ahead"
+        );
+        config.git_display.show_prefix = false;
+        config.git_display.show_id = false;
+        assert_eq!(
+            format_info(&info, &config),
+            "detached HEAD, 1 staged, 3 modified, 2 ahead"
+        );
     }
 
-    out
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::borrow::Cow;
-
     #[cfg(feature = "git")]
-    use crate::config::DEFAULT_GIT_SYMBOL;
-    use crate::config::DEFAULT_JJ_SYMBOL;
-    use crate::config::DisplayConfig;
-
-    #[allow(dead_code)]
-    fn default_config() -> Config {
-        Config::default()
+    #[test]
+    fn test_git_format_compact() {
+        let info = RepoInfo::Git(GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 2,
+            behind: 1,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        });
+        let config = Config {
+            compact: true,
+            git_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(format_compact(&info, &config), "a3b4⇡");
     }
 
-    #[allow(dead_code)]
-    fn no_symbol_config() -> Config {
-        Config {
-            truncate_name: 0,
-            id_length: 8,
-            ancestor_bookmark_depth: 10,
-            bookmarks_display_limit: 0, // unlimited for tests
-            strip_bookmark_prefix: Vec::new(),
-            jj_symbol: Cow::Borrowed(""),
-            git_symbol: Cow::Borrowed(""),
-            jj_display: DisplayConfig::all_visible(),
-            git_display: DisplayConfig::all_visible(),
-        }
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_custom_separators() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0), ("dev".into(), 0)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            layout: Layout {
+                separator: Cow::Borrowed(" • "),
+                bookmark_separator: Cow::Borrowed("|"),
+                ..Layout::default()
+            },
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(format_jj(&info, &config), "on yzxv1234 • (main|dev) • [?]");
     }
 
+    #[cfg(feature = "jj")]
     #[test]
-    fn test_jj_format_clean() {
+    fn test_jj_format_segments() {
         let info = JjInfo {
             change_id: "yzxv1234".into(),
             change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 1)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = |segment| Config {
+            segment,
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config(Segment::Name)),
+            "on yzxv1234 (main~1)"
+        );
+        assert_eq!(format_jj(&info, &config(Segment::Status)), "[?]");
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_state_styles() {
+        let mut info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 0,
             bookmarks: vec![("main".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
-            has_remote: true,
+            divergence_timed_out: false,
+            has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let yellow = Style {
+            fg: Some(TermColor::Indexed(3)),
+            ..Style::default()
+        };
+        let config = Config {
+            state_styles: StateStyles {
+                divergent: Some(yellow),
+                clean: Some(Style {
+                    fg: Some(TermColor::Indexed(2)),
+                    ..Style::default()
+                }),
+                ..StateStyles::default()
+            },
+            jj_display: DisplayConfig {
+                show_prefix_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            jj_symbol: Cow::Borrowed("@"),
+            ..no_symbol_config()
         };
         assert_eq!(
-            format_jj(&info, &no_symbol_config()),
+            format_jj(&info, &config),
+            format!("on \x1b[38;5;2m@{RESET}{PURPLE}yzxv1234{RESET} {GREEN}(main){RESET}")
+        );
+
+        info.divergent = true;
+        assert_eq!(
+            format_jj(&info, &config),
             format!(
-                "on {BLUE}{RESET}{BRIGHT_MAGENTA}yzxv{RESET}{BRIGHT_BLACK}1234{RESET} {GREEN}(main){RESET}"
+                "on \x1b[38;5;3m@{RESET}{PURPLE}yzxv1234{RESET} {GREEN}(main){RESET} \x1b[38;5;3m[⇔]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "jj")]
+    #[test]
+    fn test_jj_format_show_if() {
+        let mut info = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: false,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let config = Config {
+            show_if: ["id=no-bookmark", "jj:status=conflict", "status=unsynced"]
+                .iter()
+                .map(|rule| rule.parse().unwrap())
+                .collect(),
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        assert_eq!(format_jj(&info, &config), "on  (main)");
+
+        info.bookmarks = vec![("main".into(), 1)];
+        info.conflict = true;
+        assert_eq!(format_jj(&info, &config), "on yzxv1234 (main~1) [!?]");
+
+        info.conflict = false;
+        info.has_remote = true;
+        info.is_synced = false;
+        assert_eq!(format_jj(&info, &config), "on yzxv1234 (main~1) [?⇡]");
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_only_when_dirty() {
+        let info = |branch: &str, behind| {
+            RepoInfo::Git(GitInfo {
+                branch: Some(branch.into()),
+                head_short: "a3b4c5d".into(),
+                staged: 0,
+                modified: 0,
+                untracked: 0,
+                oversized: 0,
+                deleted: 0,
+                conflicted: 0,
+                status_timed_out: false,
+                ahead: 0,
+                behind,
+                op_in_progress: false,
+                lfs: false,
+                lfs_missing: false,
+            })
+        };
+        let config = Config {
+            only_when_dirty: true,
+            git_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..no_symbol_config()
+        };
+        let result = DetectResult::default();
+        assert_eq!(
+            render(&result, Some(&info("main", 0)), &config).as_deref(),
+            Some("")
+        );
+        assert_eq!(
+            render(&result, Some(&info("main", 2)), &config).as_deref(),
+            Some("on main (a3b4c5d) [⇣2]"),
+            "behind the remote"
+        );
+        assert_eq!(
+            render(&result, Some(&info("feature", 0)), &config).as_deref(),
+            Some("on feature (a3b4c5d)"),
+            "off the default branch"
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_p10k() {
+        let info = RepoInfo::Git(GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 1,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        });
+        let config = Config {
+            output: OutputFormat::P10k,
+            ..default_config()
+        };
+        assert_eq!(
+            format_p10k(&info, &config),
+            format!(
+                "1\n{}\n%F{{5}}main%f %F{{2}}(a3b4c5d)%f %F{{1}}[!]%f\n",
+                DEFAULT_GIT_SYMBOL.trim_end()
             )
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_dirty() {
         // When no bookmarks, only change_id is shown
@@ -260,9 +3275,14 @@ mod tests {
             bookmarks: vec![],
             empty_desc: true,
             conflict: true,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -272,6 +3292,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_with_symbol() {
         let info = JjInfo {
@@ -280,9 +3301,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: true,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &default_config()),
@@ -292,6 +3318,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_truncated() {
         let config = Config {
@@ -304,6 +3331,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         let info = JjInfo {
             change_id: "yzxv1234".into(),
@@ -311,9 +3339,14 @@ mod tests {
             bookmarks: vec![("very-long-bookmark-name".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -323,6 +3356,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_ancestor_bookmark() {
         let info = JjInfo {
@@ -331,9 +3365,14 @@ mod tests {
             bookmarks: vec![("main".into(), 3)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: true,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -343,6 +3382,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_no_bookmarks() {
         let info = JjInfo {
@@ -351,9 +3391,14 @@ mod tests {
             bookmarks: vec![],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -361,6 +3406,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_multiple_bookmarks() {
         let info = JjInfo {
@@ -369,9 +3415,14 @@ mod tests {
             bookmarks: vec![("feature".into(), 1), ("main".into(), 2)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -381,6 +3432,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_no_color() {
         let info = JjInfo {
@@ -389,9 +3441,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: true,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -410,10 +3467,12 @@ mod tests {
                 show_prefix_color: true,
             },
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(format_jj(&info, &config), "on 󱗆 yzxv1234 (main)");
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_no_id_hides_change_id() {
         let info = JjInfo {
@@ -422,9 +3481,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -443,6 +3507,7 @@ mod tests {
                 show_prefix_color: true,
             },
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         // --no-jj-id hides change_id, shows only bookmarks
         assert_eq!(
@@ -451,6 +3516,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_no_name_hides_bookmarks() {
         let info = JjInfo {
@@ -459,9 +3525,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -480,6 +3551,7 @@ mod tests {
                 show_prefix_color: true,
             },
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         // --no-jj-name hides bookmarks, shows only change_id with prefix coloring
         assert_eq!(
@@ -488,6 +3560,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_direct_bookmark_distance_zero() {
         // Verifies that when WC has a direct bookmark (distance 0),
@@ -498,9 +3571,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0)], // distance 0 = directly on WC
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -519,10 +3597,15 @@ mod tests {
             staged: 0,
             modified: 0,
             untracked: 0,
+            oversized: 0,
             deleted: 0,
             conflicted: 0,
+            status_timed_out: false,
             ahead: 0,
             behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
         };
         assert_eq!(
             format_git(&info, &no_symbol_config()),
@@ -539,10 +3622,15 @@ mod tests {
             staged: 2,
             modified: 3,
             untracked: 1,
+            oversized: 0,
             deleted: 0,
             conflicted: 0,
+            status_timed_out: false,
             ahead: 2,
             behind: 1,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
         };
         assert_eq!(
             format_git(&info, &no_symbol_config()),
@@ -552,6 +3640,188 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_lfs() {
+        let mut info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 0,
+            op_in_progress: false,
+            lfs: true,
+            lfs_missing: false,
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!("on {BLUE}{RESET}{PURPLE}main⛁{RESET} {GREEN}(a3b4c5d){RESET}")
+        );
+        info.lfs_missing = true;
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}main⛁{RESET} {GREEN}(a3b4c5d){RESET} {RED}[⛁✘]{RESET}"
+            )
+        );
+        let config = Config {
+            verbose_words: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            format_info(&RepoInfo::Git(info), &config),
+            "git repo, on branch main with LFS, commit a3b4c5d, LFS filter missing"
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_count_thresholds() {
+        let info = GitInfo {
+            branch: Some("feature".into()),
+            head_short: "1234567".into(),
+            staged: 2,
+            modified: 3,
+            untracked: 1,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 2,
+            behind: 1,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        let mut count_thresholds = CountThresholds::default();
+        for t in ["behind=1", "ahead=1", "modified=3"] {
+            t.parse::<CountThreshold>()
+                .unwrap()
+                .apply(&mut count_thresholds);
+        }
+        let config = Config {
+            count_thresholds,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_git(&info, &config),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}feature{RESET} {GREEN}(1234567){RESET} {RED}[+?⇡2]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(all(feature = "jj", feature = "git"))]
+    #[test]
+    fn test_colocated_format() {
+        use crate::config::{Backend, Colocated};
+        let jj = JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0)],
+            empty_desc: false,
+            conflict: false,
+            conflicted_commits: 0,
+            divergent: false,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        };
+        let git = GitInfo {
+            branch: Some("main".into()),
+            head_short: "1234567".into(),
+            staged: 0,
+            modified: 3,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        let mut config = no_symbol_config();
+        config.colocated = Colocated {
+            view: Backend::Jj,
+            status: Backend::Git,
+        };
+        assert_eq!(
+            format_colocated(&jj, &git, &config),
+            format!(
+                "on {BLUE}{RESET}{BRIGHT_MAGENTA}yzxv{RESET}{BRIGHT_BLACK}1234{RESET} {GREEN}(main){RESET} {RED}[!]{RESET}"
+            )
+        );
+        config.colocated.view = Backend::Git;
+        assert_eq!(
+            format_colocated(&jj, &git, &config),
+            format!("on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(1234567){RESET} {RED}[!]{RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_status_timed_out() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "1234567".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: true,
+            ahead: 1,
+            behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(1234567){RESET} {RED}[⧗⇡1]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_op_in_progress() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "1234567".into(),
+            staged: 0,
+            modified: 2,
+            untracked: 0,
+            oversized: 0,
+            deleted: 0,
+            conflicted: 0,
+            status_timed_out: false,
+            ahead: 0,
+            behind: 0,
+            op_in_progress: true,
+            lfs: false,
+            lfs_missing: false,
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!("on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(1234567){RESET} {RED}[⟳!]{RESET}")
+        );
+    }
+
     #[cfg(feature = "git")]
     #[test]
     fn test_git_format_with_symbol() {
@@ -561,10 +3831,15 @@ mod tests {
             staged: 0,
             modified: 0,
             untracked: 0,
+            oversized: 0,
             deleted: 0,
             conflicted: 0,
+            status_timed_out: false,
             ahead: 0,
             behind: 0,
+            op_in_progress: false,
+            lfs: false,
+            lfs_missing: false,
         };
         assert_eq!(
             format_git(&info, &default_config()),
@@ -574,6 +3849,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_bookmarks_display_limit() {
         let info = JjInfo {
@@ -588,9 +3864,14 @@ mod tests {
             ],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -602,6 +3883,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -611,6 +3893,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_bookmarks_display_limit_exact() {
         // When limit equals count, no overflow indicator
@@ -620,9 +3903,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0), ("feat".into(), 1)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -634,6 +3922,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -643,6 +3932,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_bookmarks_display_limit_zero_unlimited() {
         // limit=0 means unlimited
@@ -657,9 +3947,14 @@ mod tests {
             ],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -671,6 +3966,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -680,6 +3976,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_bookmarks_display_limit_one() {
         let info = JjInfo {
@@ -688,9 +3985,14 @@ mod tests {
             bookmarks: vec![("main".into(), 0), ("feat".into(), 1), ("other".into(), 2)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -702,6 +4004,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -711,6 +4014,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_strip_bookmark_prefix_single() {
         let info = JjInfo {
@@ -723,9 +4027,14 @@ mod tests {
             ],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -737,6 +4046,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -746,6 +4056,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_strip_bookmark_prefix_multiple() {
         let info = JjInfo {
@@ -758,9 +4069,14 @@ mod tests {
             ],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 0,
@@ -772,6 +4088,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -781,6 +4098,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jj")]
     #[test]
     fn test_jj_format_strip_bookmark_prefix_with_truncate() {
         // Prefix strip happens before truncation
@@ -790,9 +4108,14 @@ mod tests {
             bookmarks: vec![("dmmulroy/very-long-feature-name".into(), 0)],
             empty_desc: false,
             conflict: false,
+            conflicted_commits: 0,
             divergent: false,
+            divergence_timed_out: false,
             has_remote: false,
             is_synced: true,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
         };
         let config = Config {
             truncate_name: 10,
@@ -804,6 +4127,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         // "very-long-feature-name" after strip → truncate to 10 → "very-long…"
         assert_eq!(
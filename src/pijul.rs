@@ -0,0 +1,72 @@
+//! Pijul info collection - reads `.pijul/config`, no `pijul` subprocess
+//!
+//! Only the current channel is shown; there's no cheap stand-in for a
+//! commit id or status without loading the pristine.
+
+use crate::error::Result;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Channel used when the config doesn't name one
+const DEFAULT_CHANNEL: &str = "main";
+
+/// Pijul repo info
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PijulInfo {
+    /// Current channel
+    pub channel: String,
+}
+
+/// Collect Pijul info for the repo rooted at `repo_root`
+///
+/// # Errors
+///
+/// Returns an error if `.pijul/config` exists but can't be read.
+pub fn collect(repo_root: &Path) -> Result<PijulInfo> {
+    let config = match fs::read_to_string(repo_root.join(".pijul/config")) {
+        Ok(config) => config,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(PijulInfo {
+        channel: current_channel(&config)
+            .unwrap_or(DEFAULT_CHANNEL)
+            .to_string(),
+    })
+}
+
+/// Top-level `current_channel = "..."` from the TOML config
+fn current_channel(config: &str) -> Option<&str> {
+    config
+        .lines()
+        .map(str::trim)
+        // Keys after the first table header belong to that table
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "current_channel").then(|| value.trim().trim_matches(['"', '\'']))
+        })
+        .filter(|channel| !channel.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_current_channel() {
+        let config = "unrecord_changes = 1\ncurrent_channel = \"feature\"\n\n[hooks]\n";
+        assert_eq!(current_channel(config), Some("feature"));
+        assert_eq!(current_channel("[extra]\ncurrent_channel = \"x\"\n"), None);
+        assert_eq!(current_channel("current_channel = ''"), None);
+    }
+
+    #[test]
+    fn defaults_to_main() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join(".pijul")).unwrap();
+        assert_eq!(collect(tmp.path()).unwrap().channel, "main");
+    }
+}
@@ -0,0 +1,81 @@
+//! Sapling info collection - direct reads of `.sl`, no `sl` subprocess
+//!
+//! Only the working copy parent and the active bookmark are read, so the
+//! segment is basic: no status or remote tracking.
+
+use crate::error::{Error, Result};
+use crate::hg::{dirstate_error, read_bookmark, read_parent};
+use crate::text;
+use std::path::Path;
+
+/// Sapling repo info
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SaplingInfo {
+    /// Active bookmark, None when no bookmark is active
+    pub bookmark: Option<String>,
+    /// Working copy parent hash, truncated to `id_length`
+    pub node: String,
+}
+
+/// Collect Sapling info for the repo rooted at `repo_root`
+///
+/// # Errors
+///
+/// Returns an error if the dirstate can't be read or is truncated.
+pub fn collect(repo_root: &Path, id_length: usize) -> Result<SaplingInfo> {
+    let dot_dir = repo_root.join(".sl");
+    let node = read_parent(&dot_dir).map_err(|e| dirstate_error(e, Error::Sapling))?;
+    let node = text::prefix(&node, id_length).to_string();
+    Ok(SaplingInfo {
+        bookmark: read_bookmark(&dot_dir)?,
+        node,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn repo(dirstate: &[u8], bookmark: Option<&str>) -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let dot_dir = tmp.path().join(".sl");
+        fs::create_dir(&dot_dir).unwrap();
+        fs::write(dot_dir.join("dirstate"), dirstate).unwrap();
+        if let Some(bookmark) = bookmark {
+            fs::write(dot_dir.join("bookmarks.current"), bookmark).unwrap();
+        }
+        tmp
+    }
+
+    #[test]
+    fn reads_parent_and_bookmark() {
+        let mut dirstate = vec![0xab; 20];
+        dirstate.extend([0; 20]);
+        dirstate.extend(b"\0\0\0\x02treestate");
+        let tmp = repo(&dirstate, Some("main\n"));
+        let info = collect(tmp.path(), 8).unwrap();
+        assert_eq!(
+            info,
+            SaplingInfo {
+                bookmark: Some("main".into()),
+                node: "abababab".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn no_active_bookmark() {
+        let tmp = repo(&[0x01; 40], None);
+        assert_eq!(collect(tmp.path(), 40).unwrap().bookmark, None);
+        let tmp = repo(&[0x01; 40], Some(""));
+        assert_eq!(collect(tmp.path(), 40).unwrap().bookmark, None);
+    }
+
+    #[test]
+    fn truncated_dirstate_is_an_error() {
+        let tmp = repo(&[0x01; 12], None);
+        assert!(matches!(collect(tmp.path(), 8), Err(Error::Sapling(_))));
+    }
+}
@@ -0,0 +1,174 @@
+//! Repo scale estimation for large-repo safe mode
+//!
+//! Only fixed-size file headers are read (git index header, pack index fanout
+//! tables), so estimating scale costs a handful of syscalls regardless of size.
+//! Repos on network filesystems skip estimation and use the fast path.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Expensive collection steps, each skipped when the repo exceeds a threshold
+#[derive(Debug, Clone, Copy)]
+pub struct Checks {
+    /// Worktree status scan (git file counts)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub dirty: bool,
+    /// Ahead/behind graph walk against upstream (git)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub ahead_behind: bool,
+    /// Divergent change lookup (jj)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub divergence: bool,
+}
+
+impl Checks {
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    #[must_use]
+    pub const fn all() -> Self {
+        Self {
+            dirty: true,
+            ahead_behind: true,
+            divergence: true,
+        }
+    }
+
+    /// Ref-only reads: no worktree scan, no history walks
+    #[must_use]
+    pub const fn fast_path() -> Self {
+        Self {
+            dirty: false,
+            ahead_behind: false,
+            divergence: false,
+        }
+    }
+}
+
+/// How much work collection may do
+#[derive(Debug, Clone, Copy, Hash)]
+pub enum CollectMode {
+    /// Run every check the repo's scale allows
+    Scaled(Thresholds),
+    /// Ref-only reads (network filesystems)
+    FastPath,
+}
+
+impl CollectMode {
+    /// Decide which checks to run for the repo backed by `git_dir`
+    #[must_use = "returns enabled checks, does not modify state"]
+    pub fn checks(self, git_dir: &Path) -> Checks {
+        match self {
+            Self::Scaled(thresholds) => thresholds.checks(git_dir),
+            Self::FastPath => Checks::fast_path(),
+        }
+    }
+}
+
+/// Large-repo thresholds (0 = unlimited)
+#[derive(Debug, Clone, Copy, Hash)]
+pub struct Thresholds {
+    /// Max tracked files (git index entries) before skipping the dirty scan
+    pub files: usize,
+    /// Max packed objects before skipping history walks (ahead/behind, divergence)
+    pub objects: usize,
+}
+
+impl Thresholds {
+    /// Decide which checks are affordable for the repo backed by `git_dir`
+    ///
+    /// Headers are only read for enabled thresholds.
+    #[must_use = "returns enabled checks, does not modify state"]
+    pub fn checks(self, git_dir: &Path) -> Checks {
+        let files_ok =
+            self.files == 0 || index_entries(git_dir).is_none_or(|n| n <= self.files as u64);
+        let objects_ok = self.objects == 0
+            || packed_objects(&common_dir(git_dir).join("objects")) <= self.objects as u64;
+
+        Checks {
+            dirty: files_ok,
+            ahead_behind: objects_ok,
+            divergence: objects_ok,
+        }
+    }
+}
+
+/// Object store location - linked worktrees point at it via a `commondir` file
+fn common_dir(git_dir: &Path) -> PathBuf {
+    fs::read_to_string(git_dir.join("commondir"))
+        .map_or_else(|_| git_dir.to_path_buf(), |rel| git_dir.join(rel.trim()))
+}
+
+/// Entry count from the git index header (`DIRC`, version, entries)
+fn index_entries(git_dir: &Path) -> Option<u64> {
+    let mut header = [0u8; 12];
+    File::open(git_dir.join("index"))
+        .ok()?
+        .read_exact(&mut header)
+        .ok()?;
+    parse_index_entries(&header)
+}
+
+fn parse_index_entries(header: &[u8]) -> Option<u64> {
+    if header.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let entries: [u8; 4] = header.get(8..12)?.try_into().ok()?;
+    Some(u64::from(u32::from_be_bytes(entries)))
+}
+
+/// Total objects across all pack indexes (loose objects are ignored)
+fn packed_objects(objects_dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(objects_dir.join("pack")) else {
+        return 0;
+    };
+    entries
+        .filter_map(std::result::Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "idx"))
+        .filter_map(|e| {
+            // v2 header (8 bytes) + 256-entry fanout table
+            let mut buf = [0u8; 8 + 256 * 4];
+            File::open(e.path()).ok()?.read_exact(&mut buf).ok()?;
+            parse_pack_idx_count(&buf)
+        })
+        .sum()
+}
+
+/// Object count from the last fanout entry of a pack index (v1 or v2)
+fn parse_pack_idx_count(buf: &[u8]) -> Option<u64> {
+    let fanout_start = if buf.starts_with(b"\xfftOc") { 8 } else { 0 };
+    let last = fanout_start + 255 * 4;
+    let count: [u8; 4] = buf.get(last..last + 4)?.try_into().ok()?;
+    Some(u64::from(u32::from_be_bytes(count)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_header_entries() {
+        let header = [b'D', b'I', b'R', b'C', 0, 0, 0, 2, 0, 1, 0x86, 0xa0];
+        assert_eq!(parse_index_entries(&header), Some(100_000));
+    }
+
+    #[test]
+    fn index_header_bad_magic() {
+        let header = [0u8; 12];
+        assert_eq!(parse_index_entries(&header), None);
+    }
+
+    #[test]
+    fn pack_idx_v2_count() {
+        let mut buf = vec![0u8; 8 + 256 * 4];
+        buf[..8].copy_from_slice(b"\xfftOc\0\0\0\x02");
+        buf[8 + 255 * 4..].copy_from_slice(&42u32.to_be_bytes());
+        assert_eq!(parse_pack_idx_count(&buf), Some(42));
+    }
+
+    #[test]
+    fn pack_idx_v1_count() {
+        let mut buf = vec![0u8; 256 * 4];
+        buf[255 * 4..].copy_from_slice(&7u32.to_be_bytes());
+        assert_eq!(parse_pack_idx_count(&buf), Some(7));
+    }
+}
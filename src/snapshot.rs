@@ -0,0 +1,358 @@
+//! On-disk JJ snapshot keyed by the head operation id
+//!
+//! jj-starship never snapshots the working copy, so everything it renders is
+//! a function of the head operation. While the op head is unchanged, the last
+//! result is rendered from a small file without loading the repo at all.
+
+use crate::cache;
+use crate::jj::{self, JjInfo};
+use std::fs::{self, File, TryLockError};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Format tag; bump when the encoding changes
+const MAGIC: &[u8; 4] = b"JSS3";
+
+/// Longest wait for another prompt's collection before collecting alongside it
+const LOCK_WAIT: Duration = Duration::from_millis(200);
+
+/// Interval between lock attempts while waiting
+const LOCK_POLL: Duration = Duration::from_millis(10);
+
+/// Hash of every option that changes what `jj::collect` returns
+#[must_use = "returns fingerprint, does not modify state"]
+pub fn fingerprint(options: &impl Hash) -> u64 {
+    let mut hasher = cache::StableHasher::default();
+    options.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Cache entry location and validity key for one workspace
+#[derive(Debug)]
+pub struct SnapshotKey {
+    file: PathBuf,
+    repo_root: String,
+    op_id: String,
+    fingerprint: u64,
+    /// Age past which [`store`](Self::store) prunes other entries
+    max_age: Option<Duration>,
+}
+
+impl SnapshotKey {
+    /// Key for the workspace at `repo_root` at its current op head, in the
+    /// cache directory `dir`
+    ///
+    /// None if the op head can't be read cheaply (including multiple heads,
+    /// which need a merge).
+    #[must_use = "returns snapshot key, does not modify state"]
+    pub fn new(
+        repo_root: &Path,
+        dir: &Path,
+        max_age: Option<Duration>,
+        fingerprint: u64,
+    ) -> Option<Self> {
+        let op_id = head_op_id(repo_root)?;
+        let repo_root = repo_root.to_str()?.to_string();
+        let file = dir.join(format!("{:016x}.snap", self::fingerprint(&repo_root)));
+        Some(Self {
+            file,
+            repo_root,
+            op_id,
+            fingerprint,
+            max_age,
+        })
+    }
+
+    /// Snapshot written at the same op head with the same options
+    #[must_use = "returns cached info, does not modify state"]
+    pub fn load(&self) -> Option<JjInfo> {
+        decode(&fs::read(&self.file).ok()?, self, false)
+    }
+
+    /// Snapshot written with the same options at any op head
+    #[must_use = "returns cached info, does not modify state"]
+    pub fn load_stale(&self) -> Option<JjInfo> {
+        decode(&fs::read(&self.file).ok()?, self, true)
+    }
+
+    /// Exclusive lock on this workspace's entry, shared by every shell
+    ///
+    /// Waits up to [`LOCK_WAIT`] while another prompt is collecting the same
+    /// workspace, so a burst of prompts (e.g. tmux panes redrawing) collects
+    /// once and the rest re-read its snapshot. None if it's still held then
+    /// (the holder may be a low-priority warm process), and the caller
+    /// collects without it. Released when the returned file is dropped.
+    #[must_use = "the lock is released when the file is dropped"]
+    pub fn lock(&self) -> Option<File> {
+        let open = || {
+            fs::create_dir_all(self.file.parent()?).ok()?;
+            File::options()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(self.file.with_extension("lock"))
+                .ok()
+        };
+        let file = open()?;
+        let deadline = Instant::now() + LOCK_WAIT;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Some(file),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(LOCK_POLL);
+                }
+                Err(e) => {
+                    tracing::debug!("snapshot lock: {e}");
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Best-effort write; results with timed-out checks aren't stored
+    ///
+    /// Also prunes cache files older than the cache max age.
+    pub fn store(&self, info: &JjInfo) {
+        if info.divergence_timed_out {
+            return;
+        }
+        let Some(dir) = self.file.parent() else {
+            return;
+        };
+        // Write-then-rename so concurrent prompts never read a torn file
+        let tmp = self
+            .file
+            .with_extension(format!("tmp{}", std::process::id()));
+        let written = fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&tmp, encode(self, info)))
+            .and_then(|()| fs::rename(&tmp, &self.file));
+        if let Err(e) = written {
+            tracing::debug!("snapshot store: {e}");
+            let _ = fs::remove_file(&tmp);
+            return;
+        }
+        if let Some(max_age) = self.max_age {
+            cache::prune(dir, max_age);
+        }
+    }
+}
+
+/// The single op head's id, from the op heads directory listing
+fn head_op_id(repo_root: &Path) -> Option<String> {
+    let repo_dir = jj::repo_dir(repo_root)?;
+    let mut heads = fs::read_dir(repo_dir.join("op_heads/heads")).ok()?;
+    let head = heads.next()?.ok()?.file_name().into_string().ok()?;
+    heads.next().is_none().then_some(head)
+}
+
+fn encode(key: &SnapshotKey, info: &JjInfo) -> Vec<u8> {
+    let mut out = Vec::with_capacity(256);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&key.fingerprint.to_le_bytes());
+    put_str(&mut out, &key.op_id);
+    put_str(&mut out, &key.repo_root);
+
+    put_str(&mut out, &info.change_id);
+    put_len(&mut out, info.change_id_prefix_len);
+    let flags = [
+        info.empty_desc,
+        info.conflict,
+        info.divergent,
+        info.has_remote,
+        info.is_synced,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0u8, |acc, (bit, &set)| acc | (u8::from(set) << bit));
+    out.push(flags);
+    put_len(&mut out, info.bookmarks.len());
+    for (name, distance) in &info.bookmarks {
+        put_str(&mut out, name);
+        put_len(&mut out, *distance);
+    }
+    put_str(&mut out, &info.backend);
+    put_len(&mut out, info.conflicted_commits);
+    out
+}
+
+/// The snapshot if it matches `key`, at any op head when `any_op`
+fn decode(bytes: &[u8], key: &SnapshotKey, any_op: bool) -> Option<JjInfo> {
+    let mut r = Reader(bytes);
+    if r.take(4)? != MAGIC
+        || r.u64()? != key.fingerprint
+        || (r.str()? != key.op_id && !any_op)
+        || r.str()? != key.repo_root
+    {
+        return None;
+    }
+
+    let change_id = r.str()?.to_string();
+    let change_id_prefix_len = r.len()?;
+    let flags = r.take(1)?[0];
+    let flag = |bit: u8| flags & (1 << bit) != 0;
+    let count = r.len()?;
+    let mut bookmarks = Vec::with_capacity(count.min(64));
+    for _ in 0..count {
+        bookmarks.push((r.str()?.to_string(), r.len()?));
+    }
+    let backend = r.str()?.to_string();
+    let conflicted_commits = r.len()?;
+    r.0.is_empty().then_some(JjInfo {
+        change_id,
+        change_id_prefix_len,
+        bookmarks,
+        empty_desc: flag(0),
+        conflict: flag(1),
+        conflicted_commits,
+        divergent: flag(2),
+        divergence_timed_out: false,
+        has_remote: flag(3),
+        is_synced: flag(4),
+        op_in_progress: false,
+        stale: false,
+        backend,
+    })
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Cursor over an encoded snapshot; every read is bounds-checked
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.0.len() {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        self.u64()?.try_into().ok()
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.len()?;
+        std::str::from_utf8(self.take(len)?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(op_id: &str, fingerprint: u64) -> SnapshotKey {
+        SnapshotKey {
+            file: PathBuf::new(),
+            repo_root: "/src/repo".into(),
+            op_id: op_id.into(),
+            fingerprint,
+            max_age: None,
+        }
+    }
+
+    fn info() -> JjInfo {
+        JjInfo {
+            change_id: "yzxv1234".into(),
+            change_id_prefix_len: 4,
+            bookmarks: vec![("main".into(), 0), ("feat".into(), 3)],
+            empty_desc: true,
+            conflict: false,
+            conflicted_commits: 2,
+            divergent: true,
+            divergence_timed_out: false,
+            has_remote: true,
+            is_synced: false,
+            op_in_progress: false,
+            stale: false,
+            backend: "git".into(),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let key = key("abc", 7);
+        assert_eq!(decode(&encode(&key, &info()), &key, false), Some(info()));
+    }
+
+    #[test]
+    fn stale_op_or_options_miss() {
+        let bytes = encode(&key("abc", 7), &info());
+        assert_eq!(decode(&bytes, &key("def", 7), false), None);
+        assert_eq!(decode(&bytes, &key("abc", 8), false), None);
+    }
+
+    #[test]
+    fn stale_read_ignores_only_the_op() {
+        let bytes = encode(&key("abc", 7), &info());
+        assert_eq!(decode(&bytes, &key("def", 7), true), Some(info()));
+        assert_eq!(decode(&bytes, &key("def", 8), true), None);
+    }
+
+    #[test]
+    fn truncated_snapshot_misses() {
+        let key = key("abc", 7);
+        let bytes = encode(&key, &info());
+        assert_eq!(decode(&bytes[..bytes.len() - 1], &key, false), None);
+    }
+
+    #[test]
+    fn lock_is_exclusive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key = SnapshotKey {
+            file: tmp.path().join("cache/repo.snap"),
+            ..key("abc", 7)
+        };
+        let held = key.lock().unwrap();
+        let other = File::options()
+            .write(true)
+            .open(tmp.path().join("cache/repo.lock"))
+            .unwrap();
+        assert!(other.try_lock().is_err());
+        drop(held);
+        assert!(other.try_lock().is_ok());
+    }
+
+    #[test]
+    fn held_lock_gives_up_after_the_wait() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key = SnapshotKey {
+            file: tmp.path().join("cache/repo.snap"),
+            ..key("abc", 7)
+        };
+        let held = key.lock().unwrap();
+        let start = Instant::now();
+        assert!(key.lock().is_none());
+        assert!(start.elapsed() >= LOCK_WAIT);
+        drop(held);
+        assert!(key.lock().is_some());
+    }
+
+    #[test]
+    fn single_op_head() {
+        let tmp = tempfile::tempdir().unwrap();
+        let heads = tmp.path().join(".jj/repo/op_heads/heads");
+        fs::create_dir_all(&heads).unwrap();
+        fs::write(heads.join("aaaa"), "").unwrap();
+        assert_eq!(head_op_id(tmp.path()), Some("aaaa".into()));
+
+        // Concurrent operations: needs a merge, not cacheable
+        fs::write(heads.join("bbbb"), "").unwrap();
+        assert_eq!(head_op_id(tmp.path()), None);
+    }
+}
@@ -0,0 +1,251 @@
+//! Colors from the user's starship.toml
+//!
+//! The active palette's `red`, `green`, `blue`, `purple` (or `magenta`),
+//! `bright-purple` and `bright-black` replace jj-starship's colors of the
+//! same name, and the foreground of the `[custom.jj]` style replaces the
+//! repo symbol's color (blue), like a native module's `$symbol`, so the
+//! segment follows the prompt theme.
+//!
+//! [`style`] parses starship's style strings, for `--style`.
+
+use crate::color::{Palette, Style, TermColor};
+use crate::config::home_dir;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+/// Style words that aren't colors
+const MODIFIERS: &[&str] = &[
+    "bold",
+    "italic",
+    "underline",
+    "dimmed",
+    "inverted",
+    "blink",
+    "hidden",
+    "strikethrough",
+    "none",
+];
+
+/// `$STARSHIP_CONFIG`, else `~/.config/starship.toml`
+#[must_use]
+pub fn path() -> Option<PathBuf> {
+    env::var_os("STARSHIP_CONFIG")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config/starship.toml")))
+}
+
+/// Palette from the starship.toml at [`path()`]; the default palette when
+/// it's missing or doesn't parse (logged at warn)
+#[must_use]
+pub fn load_palette() -> Palette {
+    let Some(path) = path() else {
+        return Palette::default();
+    };
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Palette::default();
+    };
+    palette(&text).unwrap_or_else(|e| {
+        tracing::warn!("{}: {e}", path.display());
+        Palette::default()
+    })
+}
+
+/// Named colors of the active palette in the starship.toml at [`path()`],
+/// empty when there's none
+#[must_use]
+pub fn palette_colors() -> Table {
+    let Some(text) = path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Table::new();
+    };
+    text.parse()
+        .map(|starship| active_colors(&starship))
+        .unwrap_or_default()
+}
+
+/// Palette from starship.toml contents
+///
+/// # Errors
+///
+/// Returns the TOML syntax error.
+pub fn palette(starship_toml: &str) -> Result<Palette, toml::de::Error> {
+    let starship: Table = starship_toml.parse()?;
+    let colors = active_colors(&starship);
+    let defined = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| color(colors.get(*name)?.as_str()?, &colors))
+    };
+    let style = starship
+        .get("custom")
+        .and_then(|custom| custom.get("jj")?.get("style")?.as_str());
+
+    Ok(Palette {
+        purple: defined(&["purple", "magenta"]),
+        green: defined(&["green"]),
+        red: defined(&["red"]),
+        blue: style
+            .and_then(|style| foreground(style, &colors))
+            .or_else(|| defined(&["blue"])),
+        bright_magenta: defined(&["bright-purple", "bright-magenta"]),
+        bright_black: defined(&["bright-black"]),
+    })
+}
+
+/// Entries of the palette named by the top-level `palette` key
+fn active_colors(starship: &Table) -> Table {
+    starship
+        .get("palette")
+        .and_then(Value::as_str)
+        .and_then(|name| starship.get("palettes")?.get(name)?.as_table())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Parse a starship style string (`bold fg:#ff8800 bg:color_aqua`), with
+/// `colors` naming palette entries
+///
+/// `inverted`, `blink`, `hidden` and `strikethrough` are accepted and
+/// ignored.
+///
+/// # Errors
+///
+/// Returns a message naming the first word that isn't a modifier or color.
+pub fn style(spec: &str, colors: &Table) -> Result<Style, String> {
+    let mut style = Style::default();
+    for word in spec.split_whitespace() {
+        match word {
+            "bold" => style.bold = true,
+            "italic" => style.italic = true,
+            "underline" => style.underline = true,
+            "dimmed" => style.dimmed = true,
+            "none" | "bg:none" | "fg:none" => {}
+            _ if MODIFIERS.contains(&word) => {}
+            _ => {
+                let (slot, spec) = match word.split_once(':') {
+                    Some(("bg", spec)) => (&mut style.bg, spec),
+                    Some(("fg", spec)) => (&mut style.fg, spec),
+                    _ => (&mut style.fg, word),
+                };
+                *slot = Some(color(spec, colors).ok_or_else(|| format!("unknown color {spec:?}"))?);
+            }
+        }
+    }
+    Ok(style)
+}
+
+/// Foreground color of a starship style string (`bold fg:#ff8800 bg:blue`)
+fn foreground(style: &str, colors: &Table) -> Option<TermColor> {
+    style
+        .split_whitespace()
+        .filter(|word| !MODIFIERS.contains(word) && !word.starts_with("bg:"))
+        .find_map(|word| color(word.strip_prefix("fg:").unwrap_or(word), colors))
+}
+
+/// A starship color: a palette entry, a color name, `#rrggbb` or 0-255
+fn color(spec: &str, colors: &Table) -> Option<TermColor> {
+    let spec = colors.get(spec).and_then(Value::as_str).unwrap_or(spec);
+    let (bright, name) = match spec.strip_prefix("bright-") {
+        Some(name) => (8, name),
+        None => (0, spec),
+    };
+    let base = match name {
+        "purple" => Some(5),
+        _ => match name.parse() {
+            Ok(TermColor::Indexed(n)) if n < 8 && !name.starts_with(char::is_numeric) => Some(n),
+            Ok(color) if bright == 0 => return Some(color),
+            _ => None,
+        },
+    };
+    base.map(|n| TermColor::Indexed(n + bright))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_palette_and_module_style() {
+        let palette = palette(
+            r##"
+palette = "mocha"
+
+[palettes.mocha]
+red = "#f38ba8"
+green = "#a6e3a1"
+mauve = "#cba6f7"
+bright-black = "244"
+
+[palettes.latte]
+blue = "#1e66f5"
+
+[custom.jj]
+style = "bold fg:mauve bg:black"
+"##,
+        )
+        .unwrap();
+        assert_eq!(
+            palette,
+            Palette {
+                purple: None,
+                green: Some(TermColor::Rgb(0xa6, 0xe3, 0xa1)),
+                red: Some(TermColor::Rgb(0xf3, 0x8b, 0xa8)),
+                blue: Some(TermColor::Rgb(0xcb, 0xa6, 0xf7)),
+                bright_magenta: None,
+                bright_black: Some(TermColor::Indexed(244)),
+            }
+        );
+    }
+
+    #[test]
+    fn color_names() {
+        let none = Table::new();
+        assert_eq!(color("purple", &none), Some(TermColor::Indexed(5)));
+        assert_eq!(color("bright-purple", &none), Some(TermColor::Indexed(13)));
+        assert_eq!(color("bright-black", &none), Some(TermColor::Indexed(8)));
+        assert_eq!(color("3", &none), Some(TermColor::Indexed(3)));
+        assert_eq!(color("bright-3", &none), None);
+        assert_eq!(color("mauve", &none), None);
+        assert_eq!(
+            foreground("bold italic", &none),
+            None,
+            "modifiers only leave the default"
+        );
+        assert_eq!(
+            palette("[custom.jj]\nstyle = \"bold cyan\"\n")
+                .unwrap()
+                .blue,
+            Some(TermColor::Indexed(6))
+        );
+    }
+
+    #[test]
+    fn style_strings() {
+        let mut colors = Table::new();
+        colors.insert("color_aqua".into(), "#689d6a".into());
+        assert_eq!(
+            style("bold fg:#ff8800 bg:color_aqua", &colors),
+            Ok(Style {
+                fg: Some(TermColor::Rgb(0xff, 0x88, 0x00)),
+                bg: Some(TermColor::Rgb(0x68, 0x9d, 0x6a)),
+                bold: true,
+                ..Style::default()
+            })
+        );
+        assert_eq!(
+            style("italic underline bright-purple blink", &colors),
+            Ok(Style {
+                fg: Some(TermColor::Indexed(13)),
+                italic: true,
+                underline: true,
+                ..Style::default()
+            })
+        );
+        assert_eq!(
+            style("bold bg:color_aqua", &Table::new()),
+            Err("unknown color \"color_aqua\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! Char-boundary-safe truncation for ids and names
+//!
+//! Byte slicing (`s[..n]`) panics mid-character, and bookmark, branch and
+//! description text is arbitrary UTF-8. All truncation goes through here,
+//! counting chars rather than bytes.
+
+use std::borrow::Cow;
+
+/// First `n` chars of `s` (all of it when shorter)
+#[must_use]
+pub fn prefix(s: &str, n: usize) -> &str {
+    split_at(s, n).0
+}
+
+/// Split `s` after its first `n` chars
+#[must_use]
+pub fn split_at(s: &str, n: usize) -> (&str, &str) {
+    let idx = s.char_indices().nth(n).map_or(s.len(), |(idx, _)| idx);
+    s.split_at(idx)
+}
+
+/// Shorten `s` to at most `max` chars, ending in `…` when cut (0 = no limit)
+#[must_use]
+pub fn ellipsize(s: &str, max: usize) -> Cow<'_, str> {
+    match max {
+        0 => Cow::Borrowed(s),
+        _ if s.chars().nth(max).is_none() => Cow::Borrowed(s),
+        1 => Cow::Borrowed("…"),
+        _ => Cow::Owned(format!("{}…", prefix(s, max - 1))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_on_char_boundaries() {
+        assert_eq!(prefix("abcdef", 3), "abc");
+        assert_eq!(prefix("ab", 8), "ab");
+        assert_eq!(prefix("日本語ブランチ", 2), "日本");
+        assert_eq!(split_at("ñandú", 2), ("ña", "ndú"));
+        assert_eq!(split_at("🦀", 0), ("", "🦀"));
+    }
+
+    #[test]
+    fn ellipsizes_multibyte_names() {
+        assert_eq!(ellipsize("feature", 0), "feature");
+        assert_eq!(ellipsize("feature", 7), "feature");
+        assert_eq!(ellipsize("feature", 5), "feat…");
+        assert_eq!(ellipsize("über-branch", 4), "übe…");
+        assert_eq!(ellipsize("🦀🦀🦀", 1), "…");
+    }
+}
@@ -0,0 +1,82 @@
+//! Per-phase timings for `--timings` self-profiling
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Ordered phase durations, reported to stderr on request
+///
+/// Recording is always on - an `Instant::now()` pair per phase is noise next
+/// to repo I/O - so collectors don't need a separate untimed code path.
+#[derive(Debug, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Run `f` inside a `phase` span and record its duration under `phase`
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let _span = tracing::debug_span!("phase", name = phase).entered();
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    /// Record a duration measured across several statements
+    pub fn record(&mut self, phase: &'static str, elapsed: Duration) {
+        self.phases.push((phase, elapsed));
+    }
+
+    /// Append phases recorded by another collector (e.g. a worker thread)
+    #[cfg_attr(not(feature = "jj"), allow(dead_code))]
+    pub fn extend(&mut self, other: Self) {
+        self.phases.extend(other.phases);
+    }
+
+    /// Write one aligned line per phase, in recording order
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing to `out`.
+    pub fn report(&self, out: &mut impl Write) -> io::Result<()> {
+        let width = self
+            .phases
+            .iter()
+            .map(|(phase, _)| phase.len())
+            .max()
+            .unwrap_or(0);
+        for (phase, elapsed) in &self.phases {
+            writeln!(
+                out,
+                "{phase:<width$}  {:>8.3}ms",
+                elapsed.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_aligns_phases_in_order() {
+        let mut timings = Timings::default();
+        timings.record("detect", Duration::from_micros(120));
+        timings.record("jj commit", Duration::from_millis(3));
+        let mut out = Vec::new();
+        timings.report(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "detect        0.120ms\njj commit     3.000ms\n"
+        );
+    }
+
+    #[test]
+    fn time_returns_closure_result() {
+        let mut timings = Timings::default();
+        assert_eq!(timings.time("phase", || 42), 42);
+        assert_eq!(timings.phases.len(), 1);
+    }
+}
@@ -0,0 +1,156 @@
+//! `watch` subcommand - re-render whenever the repo changes
+//!
+//! JJ output only changes with the operation log (jj-starship never
+//! snapshots the working copy), so JJ repos watch `.jj` alone. Git repos
+//! (and colocated repos rendering anything from Git) watch the whole
+//! worktree, since any file can change the status.
+
+use jj_starship::config::Colocated;
+use jj_starship::detect::{DetectResult, RepoType};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Longest a burst may hold back a render, in debounce periods, so a
+/// process that keeps writing (a build, a log) still gets updates
+const MAX_SETTLE: u32 = 10;
+
+/// Print `render()` once, then again after every change that alters it
+///
+/// Each render is one line (JSON output is one object per line). Returns
+/// when the watcher stops; writing to a closed `out` fails with
+/// `BrokenPipe`.
+pub fn run(
+    result: &DetectResult,
+    colocated: Colocated,
+    debounce: Duration,
+    mut render: impl FnMut() -> String,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+    for root in watch_roots(result, colocated) {
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(io::Error::other)?;
+    }
+
+    let mut last = render();
+    emit(out, &last)?;
+    while let Ok(event) = rx.recv() {
+        if !is_change(&event) {
+            continue;
+        }
+        // Let a burst (checkout, build, jj operation) settle first
+        let deadline = Instant::now() + debounce * MAX_SETTLE;
+        loop {
+            let wait = debounce.min(deadline.saturating_duration_since(Instant::now()));
+            if wait.is_zero() || rx.recv_timeout(wait).is_err() {
+                break;
+            }
+        }
+        let next = render();
+        if next != last {
+            emit(out, &next)?;
+            last = next;
+        }
+    }
+    Ok(())
+}
+
+/// Directories whose changes can alter the rendered output
+fn watch_roots(result: &DetectResult, colocated: Colocated) -> Vec<PathBuf> {
+    let Some(root) = result.repo_root.as_deref() else {
+        return Vec::new();
+    };
+    match result.repo_type {
+        RepoType::JjColocated if colocated.uses_git() => vec![root.to_path_buf()],
+        RepoType::Jj | RepoType::JjColocated => {
+            let jj_dir = root.join(".jj");
+            let mut roots = vec![jj_dir.clone()];
+            // Secondary workspaces record operations in the shared repo
+            if let Some(store) = &result.store_path
+                && !store.starts_with(&jj_dir)
+            {
+                roots.push(store.clone());
+            }
+            roots
+        }
+        _ => vec![root.to_path_buf()],
+    }
+}
+
+/// Collection reads the repo itself; only writes may trigger a render
+fn is_change(event: &notify::Result<Event>) -> bool {
+    event
+        .as_ref()
+        .is_ok_and(|event| !matches!(event.kind, EventKind::Access(_)))
+}
+
+/// Write one render as a line (an empty line when there's nothing to show)
+fn emit(out: &mut impl Write, render: &str) -> io::Result<()> {
+    out.write_all(render.as_bytes())?;
+    if !render.ends_with('\n') {
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_starship::config::Backend;
+
+    #[test]
+    fn jj_watches_op_log_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let shared = tmp.path().join("main/.jj/repo");
+        let secondary = tmp.path().join("second");
+
+        let result = DetectResult {
+            repo_type: RepoType::Jj,
+            repo_root: Some(secondary.clone()),
+            store_path: Some(shared.clone()),
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let jj_only = Colocated::default();
+        assert_eq!(
+            watch_roots(&result, jj_only),
+            [secondary.join(".jj"), shared]
+        );
+
+        let result = DetectResult {
+            repo_type: RepoType::Git,
+            repo_root: Some(secondary.clone()),
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        assert_eq!(watch_roots(&result, jj_only), [secondary.as_path()]);
+
+        let result = DetectResult {
+            repo_type: RepoType::JjColocated,
+            repo_root: Some(secondary.clone()),
+            store_path: None,
+            dot_dir: None,
+            nested_roots: Vec::new(),
+        };
+        let git_status = Colocated {
+            status: Backend::Git,
+            ..jj_only
+        };
+        assert_eq!(watch_roots(&result, git_status), [secondary]);
+    }
+
+    #[test]
+    fn one_line_per_render() {
+        let mut out = Vec::new();
+        emit(&mut out, "on main").unwrap();
+        emit(&mut out, "{\"jj\":null}\n").unwrap();
+        emit(&mut out, "").unwrap();
+        assert_eq!(out, b"on main\n{\"jj\":null}\n\n");
+    }
+}